@@ -0,0 +1,132 @@
+/// The actions that the editor can perform, such as moving the cursor or inserting a fraction, and their names, which
+/// are used to bind keys to them.
+use std::fmt::{self, Display, Formatter};
+
+use func::FuncType;
+use edit::ButtonID;
+
+/// The constructs that can be inserted by name, other than functions.
+const CONSTRUCTS: &'static [(&'static str, ButtonID)] = &[
+	("pow", ButtonID::Pow), ("square", ButtonID::Square), ("exp", ButtonID::E), ("cbrt", ButtonID::Cbrt),
+	("frac", ButtonID::Frac), ("list", ButtonID::List), ("comma", ButtonID::Comma), ("ans", ButtonID::Ans),
+	("hist", ButtonID::Hist), ("plus-minus", ButtonID::PlusMinus),
+];
+
+/// Ways of moving the cursor.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Motion {
+	Left,
+	Right,
+	Up,
+	Down,
+	WordLeft, // Over a whole number or name
+	WordRight,
+	Start, // Of the expression the cursor is in
+	End,
+	RootStart, // Of the whole expression
+	RootEnd,
+	Out, // To just after the construct the cursor is in
+}
+
+/// Settings that can be switched on and off from the keyboard.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Mode {
+	Shift,
+	Ctrl,
+	Intervals,
+	Live,
+	Angle, // Moves to the next of radians, degrees and gradians
+	DebugStage(u8), // Debug printing of a stage of evaluation, from 1 to 3
+	DebugView,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Action {
+	Move(Motion),
+	Select(Motion),
+	NextSlot, // Moves to the next empty box, e.g. in a fraction
+	PrevSlot,
+	Delete,
+	Backspace,
+	Undo,
+	Redo,
+	Cut,
+	Copy,
+	CopyLatex,
+	Paste,
+	Insert(ButtonID), // Does what pressing the button does
+	Evaluate,
+	NewRow,
+	Store,
+	Toggle(Mode),
+	Open,
+	Save,
+	SaveAs,
+	Export, // Draws the expression being edited to an image file
+	ShowKeys,
+	QuickFix, // Applies the first fix for a problem at the cursor
+}
+
+const DIRECTIONS: &'static [(&'static str, Motion)] = &[
+	("left", Motion::Left), ("right", Motion::Right), ("up", Motion::Up), ("down", Motion::Down),
+	("word-left", Motion::WordLeft), ("word-right", Motion::WordRight), ("start", Motion::Start), ("end", Motion::End),
+	("root-start", Motion::RootStart), ("root-end", Motion::RootEnd), ("out", Motion::Out),
+];
+
+const MODES: &'static [(&'static str, Mode)] = &[
+	("shift", Mode::Shift), ("ctrl", Mode::Ctrl), ("intervals", Mode::Intervals), ("live", Mode::Live), ("angle", Mode::Angle),
+	("debug-1", Mode::DebugStage(1)), ("debug-2", Mode::DebugStage(2)), ("debug-3", Mode::DebugStage(3)), ("debug-view", Mode::DebugView),
+];
+
+const SIMPLE_ACTIONS: &'static [(&'static str, Action)] = &[
+	("delete", Action::Delete), ("backspace", Action::Backspace), ("undo", Action::Undo), ("redo", Action::Redo),
+	("cut", Action::Cut), ("copy", Action::Copy), ("copy-latex", Action::CopyLatex), ("paste", Action::Paste),
+	("next-slot", Action::NextSlot), ("prev-slot", Action::PrevSlot),
+	("evaluate", Action::Evaluate), ("new-row", Action::NewRow), ("store", Action::Store),
+	("open", Action::Open), ("save", Action::Save), ("save-as", Action::SaveAs), ("export", Action::Export),
+	("show-keys", Action::ShowKeys),
+	("quick-fix", Action::QuickFix),
+];
+
+/// Finds the value with the name given in a table of names.
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+	table.iter().find(|&&(n, _)| n == name).map(|&(_, v)| v)
+}
+
+/// Finds the name of a value in a table of names.
+fn name_of<T: PartialEq>(table: &'static [(&'static str, T)], v: &T) -> &'static str {
+	table.iter().find(|&&(_, ref t)| t == v).map_or("?", |&(n, _)| n)
+}
+
+impl Action {
+	/// Parses the name of an action, such as `undo`, `move left`, `insert frac`, `insert sin` or `toggle intervals`.
+	pub fn from_name(s: &str) -> Option<Action> {
+		let mut words = s.split_whitespace();
+		let (verb, arg) = (words.next().unwrap_or(""), words.next());
+		if words.next().is_some() {
+			return None;
+		}
+		match (verb, arg) {
+			("move", Some(dir)) => lookup(DIRECTIONS, dir).map(Action::Move),
+			("select", Some(dir)) => lookup(DIRECTIONS, dir).map(Action::Select),
+			("toggle", Some(mode)) => lookup(MODES, mode).map(Action::Toggle),
+			("insert", Some(name)) => lookup(CONSTRUCTS, name).or(FuncType::from_name(name).map(ButtonID::Func)).map(Action::Insert),
+			(verb, None) => lookup(SIMPLE_ACTIONS, verb),
+			_ => None,
+		}
+	}
+}
+
+impl Display for Action {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			&Action::Move(dir) => write!(f, "move {}", name_of(DIRECTIONS, &dir)),
+			&Action::Select(dir) => write!(f, "select {}", name_of(DIRECTIONS, &dir)),
+			&Action::Toggle(mode) => write!(f, "toggle {}", name_of(MODES, &mode)),
+			&Action::Insert(ButtonID::Func(FuncType::Sqrt)) => write!(f, "insert sqrt"),
+			&Action::Insert(ButtonID::Func(func)) => write!(f, "insert {}", func),
+			&Action::Insert(id) => write!(f, "insert {}", name_of(CONSTRUCTS, &id)),
+			action => write!(f, "{}", name_of(SIMPLE_ACTIONS, action)),
+		}
+	}
+}
@@ -0,0 +1,212 @@
+/// Evaluates expressions from the command line, for use in shell scripts, e.g. `equator-cli --degrees "a=30" "2sin(a)"`.
+///
+/// Each expression can use the variables assigned to by the expressions before it, and `#n` refers to the result of the
+/// nth, as long as it is one of the last 1000. With no expressions given, they are read from stdin, one per line.
+///
+/// With `--export`, the last expression is also drawn to an image, e.g. for pasting into a report.
+extern crate equator;
+
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+#[cfg(feature = "render")]
+use std::path::PathBuf;
+
+use equator::session::Session;
+use equator::com::{self, EvalMode, TrigMode};
+use equator::val::Value;
+use equator::save::value_to_json;
+#[cfg(feature = "render")]
+use equator::render::{self, ExportOptions, ImageFormat, export_expression};
+
+const USAGE: &'static str = "usage: equator-cli [options] [expression...]
+
+Evaluates each expression in turn, printing its result. With no expressions, they are read from stdin, one per line.
+
+options:
+  --radians, --degrees, --gradians   the units of angles (radians by default)
+  --intervals                        calculate with intervals that are guaranteed to contain the exact result
+  --format plain|expr|json           how results are printed (plain by default)
+  -h, --help                         show this help
+
+export options:
+  --export <file>                    draw the last expression and its result to a .png, .svg or .pdf file
+  --scale <n>                        how many times larger than on screen to draw it (1 by default)
+  --padding <n>                      the space to leave around it (8 by default)
+  --foreground <#rrggbb>             the colour to draw it in (black by default)
+  --background <#rrggbb>|transparent the colour behind it (white by default)
+  --no-result                        draw the expression without its result";
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Format {
+	Plain, // Just the result
+	Expr,  // The expression, then `=` and the result
+	Json,  // The result as it is saved in workspace files
+}
+
+fn print_result(format: Format, text: &str, v: &Value) {
+	match format {
+		Format::Plain => println!("{}", v),
+		Format::Expr => println!("{} = {}", text, v),
+		Format::Json => println!("{}", value_to_json(v)),
+	}
+}
+
+/// Evaluates `text` and prints its result, or exits if it is an error. `line` is the line of stdin it was read from,
+/// if it was.
+fn evaluate(session: &mut Session, format: Format, text: &str, line: Option<usize>) {
+	let text = text.trim();
+	if text.is_empty() {
+		return;
+	}
+	match session.evaluate_in_place(text) {
+		Ok(v) => print_result(format, text, &v),
+		Err(e) => {
+			// Say which line it was, as the expression may be far back in a script
+			match line {
+				Some(n) => { let _ = writeln!(io::stderr(), "equator-cli: line {}: error: {}", n, e); },
+				None => { let _ = writeln!(io::stderr(), "equator-cli: '{}': error: {}", text, e); },
+			}
+			process::exit(1);
+		},
+	}
+}
+
+fn usage_error(msg: &str) -> ! {
+	let _ = writeln!(io::stderr(), "equator-cli: {}\n\n{}", msg, USAGE);
+	process::exit(2);
+}
+
+/// Where to export the last expression to, and how to draw it.
+#[cfg(feature = "render")]
+struct Export {
+	file: Option<(PathBuf, ImageFormat)>,
+	options: ExportOptions,
+	result: bool,
+}
+
+#[cfg(feature = "render")]
+impl Export {
+	fn new() -> Export {
+		Export{ file: None, options: ExportOptions::new(), result: true }
+	}
+
+	/// Reads one of the export options, which is followed by `value` if it takes one.
+	fn set_option(&mut self, opt: &str, value: Option<String>) {
+		if opt == "--no-result" {
+			self.result = false;
+			return;
+		}
+		let value = value.unwrap_or_else(|| usage_error(&format!("{} needs a value", opt)));
+		let number = || match value.parse::<f64>() {
+			Ok(n) if n >= 0.0 => n,
+			_ => usage_error(&format!("{} needs a number, not '{}'", opt, value)),
+		};
+		let colour = || render::parse_colour(&value)
+			.unwrap_or_else(|| usage_error(&format!("{} needs a colour such as #ff8000, not '{}'", opt, value)));
+		match opt {
+			"--export" => {
+				let path = PathBuf::from(&value);
+				let format = ImageFormat::from_path(&path)
+					.unwrap_or_else(|| usage_error(&format!("can't tell the image format of '{}' (use .png, .svg or .pdf)", value)));
+				self.file = Some((path, format));
+			},
+			"--scale" => match number() {
+				n if n > 0.0 => self.options.scale = n,
+				_ => usage_error("--scale needs a number above 0"),
+			},
+			"--padding" => self.options.padding = number(),
+			"--foreground" => self.options.foreground = colour(),
+			"--background" if value == "transparent" => self.options.background = None,
+			"--background" => self.options.background = Some(colour()),
+			_ => usage_error(&format!("unknown option '{}'", opt)),
+		}
+	}
+
+	/// Draws the expression that was evaluated last, if an image was asked for.
+	fn write(&self, session: &mut Session) {
+		let (path, format) = match self.file {
+			Some((ref path, format)) => (path, format),
+			None => return,
+		};
+		if session.editor().root_ex.borrow().tokens.is_empty() {
+			let _ = writeln!(io::stderr(), "equator-cli: there is no expression to export");
+			process::exit(1);
+		}
+		let result = session.sheet.rows[session.sheet.current].result.clone();
+		let result = if self.result { Some(&result) } else { None };
+		if let Err(e) = export_expression(session.editor(), result, format, &self.options, path) {
+			let _ = writeln!(io::stderr(), "equator-cli: could not export to '{}': {}", path.display(), e);
+			process::exit(1);
+		}
+	}
+}
+
+/// Exporting needs cairo, which this build doesn't have.
+#[cfg(not(feature = "render"))]
+struct Export;
+
+#[cfg(not(feature = "render"))]
+impl Export {
+	fn new() -> Export {
+		Export
+	}
+	fn set_option(&mut self, opt: &str, _value: Option<String>) {
+		usage_error(&format!("{} needs Equator to be built with the render feature", opt));
+	}
+	fn write(&self, _session: &mut Session) {}
+}
+
+fn main() {
+	// Only the results go to stdout
+	unsafe { com::debug_print_commands = false; }
+	let mut session = Session::new();
+	let mut format = Format::Plain;
+	let mut exprs = Vec::new();
+	let mut export = Export::new();
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--radians"  => session.ctx.vm.set_trig_mode(TrigMode::Radians),
+			"--degrees"  => session.ctx.vm.set_trig_mode(TrigMode::Degrees),
+			"--gradians" => session.ctx.vm.set_trig_mode(TrigMode::Gradians),
+			"--intervals" => session.ctx.vm.set_mode(EvalMode::Interval),
+			"--format" => format = match args.next().as_ref().map(|s| s.as_str()) {
+				Some("plain") => Format::Plain,
+				Some("expr") => Format::Expr,
+				Some("json") => Format::Json,
+				Some(f) => usage_error(&format!("unknown format '{}'", f)),
+				None => usage_error("--format needs a format"),
+			},
+			"--no-result" => export.set_option(&arg, None),
+			"--export" | "--scale" | "--padding" | "--foreground" | "--background" => export.set_option(&arg, args.next()),
+			"-h" | "--help" => {
+				println!("{}", USAGE);
+				return;
+			},
+			// Expressions can start with a minus, e.g. `-2^2`
+			a if a.starts_with("--") => usage_error(&format!("unknown option '{}'", a)),
+			_ => exprs.push(arg),
+		}
+	}
+
+	if exprs.is_empty() {
+		// Each line is evaluated as soon as it is read, so that programs piping expressions in get each result back
+		// before sending the next expression
+		let stdin = io::stdin();
+		for (i, line) in stdin.lock().lines().enumerate() {
+			match line {
+				Ok(line) => evaluate(&mut session, format, &line, Some(i + 1)),
+				Err(e) => {
+					let _ = writeln!(io::stderr(), "equator-cli: could not read stdin: {}", e);
+					process::exit(1);
+				},
+			}
+		}
+	} else {
+		for text in &exprs {
+			evaluate(&mut session, format, text, None);
+		}
+	}
+	export.write(&mut session);
+}
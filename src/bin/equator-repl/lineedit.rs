@@ -0,0 +1,307 @@
+/// A small line editor with history, for when stdin is a terminal. Otherwise lines are read as they are.
+///
+/// Keys: Left/Right (or Ctrl-B/Ctrl-F) move the cursor, Home/End (or Ctrl-A/Ctrl-E) move to the ends of the line,
+/// Up/Down go through the history, Ctrl-K and Ctrl-U delete to the end and start of the line, Ctrl-C abandons the line
+/// and Ctrl-D at the start of an empty line ends the input.
+use std::io::{self, BufRead, Write};
+
+pub struct LineEditor {
+	history: Vec<String>,
+}
+
+/// What happened to the line after a key was pressed.
+enum Edit {
+	Continue,
+	Done,
+	Cancel,
+	Eof,
+}
+
+/// The line being edited, with the cursor as a position in `chars`.
+struct Line {
+	chars: Vec<char>,
+	cursor: usize,
+	/// The position in the history being shown. It is the history's length for the new line.
+	hist_pos: usize,
+	/// The new line, kept while going through the history.
+	new_line: Vec<char>,
+}
+
+impl LineEditor {
+	pub fn new() -> LineEditor {
+		LineEditor{ history: Vec::new() }
+	}
+
+	/// Adds `line` to the end of the history, unless it is the same as the last line added.
+	pub fn add_history(&mut self, line: &str) {
+		if line.is_empty() || self.history.last().map_or(false, |l| l == line) {
+			return;
+		}
+		self.history.push(line.to_string());
+	}
+
+	/// Reads a line, showing `prompt` before it. Returns `None` at the end of the input.
+	pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+		match raw::RawMode::enable() {
+			Some(_raw) => self.edit_line(prompt),
+			None => {
+				// The prompt would be mixed in with the output when reading from a file or pipe
+				if raw::stdin_is_terminal() {
+					print!("{}", prompt);
+					let _ = io::stdout().flush();
+				}
+				let mut s = String::new();
+				match io::stdin().lock().read_line(&mut s) {
+					Ok(0) | Err(_) => None,
+					Ok(_) => Some(s.trim_right_matches(|c| c == '\n' || c == '\r').to_string()),
+				}
+			},
+		}
+	}
+
+	fn edit_line(&mut self, prompt: &str) -> Option<String> {
+		let mut line = Line{ chars: Vec::new(), cursor: 0, hist_pos: self.history.len(), new_line: Vec::new() };
+		redraw(prompt, &line);
+		loop {
+			let c = match raw::read_char() {
+				Some(c) => c,
+				None => return None,
+			};
+			match self.handle_key(&mut line, c) {
+				Edit::Continue => redraw(prompt, &line),
+				Edit::Done => {
+					print!("\r\n");
+					let _ = io::stdout().flush();
+					return Some(line.chars.iter().cloned().collect());
+				},
+				Edit::Cancel => {
+					print!("^C\r\n");
+					line = Line{ chars: Vec::new(), cursor: 0, hist_pos: self.history.len(), new_line: Vec::new() };
+					redraw(prompt, &line);
+				},
+				Edit::Eof => {
+					print!("\r\n");
+					let _ = io::stdout().flush();
+					return None;
+				},
+			}
+		}
+	}
+
+	fn handle_key(&self, line: &mut Line, c: char) -> Edit {
+		match c {
+			'\r' | '\n' => return Edit::Done,
+			'\x03' => return Edit::Cancel,
+			'\x04' if line.chars.is_empty() => return Edit::Eof,
+			'\x04' => line.delete(),
+			'\x7f' | '\x08' => line.backspace(),
+			'\x01' => line.cursor = 0,
+			'\x05' => line.cursor = line.chars.len(),
+			'\x02' => line.left(),
+			'\x06' => line.right(),
+			'\x0b' => { let cursor = line.cursor; line.chars.truncate(cursor); },
+			'\x15' => { let cursor = line.cursor; line.chars.drain(..cursor); line.cursor = 0; },
+			'\x1b' => return self.handle_escape(line),
+			_ if c.is_control() => {},
+			_ => {
+				line.chars.insert(line.cursor, c);
+				line.cursor += 1;
+			},
+		}
+		Edit::Continue
+	}
+
+	/// Handles the rest of an escape sequence, such as `ESC [ A` for Up.
+	fn handle_escape(&self, line: &mut Line) -> Edit {
+		match raw::read_char() {
+			Some('[') | Some('O') => {},
+			Some(_) => return Edit::Continue,
+			None => return Edit::Eof,
+		}
+		// The sequence ends with the first character that isn't part of its argument, e.g. `ESC [ 3 ~` for Delete
+		let mut arg = String::new();
+		let end = loop {
+			match raw::read_char() {
+				Some(d) if d.is_digit(10) || d == ';' => arg.push(d),
+				Some(c) => break c,
+				None => return Edit::Eof,
+			}
+		};
+		match end {
+			'A' => self.history_prev(line),
+			'B' => self.history_next(line),
+			'C' => line.right(),
+			'D' => line.left(),
+			'H' => line.cursor = 0,
+			'F' => line.cursor = line.chars.len(),
+			'~' => match arg.as_str() {
+				"1" | "7" => line.cursor = 0,
+				"4" | "8" => line.cursor = line.chars.len(),
+				"3" => line.delete(),
+				_ => {},
+			},
+			_ => {},
+		}
+		Edit::Continue
+	}
+
+	fn history_prev(&self, line: &mut Line) {
+		if line.hist_pos == 0 {
+			return;
+		}
+		if line.hist_pos == self.history.len() {
+			line.new_line = line.chars.clone();
+		}
+		line.hist_pos -= 1;
+		line.set(self.history[line.hist_pos].chars().collect());
+	}
+
+	fn history_next(&self, line: &mut Line) {
+		if line.hist_pos >= self.history.len() {
+			return;
+		}
+		line.hist_pos += 1;
+		let chars = match self.history.get(line.hist_pos) {
+			Some(s) => s.chars().collect(),
+			None => line.new_line.clone(),
+		};
+		line.set(chars);
+	}
+}
+
+impl Line {
+	fn set(&mut self, chars: Vec<char>) {
+		self.cursor = chars.len();
+		self.chars = chars;
+	}
+	fn left(&mut self) {
+		if self.cursor > 0 {
+			self.cursor -= 1;
+		}
+	}
+	fn right(&mut self) {
+		if self.cursor < self.chars.len() {
+			self.cursor += 1;
+		}
+	}
+	fn backspace(&mut self) {
+		if self.cursor > 0 {
+			self.cursor -= 1;
+			self.chars.remove(self.cursor);
+		}
+	}
+	fn delete(&mut self) {
+		if self.cursor < self.chars.len() {
+			self.chars.remove(self.cursor);
+		}
+	}
+}
+
+/// Draws the prompt and line over the current terminal line, and puts the cursor where it is in the line.
+fn redraw(prompt: &str, line: &Line) {
+	let text: String = line.chars.iter().cloned().collect();
+	let mut out = format!("\r{}{}\x1b[K\r", prompt, text);
+	let col = prompt.chars().count() + line.cursor;
+	if col > 0 {
+		out.push_str(&format!("\x1b[{}C", col));
+	}
+	print!("{}", out);
+	let _ = io::stdout().flush();
+}
+
+#[cfg(unix)]
+mod raw {
+	use libc;
+	use std::mem;
+
+	/// Puts the terminal in raw mode while it exists, so that keys are read as they are pressed.
+	pub struct RawMode {
+		orig: libc::termios,
+	}
+
+	impl RawMode {
+		/// Returns `None` if stdin or stdout isn't a terminal.
+		pub fn enable() -> Option<RawMode> {
+			unsafe {
+				if libc::isatty(libc::STDIN_FILENO) == 0 || libc::isatty(libc::STDOUT_FILENO) == 0 {
+					return None;
+				}
+				let mut orig: libc::termios = mem::zeroed();
+				if libc::tcgetattr(libc::STDIN_FILENO, &mut orig) != 0 {
+					return None;
+				}
+				let mut raw = orig;
+				raw.c_iflag &= !(libc::BRKINT | libc::ICRNL | libc::INPCK | libc::ISTRIP | libc::IXON);
+				raw.c_oflag &= !libc::OPOST;
+				raw.c_cflag |= libc::CS8;
+				raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+				raw.c_cc[libc::VMIN] = 1;
+				raw.c_cc[libc::VTIME] = 0;
+				if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) != 0 {
+					return None;
+				}
+				Some(RawMode{ orig: orig })
+			}
+		}
+	}
+
+	pub fn stdin_is_terminal() -> bool {
+		unsafe { libc::isatty(libc::STDIN_FILENO) != 0 }
+	}
+
+	impl Drop for RawMode {
+		fn drop(&mut self) {
+			unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.orig); }
+		}
+	}
+
+	fn read_byte() -> Option<u8> {
+		let mut b = 0u8;
+		let n = unsafe { libc::read(libc::STDIN_FILENO, &mut b as *mut u8 as *mut libc::c_void, 1) };
+		if n == 1 { Some(b) } else { None }
+	}
+
+	/// Reads a character from stdin, decoding it from UTF-8. Returns `None` at the end of the input.
+	pub fn read_char() -> Option<char> {
+		let first = match read_byte() {
+			Some(b) => b,
+			None => return None,
+		};
+		let len = match first {
+			0x00 ... 0x7f => return Some(first as char),
+			0xc0 ... 0xdf => 2,
+			0xe0 ... 0xef => 3,
+			0xf0 ... 0xf7 => 4,
+			_ => return Some('\u{fffd}'),
+		};
+		let mut bytes = vec![first];
+		for _ in 1..len {
+			match read_byte() {
+				Some(b) => bytes.push(b),
+				None => return None,
+			}
+		}
+		Some(String::from_utf8(bytes).ok().and_then(|s| s.chars().next()).unwrap_or('\u{fffd}'))
+	}
+}
+
+#[cfg(not(unix))]
+mod raw {
+	/// Raw mode is only supported on Unix, so lines are always read as they are elsewhere.
+	pub struct RawMode;
+
+	impl RawMode {
+		pub fn enable() -> Option<RawMode> {
+			None
+		}
+	}
+
+	/// Always shows the prompt, as this can't tell whether it is needed.
+	pub fn stdin_is_terminal() -> bool {
+		true
+	}
+
+	pub fn read_char() -> Option<char> {
+		None
+	}
+}
@@ -0,0 +1,134 @@
+/// An interactive calculator for the terminal. Each line is evaluated as a row of a worksheet, so it can use the
+/// variables assigned to before it, and the expression is printed in 2D with its result, e.g.
+///
+/// ```text
+/// > (1+x)/2
+///  1 + x
+/// ─────── = 1.5
+///    2
+/// ```
+///
+/// Lines starting with `:` are commands, such as `:vars` and `:mode deg`. `:help` lists them.
+extern crate equator;
+#[cfg(unix)]
+extern crate libc;
+
+mod lineedit;
+
+use std::env;
+use std::path::Path;
+
+use equator::session::Session;
+use equator::com::{self, EvalMode, TrigMode};
+use equator::layout::{Block, layout_vexpr};
+use equator::save::{Workspace, save_workspace};
+
+use lineedit::LineEditor;
+
+const PROMPT: &'static str = "> ";
+
+const HELP: &'static str = "Enter an expression to evaluate it, e.g. `a=sqrt(2)/2` or `sin(pi/4) - a`.
+
+commands:
+  :vars                      list the variables that have been assigned to
+  :mode deg|rad|grad         set the units of angles
+  :mode intervals|normal     calculate with intervals, or with single numbers
+  :mode                      show the current modes
+  :save <path>               save the worksheet of everything entered, and the variables
+  :help                      show this help
+  :quit                      exit (as does Ctrl-D)";
+
+/// Prints the expression being edited in 2D, with `= result` on its baseline.
+fn print_row(session: &mut Session, result: &str) {
+	let mut block = layout_vexpr(&session.editor().root_ex);
+	block.append(&Block::text(&format!(" = {}", result)));
+	println!("{}", block);
+}
+
+fn mode_name(session: &Session) -> String {
+	let trig = match session.ctx.vm.get_trig_mode() {
+		TrigMode::Radians => "radians",
+		TrigMode::Degrees => "degrees",
+		TrigMode::Gradians => "gradians",
+	};
+	let eval = match session.ctx.vm.get_mode() {
+		EvalMode::Normal => "normal",
+		EvalMode::Interval => "intervals",
+	};
+	format!("{}, {}", trig, eval)
+}
+
+/// Runs a line starting with `:`. Returns false if the REPL should exit.
+fn run_command(session: &mut Session, line: &str) -> bool {
+	let mut words = line[1..].split_whitespace();
+	let cmd = words.next().unwrap_or("");
+	let arg = words.next();
+	match cmd {
+		"q" | "quit" | "exit" => return false,
+		"h" | "help" => println!("{}", HELP),
+		"vars" => {
+			let vars = session.ctx.vm.get_vars();
+			if vars.is_empty() {
+				println!("no variables have been assigned to");
+			}
+			for (name, v) in vars {
+				println!("{} = {}", name, v);
+			}
+		},
+		"mode" => {
+			match arg {
+				Some("deg") | Some("degrees") => session.ctx.vm.set_trig_mode(TrigMode::Degrees),
+				Some("rad") | Some("radians") => session.ctx.vm.set_trig_mode(TrigMode::Radians),
+				Some("grad") | Some("gradians") => session.ctx.vm.set_trig_mode(TrigMode::Gradians),
+				Some("intervals") => session.ctx.vm.set_mode(EvalMode::Interval),
+				Some("normal") => session.ctx.vm.set_mode(EvalMode::Normal),
+				Some(m) => {
+					println!("error: unknown mode '{}'", m);
+					return true;
+				},
+				None => {},
+			}
+			println!("mode: {}", mode_name(session));
+		},
+		"save" => match arg {
+			Some(path) => {
+				let ws = Workspace::current(&session.sheet, &session.ctx.vm);
+				match save_workspace(Path::new(path), &ws) {
+					Ok(()) => println!("saved to {}", path),
+					Err(e) => println!("error: could not save {}: {}", path, e),
+				}
+			},
+			None => println!("error: :save needs a path"),
+		},
+		_ => println!("error: unknown command ':{}' (:help lists them)", cmd),
+	}
+	true
+}
+
+fn main() {
+	// The evaluator's debug output would be mixed in with the results
+	unsafe { com::debug_print_commands = false; }
+	if env::args().skip(1).any(|a| a == "-h" || a == "--help") {
+		println!("usage: equator-repl\n\n{}", HELP);
+		return;
+	}
+	let mut session = Session::new();
+	let mut editor = LineEditor::new();
+	while let Some(line) = editor.read_line(PROMPT) {
+		let line = line.trim();
+		if line.is_empty() {
+			continue;
+		}
+		editor.add_history(line);
+		if line.starts_with(':') {
+			if !run_command(&mut session, line) {
+				break;
+			}
+			continue;
+		}
+		match session.evaluate_text(line) {
+			Ok(v) => print_row(&mut session, &v.to_string()),
+			Err(e) => println!("error: {}", e),
+		}
+	}
+}
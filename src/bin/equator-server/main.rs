@@ -0,0 +1,140 @@
+/// Serves Equator's evaluator to other programs over JSON-RPC 2.0, one request or response per line, e.g.
+///
+/// ```text
+/// --> {"jsonrpc": "2.0", "method": "evaluate", "params": {"text": "2^10"}, "id": 1}
+/// <-- {"jsonrpc":"2.0","result":{"value":"1024","text":"1024"},"id":1}
+/// ```
+///
+/// Requests are read from stdin, or from connections to a TCP or Unix socket, each of which has a session of its own.
+/// The methods are described in `rpc`.
+extern crate equator;
+
+mod rpc;
+
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process;
+use std::thread;
+
+use equator::com;
+
+use rpc::Server;
+
+const USAGE: &'static str = "usage: equator-server [--tcp <address> | --unix <path>]
+
+Answers JSON-RPC 2.0 requests, one per line, from stdin, or from each connection to a socket.
+
+options:
+  --tcp <address>    listen on a TCP address, e.g. 127.0.0.1:7010
+  --unix <path>      listen on a Unix socket
+  -h, --help         show this help";
+
+fn usage_error(msg: &str) -> ! {
+	let _ = writeln!(io::stderr(), "equator-server: {}\n\n{}", msg, USAGE);
+	process::exit(2);
+}
+
+fn fail(msg: &str) -> ! {
+	let _ = writeln!(io::stderr(), "equator-server: {}", msg);
+	process::exit(1);
+}
+
+/// Answers the requests read from `input` until it ends, with a new session.
+fn serve<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+	let mut server = Server::new();
+	for line in input.lines() {
+		let line = try!(line);
+		if line.trim().is_empty() {
+			continue;
+		}
+		if let Some(response) = server.handle_line(&line) {
+			try!(writeln!(output, "{}", response));
+			try!(output.flush());
+		}
+	}
+	Ok(())
+}
+
+fn serve_tcp(address: &str) {
+	let listener = match TcpListener::bind(address) {
+		Ok(listener) => listener,
+		Err(e) => fail(&format!("could not listen on {}: {}", address, e)),
+	};
+	for stream in listener.incoming() {
+		let stream = match stream {
+			Ok(stream) => stream,
+			Err(e) => {
+				let _ = writeln!(io::stderr(), "equator-server: could not accept a connection: {}", e);
+				continue;
+			},
+		};
+		thread::spawn(move || {
+			let res = stream.try_clone().and_then(|input| serve(BufReader::new(input), stream));
+			if let Err(e) = res {
+				let _ = writeln!(io::stderr(), "equator-server: connection closed: {}", e);
+			}
+		});
+	}
+}
+
+#[cfg(unix)]
+fn serve_unix(path: &str) {
+	use std::os::unix::net::UnixListener;
+
+	let listener = match UnixListener::bind(path) {
+		Ok(listener) => listener,
+		Err(e) => fail(&format!("could not listen on {}: {}", path, e)),
+	};
+	for stream in listener.incoming() {
+		let stream = match stream {
+			Ok(stream) => stream,
+			Err(e) => {
+				let _ = writeln!(io::stderr(), "equator-server: could not accept a connection: {}", e);
+				continue;
+			},
+		};
+		thread::spawn(move || {
+			let res = stream.try_clone().and_then(|input| serve(BufReader::new(input), stream));
+			if let Err(e) = res {
+				let _ = writeln!(io::stderr(), "equator-server: connection closed: {}", e);
+			}
+		});
+	}
+}
+
+#[cfg(not(unix))]
+fn serve_unix(_path: &str) {
+	usage_error("Unix sockets are only supported on Unix");
+}
+
+fn main() {
+	// The evaluator's debug output would be mixed in with the responses
+	unsafe { com::debug_print_commands = false; }
+	let mut tcp = None;
+	let mut unix = None;
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--tcp" => tcp = Some(args.next().unwrap_or_else(|| usage_error("--tcp needs an address"))),
+			"--unix" => unix = Some(args.next().unwrap_or_else(|| usage_error("--unix needs a path"))),
+			"-h" | "--help" => {
+				println!("{}", USAGE);
+				return;
+			},
+			a => usage_error(&format!("unknown option '{}'", a)),
+		}
+	}
+	match (tcp, unix) {
+		(Some(_), Some(_)) => usage_error("only one of --tcp and --unix can be given"),
+		(Some(address), None) => serve_tcp(&address),
+		(None, Some(path)) => serve_unix(&path),
+		(None, None) => {
+			let stdin = io::stdin();
+			let stdout = io::stdout();
+			if let Err(e) = serve(stdin.lock(), stdout.lock()) {
+				fail(&format!("could not read requests: {}", e));
+			}
+		},
+	}
+}
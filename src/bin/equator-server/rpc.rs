@@ -0,0 +1,585 @@
+/// JSON-RPC 2.0 requests, and the methods that a session answers. Parameters are given by name, and expressions are
+/// given as text, as they are typed in.
+///
+/// - `evaluate {text}` evaluates an expression, which can assign to a variable (`a=2`) and use earlier results (`#1`).
+///   Returns `{value, text}`, where `value` is as it is saved in workspace files and `text` is as it is displayed.
+///   Only the last 1000 results are kept, so `#n` is an error once the nth result is older than that.
+/// - `set_var {name, value}` evaluates `value` and assigns it to the variable `name` (e.g. `x`, or `x₁` or `x_1`).
+/// - `get_var {name}` returns the value of a variable, and `get_vars` returns `[{name, value, text}]` for every variable.
+/// - `define_function {name, params, body}` defines a function of the variables in `params`, e.g.
+///   `{"name": "f", "params": ["x"], "body": "x^2+1"}`. It can be called in the expressions given to the other
+///   methods, as `f(2)`, where the call is replaced by the body with `(2)` in place of `x`. Functions can call each
+///   other, and a function wins over a variable with the same name. It can also be called with
+///   `call_function {name, args}`, which evaluates the body with each argument assigned to its parameter.
+/// - `set_trig_mode {mode}` sets the units of angles to `radians`, `degrees` or `gradians`, and `get_trig_mode` gets
+///   them.
+/// - `render {text, format}` draws an expression as an image, where `format` is `svg`, `png` or `pdf`. Returns
+///   `{format, width, height, data}`, where `data` is the SVG document, or the PNG or PDF file in base64.
+///
+/// Errors in expressions have the code `EVAL_ERROR`, and their `data` is `{kind, text}`, where `kind` is the name of
+/// the `ParseError`, with `position` (the character in the text) if the text couldn't be parsed, or `part` (the part
+/// of the expression that couldn't be calculated) if it is known. Calls to defined functions with the wrong number of
+/// arguments, or that are nested too deeply (as in a function that calls itself), have the `kind` `function_call`.
+use std::collections::HashMap;
+
+use equator::json::{self, Json};
+use equator::session::Session;
+use equator::com::{self, TrigMode, VarName};
+use equator::err::ParseError;
+use equator::parse::parse_text;
+use equator::save::value_to_json;
+use equator::val::Value;
+use equator::func::FuncType;
+use equator::vis::{DelimType, VExpr, VExprRef, VToken, copy_tokens, copy_vexpr, subscript_digits, tokens_to_text};
+
+// The error codes from the JSON-RPC specification
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+// Equator's own error codes
+const EVAL_ERROR: i32 = 1; // An expression couldn't be parsed or calculated
+const RENDER_ERROR: i32 = 2; // An expression couldn't be drawn
+
+/// How deeply calls to defined functions can be nested, counting those in the bodies of functions.
+const MAX_CALL_DEPTH: usize = 64;
+/// How many calls to defined functions an expression can expand to, so that functions that call each other many
+/// times don't use up the memory.
+const MAX_CALLS: usize = 10000;
+
+pub struct RpcError {
+	code: i32,
+	message: String,
+	data: Option<Json>,
+}
+
+impl RpcError {
+	fn new(code: i32, message: String) -> RpcError {
+		RpcError{ code: code, message: message, data: None }
+	}
+
+	fn invalid_params(message: String) -> RpcError {
+		RpcError::new(INVALID_PARAMS, message)
+	}
+
+	/// An error in the expression `text`. `ex` is the expression it was parsed into, or `None` if the error is from
+	/// parsing it, in which case the position of the error is in the text.
+	fn from_parse_error(e: &ParseError, text: &str, ex: Option<&VExprRef>) -> RpcError {
+		let mut data = vec![
+			("kind".to_string(), Json::Str(e.name().to_string())),
+			("text".to_string(), Json::Str(text.to_string())),
+		];
+		match (ex, e) {
+			(None, &ParseError::IllegalChar(_, pos)) | (None, &ParseError::UnmatchedParen(pos)) => {
+				data.push(("position".to_string(), Json::Num(pos as f64)));
+			},
+			(Some(ex), _) => if let Some(span) = com::error_span(ex, e) {
+				let part = span.ex.borrow().tokens.get(span.start..span.end).map(tokens_to_text);
+				if let Some(part) = part {
+					data.push(("part".to_string(), Json::Str(part)));
+				}
+			},
+			_ => {},
+		}
+		RpcError{ code: EVAL_ERROR, message: format!("{}", e), data: Some(Json::Object(data)) }
+	}
+
+	/// An error in calling a defined function, in the expression `ex`.
+	fn from_call_error(message: String, ex: &VExprRef) -> RpcError {
+		let data = vec![
+			("kind".to_string(), Json::Str("function_call".to_string())),
+			("text".to_string(), Json::Str(tokens_to_text(&ex.borrow().tokens))),
+		];
+		RpcError{ code: EVAL_ERROR, message: message, data: Some(Json::Object(data)) }
+	}
+
+	fn to_json(&self) -> Json {
+		let mut members = vec![
+			("code".to_string(), Json::Num(self.code as f64)),
+			("message".to_string(), Json::Str(self.message.clone())),
+		];
+		if let Some(ref data) = self.data {
+			members.push(("data".to_string(), data.clone()));
+		}
+		Json::Object(members)
+	}
+}
+
+/// A function defined with `define_function`.
+struct Function {
+	params: Vec<VarName>,
+	body: VExprRef,
+}
+
+/// What a connection talks to: a session of its own, with its own variables and history, and the functions defined.
+pub struct Server {
+	session: Session,
+	functions: HashMap<String, Function>,
+}
+
+impl Server {
+	pub fn new() -> Server {
+		Server{ session: Session::new(), functions: HashMap::new() }
+	}
+
+	/// Handles a line of input, which is a request or a batch of them. Returns the line to send back, if any, as
+	/// notifications aren't answered.
+	pub fn handle_line(&mut self, line: &str) -> Option<String> {
+		let response = match json::parse(line) {
+			Ok(Json::Array(ref reqs)) if reqs.is_empty() => Some(error_response(Json::Null, &RpcError::new(INVALID_REQUEST, "empty batch".to_string()))),
+			Ok(Json::Array(ref reqs)) => {
+				let responses: Vec<Json> = reqs.iter().filter_map(|req| self.handle_request(req)).collect();
+				if responses.is_empty() { None } else { Some(Json::Array(responses)) }
+			},
+			Ok(ref req) => self.handle_request(req),
+			Err(e) => Some(error_response(Json::Null, &RpcError::new(PARSE_ERROR, format!("invalid JSON: {}", e)))),
+		};
+		response.map(|r| r.to_compact_string())
+	}
+
+	fn handle_request(&mut self, req: &Json) -> Option<Json> {
+		let id = req.get("id").cloned();
+		let method = match req.get("method").and_then(Json::as_str) {
+			Some(method) if req.get("jsonrpc").and_then(Json::as_str) == Some("2.0") => method,
+			_ => return Some(error_response(id.unwrap_or(Json::Null), &RpcError::new(INVALID_REQUEST, "not a JSON-RPC 2.0 request".to_string()))),
+		};
+		let res = match req.get("params") {
+			None | Some(&Json::Object(_)) => self.call(method, req.get("params").unwrap_or(&Json::Null)),
+			Some(_) => Err(RpcError::invalid_params("parameters must be given by name".to_string())),
+		};
+		// Requests without an id are notifications, which get no response
+		let id = match id {
+			Some(id) => id,
+			None => return None,
+		};
+		Some(match res {
+			Ok(result) => Json::Object(vec![
+				("jsonrpc".to_string(), Json::Str("2.0".to_string())),
+				("result".to_string(), result),
+				("id".to_string(), id),
+			]),
+			Err(e) => error_response(id, &e),
+		})
+	}
+
+	fn call(&mut self, method: &str, params: &Json) -> Result<Json, RpcError> {
+		match method {
+			"evaluate" => {
+				let text = try!(str_param(params, "text"));
+				let ex = try!(self.expand_calls(&try!(parse(text))));
+				match self.session.evaluate_expression_in_place(&ex) {
+					Ok(v) => Ok(value_result(&v)),
+					Err(e) => {
+						let ex = self.session.editor().root_ex.clone();
+						Err(RpcError::from_parse_error(&e, text, Some(&ex)))
+					},
+				}
+			},
+			"set_var" => {
+				let id = try!(var_param(params, "name"));
+				let v = try!(self.evaluate(try!(str_param(params, "value"))));
+				self.session.ctx.vm.set_var(id, v.clone());
+				Ok(value_result(&v))
+			},
+			"get_var" => {
+				let id = try!(var_param(params, "name"));
+				match self.session.ctx.vm.get_var(id) {
+					Some(v) => Ok(value_result(&v)),
+					None => Err(RpcError::from_parse_error(&ParseError::UndefVar(id, 0), &id.to_string(), None)),
+				}
+			},
+			"get_vars" => Ok(Json::Array(self.session.ctx.vm.get_vars().iter().map(|&(id, ref v)| Json::Object(vec![
+				("name".to_string(), Json::Str(id.to_string())),
+				("value".to_string(), value_to_json(v)),
+				("text".to_string(), Json::Str(v.to_string())),
+			])).collect())),
+			"define_function" => self.define_function(params).map(|_| Json::Null),
+			"call_function" => self.call_function(params).map(|v| value_result(&v)),
+			"set_trig_mode" => {
+				let mode = match try!(str_param(params, "mode")) {
+					"radians" => TrigMode::Radians,
+					"degrees" => TrigMode::Degrees,
+					"gradians" => TrigMode::Gradians,
+					m => return Err(RpcError::invalid_params(format!("unknown trig mode '{}'", m))),
+				};
+				self.session.ctx.vm.set_trig_mode(mode);
+				Ok(Json::Null)
+			},
+			"get_trig_mode" => Ok(Json::Str(match self.session.ctx.vm.get_trig_mode() {
+				TrigMode::Radians => "radians",
+				TrigMode::Degrees => "degrees",
+				TrigMode::Gradians => "gradians",
+			}.to_string())),
+			"render" => render(params),
+			_ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+		}
+	}
+
+	/// Evaluates `text` without adding it to the history.
+	fn evaluate(&mut self, text: &str) -> Result<Value, RpcError> {
+		let ex = try!(self.expand_calls(&try!(parse(text))));
+		self.session.ctx.vm.evaluate(&ex).map_err(|e| RpcError::from_parse_error(&e, text, Some(&ex)))
+	}
+
+	/// Returns a copy of `ex` in which every call to a defined function, such as `f(2)`, is replaced by the body of
+	/// the function in brackets, with each parameter replaced by its argument in brackets.
+	fn expand_calls(&self, ex: &VExprRef) -> Result<VExprRef, RpcError> {
+		let copy = copy_vexpr(ex, None);
+		let mut calls = 0;
+		try!(self.expand_calls_in(&copy, 0, &mut calls));
+		Ok(copy)
+	}
+
+	fn expand_calls_in(&self, ex: &VExprRef, depth: usize, calls: &mut usize) -> Result<(), RpcError> {
+		// The arguments are expanded before they are put into the bodies
+		let inner: Vec<VExprRef> = ex.borrow().tokens.iter().flat_map(|tok| tok.get_inner_expr().into_vec()).collect();
+		for inner in &inner {
+			try!(self.expand_calls_in(inner, depth, calls));
+		}
+		let mut i = 0;
+		while i < ex.borrow().tokens.len() {
+			let (name, f, len, args) = match self.call_at(&ex.borrow().tokens, i) {
+				Some(call) => call,
+				None => {
+					i += 1;
+					continue;
+				},
+			};
+			*calls += 1;
+			if depth >= MAX_CALL_DEPTH || *calls > MAX_CALLS {
+				return Err(RpcError::from_call_error(format!("the calls to '{}' are nested too deeply", name), ex));
+			}
+			let args = split_args(&args);
+			if args.len() != f.params.len() {
+				let message = format!("'{}' takes {} arguments, not {}", name, f.params.len(), args.len());
+				return Err(RpcError::from_call_error(message, ex));
+			}
+			let body = copy_vexpr(&f.body, Some(ex));
+			try!(self.expand_calls_in(&body, depth + 1, calls));
+			substitute(&body, &f.params, &args);
+			let mut ex = ex.borrow_mut();
+			let rest = ex.tokens.split_off(i + len + 1);
+			ex.tokens.truncate(i);
+			ex.tokens.push(VToken::Delim(DelimType::Paren, body));
+			ex.tokens.extend(rest);
+			i += 1;
+		}
+		Ok(())
+	}
+
+	/// If a call to a defined function starts at `tokens[i]`, returns the name and the function, the number of
+	/// tokens in the name, and the expression of the arguments. Of functions whose names overlap, the longest wins.
+	fn call_at(&self, tokens: &[VToken], i: usize) -> Option<(&str, &Function, usize, VExprRef)> {
+		let mut call: Option<(&str, &Function, usize, VExprRef)> = None;
+		for (name, f) in &self.functions {
+			let len = name.chars().count();
+			let named = i + len <= tokens.len() && tokens[i..i + len].iter().zip(name.chars()).all(|(tok, c)| match tok {
+				&VToken::Char(d) | &VToken::Digit(d) => d == c,
+				_ => false,
+			});
+			let longer = call.as_ref().map_or(true, |&(_, _, l, _)| len > l);
+			match tokens.get(i + len) {
+				Some(&VToken::Delim(DelimType::Paren, ref args)) if named && longer => call = Some((name, f, len, args.clone())),
+				_ => {},
+			}
+		}
+		call
+	}
+
+	fn define_function(&mut self, params: &Json) -> Result<(), RpcError> {
+		let name = try!(str_param(params, "name"));
+		if !name.chars().next().map_or(false, char::is_alphabetic) || !name.chars().all(char::is_alphanumeric) {
+			return Err(RpcError::invalid_params(format!("'{}' is not a valid function name", name)));
+		}
+		// Those are read as the built in function or constant, so calls to the function would never be found
+		if FuncType::from_name(name).is_some() || name == "root" || name == "pi" || name == "phi" || name.starts_with("Ans") {
+			return Err(RpcError::invalid_params(format!("'{}' is already the name of a function or constant", name)));
+		}
+		let names = match params.get("params").and_then(Json::as_array) {
+			Some(names) => names,
+			None => return Err(RpcError::invalid_params("'params' must be an array of variable names".to_string())),
+		};
+		let mut vars = Vec::new();
+		for name in names {
+			let id = try!(name.as_str().and_then(VarName::from_text)
+				.ok_or_else(|| RpcError::invalid_params(format!("'{}' is not a variable name", name))));
+			if id.is_constant() || vars.contains(&id) {
+				return Err(RpcError::invalid_params(format!("'{}' can't be a parameter", id)));
+			}
+			vars.push(id);
+		}
+		let body = try!(parse(try!(str_param(params, "body"))));
+		self.functions.insert(name.to_string(), Function{ params: vars, body: body });
+		Ok(())
+	}
+
+	/// Calls a function defined with `define_function`. The variables used as parameters are put back afterwards.
+	fn call_function(&mut self, params: &Json) -> Result<Value, RpcError> {
+		let name = try!(str_param(params, "name"));
+		let args = match params.get("args").and_then(Json::as_array) {
+			Some(args) => args,
+			None => return Err(RpcError::invalid_params("'args' must be an array of expressions".to_string())),
+		};
+		let (vars, body) = match self.functions.get(name) {
+			Some(f) if f.params.len() == args.len() => (f.params.clone(), f.body.clone()),
+			Some(f) => return Err(RpcError::invalid_params(format!("'{}' takes {} arguments, not {}", name, f.params.len(), args.len()))),
+			None => return Err(RpcError::invalid_params(format!("no function named '{}'", name))),
+		};
+		// Every argument is evaluated before any parameter is assigned to, as they may use the same variables
+		let mut values = Vec::new();
+		for arg in args {
+			match arg.as_str() {
+				Some(text) => values.push(try!(self.evaluate(text))),
+				None => return Err(RpcError::invalid_params(format!("the arguments must be strings, not {}", arg.kind()))),
+			}
+		}
+		let body = try!(self.expand_calls(&body));
+		let vm = &mut self.session.ctx.vm;
+		let saved: Vec<Option<Value>> = vars.iter().map(|&id| vm.get_var(id)).collect();
+		for (&id, v) in vars.iter().zip(values) {
+			vm.set_var(id, v);
+		}
+		let res = vm.evaluate(&body);
+		for (&id, v) in vars.iter().zip(saved) {
+			match v {
+				Some(v) => vm.set_var(id, v),
+				None => { vm.remove_var(id); },
+			}
+		}
+		res.map_err(|e| RpcError::from_parse_error(&e, &tokens_to_text(&body.borrow().tokens), Some(&body)))
+	}
+}
+
+fn error_response(id: Json, e: &RpcError) -> Json {
+	Json::Object(vec![
+		("jsonrpc".to_string(), Json::Str("2.0".to_string())),
+		("error".to_string(), e.to_json()),
+		("id".to_string(), id),
+	])
+}
+
+fn value_result(v: &Value) -> Json {
+	Json::Object(vec![
+		("value".to_string(), value_to_json(v)),
+		("text".to_string(), Json::Str(v.to_string())),
+	])
+}
+
+/// Splits the arguments of a function call at the commas. `f()` has no arguments.
+fn split_args(args: &VExprRef) -> Vec<Vec<VToken>> {
+	let tokens = &args.borrow().tokens;
+	if tokens.is_empty() {
+		return Vec::new();
+	}
+	tokens.split(|tok| match tok { &VToken::Char(',') => true, _ => false }).map(|arg| arg.to_vec()).collect()
+}
+
+/// Replaces each of the variables `params` in `ex` with the argument at the same index, in brackets.
+fn substitute(ex: &VExprRef, params: &[VarName], args: &[Vec<VToken>]) {
+	let inner: Vec<VExprRef> = ex.borrow().tokens.iter().flat_map(|tok| tok.get_inner_expr().into_vec()).collect();
+	for inner in &inner {
+		substitute(inner, params, args);
+	}
+	let mut i = 0;
+	while i < ex.borrow().tokens.len() {
+		let param = com::var_name_at(&ex.borrow().tokens, i)
+			.and_then(|(id, len)| params.iter().position(|&p| p == id).map(|k| (k, len)));
+		if let Some((k, len)) = param {
+			let arg = VExpr::with_parent(ex.clone()).to_ref();
+			arg.borrow_mut().tokens = copy_tokens(&args[k], &arg);
+			let mut ex = ex.borrow_mut();
+			let rest = ex.tokens.split_off(i + len);
+			ex.tokens.truncate(i);
+			ex.tokens.push(VToken::Delim(DelimType::Paren, arg));
+			ex.tokens.extend(rest);
+		}
+		i += 1;
+	}
+}
+
+fn parse(text: &str) -> Result<VExprRef, RpcError> {
+	parse_text(text).map_err(|e| RpcError::from_parse_error(&e, text, None))
+}
+
+fn str_param<'a>(params: &'a Json, key: &str) -> Result<&'a str, RpcError> {
+	match params.get(key) {
+		Some(&Json::Str(ref s)) => Ok(s),
+		Some(v) => Err(RpcError::invalid_params(format!("'{}' must be a string, not {}", key, v.kind()))),
+		None => Err(RpcError::invalid_params(format!("missing parameter '{}'", key))),
+	}
+}
+
+/// Reads a variable name, written as it is displayed (`x₁`) or typed (`x_1`).
+fn var_param(params: &Json, key: &str) -> Result<VarName, RpcError> {
+	let name = try!(str_param(params, key));
+	// The digits typed are read as subscript digits, so that they follow the same rules
+	let typed = match name.find('_') {
+		Some(i) if i + 1 < name.len() => {
+			let sub: Option<String> = name[i + 1..].chars().map(|c| c.to_digit(10).map(|d| subscript_digits(d as usize))).collect();
+			sub.and_then(|sub| VarName::from_text(&format!("{}{}", &name[..i], sub)))
+		},
+		_ => None,
+	};
+	match typed.or_else(|| VarName::from_text(name)) {
+		Some(id) if id.is_constant() => Err(RpcError::invalid_params(format!("'{}' is a constant", id))),
+		Some(id) => Ok(id),
+		None => Err(RpcError::invalid_params(format!("'{}' is not a variable name", name))),
+	}
+}
+
+#[cfg(feature = "render")]
+fn render(params: &Json) -> Result<Json, RpcError> {
+	use equator::edit::Editor;
+	use equator::render::{ExportOptions, ImageFormat, export_expression_bytes};
+
+	let format = try!(str_param(params, "format"));
+	let format = try!(ImageFormat::from_name(format)
+		.ok_or_else(|| RpcError::invalid_params(format!("unknown image format '{}'", format))));
+	let ed = Editor::with_expression(try!(parse(try!(str_param(params, "text")))), 0);
+
+	let res = export_expression_bytes(&ed, None, format, &ExportOptions::new());
+	let ((w, h), bytes) = try!(res.map_err(|e| RpcError::new(RENDER_ERROR, format!("could not render: {}", e))));
+	let data = match format {
+		ImageFormat::Svg => String::from_utf8_lossy(&bytes).into_owned(),
+		ImageFormat::Png | ImageFormat::Pdf => base64(&bytes),
+	};
+	Ok(Json::Object(vec![
+		("format".to_string(), Json::Str(format.name().to_string())),
+		("width".to_string(), Json::Num(w)),
+		("height".to_string(), Json::Num(h)),
+		("data".to_string(), Json::Str(data)),
+	]))
+}
+
+#[cfg(not(feature = "render"))]
+fn render(_params: &Json) -> Result<Json, RpcError> {
+	Err(RpcError::new(RENDER_ERROR, "this build of Equator can't render expressions".to_string()))
+}
+
+#[cfg(feature = "render")]
+fn base64(bytes: &[u8]) -> String {
+	const CHARS: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut s = String::with_capacity((bytes.len() + 2) / 3 * 4);
+	for chunk in bytes.chunks(3) {
+		let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+		for i in 0..4 {
+			if i <= chunk.len() {
+				s.push(CHARS[n >> (18 - 6 * i) & 0x3f] as char);
+			} else {
+				s.push('=');
+			}
+		}
+	}
+	s
+}
+
+#[test]
+fn set_var_test() {
+	fn request(server: &mut Server, id: usize, method: &str, params: &str) -> String {
+		let line = format!(r#"{{"jsonrpc": "2.0", "method": "{}", "params": {}, "id": {}}}"#, method, params, id);
+		server.handle_line(&line).unwrap()
+	}
+	fn result(id: usize, value: &str) -> String {
+		format!(r#"{{"jsonrpc":"2.0","result":{{"value":"{0}","text":"{0}"}},"id":{1}}}"#, value, id)
+	}
+
+	let mut server = Server::new();
+	assert_eq!(request(&mut server, 1, "evaluate", r#"{"text": "a=1"}"#), result(1, "1"));
+	assert_eq!(request(&mut server, 2, "set_var", r#"{"name": "a", "value": "5"}"#), result(2, "5"));
+	// The value set wins over the one the expression before assigned
+	assert_eq!(request(&mut server, 3, "evaluate", r#"{"text": "a"}"#), result(3, "5"));
+	assert_eq!(request(&mut server, 4, "get_var", r#"{"name": "a"}"#), result(4, "5"));
+	assert_eq!(request(&mut server, 5, "evaluate", r#"{"text": "a=a+1"}"#), result(5, "6"));
+	assert_eq!(request(&mut server, 6, "get_var", r#"{"name": "a"}"#), result(6, "6"));
+	assert_eq!(request(&mut server, 7, "evaluate", r#"{"text": "2#2"}"#), result(7, "10"));
+}
+
+#[test]
+fn protocol_test() {
+	fn error_code(response: &str) -> Option<f64> {
+		match json::parse(response).unwrap().get("error").and_then(|e| e.get("code")) {
+			Some(&Json::Num(code)) => Some(code),
+			_ => None,
+		}
+	}
+
+	let mut server = Server::new();
+	let mut error = |line: &str| server.handle_line(line).as_ref().and_then(|r| error_code(r)).map(|c| c as i32);
+	assert_eq!(error(r#"{"jsonrpc": "2.0", "method": "#), Some(PARSE_ERROR));
+	assert_eq!(error(r#"[]"#), Some(INVALID_REQUEST));
+	assert_eq!(error(r#"{"method": "get_vars", "id": 1}"#), Some(INVALID_REQUEST));
+	assert_eq!(error(r#"{"jsonrpc": "2.0", "method": "fly", "id": 1}"#), Some(METHOD_NOT_FOUND));
+	assert_eq!(error(r#"{"jsonrpc": "2.0", "method": "evaluate", "params": ["1"], "id": 1}"#), Some(INVALID_PARAMS));
+	assert_eq!(error(r#"{"jsonrpc": "2.0", "method": "evaluate", "params": {}, "id": 1}"#), Some(INVALID_PARAMS));
+	assert_eq!(error(r#"{"jsonrpc": "2.0", "method": "evaluate", "params": {"text": 1}, "id": 1}"#), Some(INVALID_PARAMS));
+	assert_eq!(error(r#"{"jsonrpc": "2.0", "method": "set_trig_mode", "params": {"mode": "turns"}, "id": 1}"#), Some(INVALID_PARAMS));
+	assert_eq!(error(r#"{"jsonrpc": "2.0", "method": "get_var", "params": {"name": "z"}, "id": 1}"#), Some(EVAL_ERROR));
+	assert_eq!(error(r#"{"jsonrpc": "2.0", "method": "get_var", "params": {"name": "x_01"}, "id": 1}"#), Some(INVALID_PARAMS));
+	assert_eq!(error(r#"{"jsonrpc": "2.0", "method": "get_var", "params": {"name": "x₀₁"}, "id": 1}"#), Some(INVALID_PARAMS));
+
+	// Notifications are carried out, but not answered, even in a batch
+	assert_eq!(server.handle_line(r#"{"jsonrpc": "2.0", "method": "set_var", "params": {"name": "x_1", "value": "3"}}"#), None);
+	let response = server.handle_line(r#"[
+		{"jsonrpc": "2.0", "method": "set_trig_mode", "params": {"mode": "degrees"}},
+		{"jsonrpc": "2.0", "method": "get_var", "params": {"name": "x₁"}, "id": "a"},
+		{"jsonrpc": "2.0", "method": "get_trig_mode", "id": "b"}
+	]"#).unwrap();
+	assert_eq!(response, concat!(r#"[{"jsonrpc":"2.0","result":{"value":"3","text":"3"},"id":"a"},"#,
+		r#"{"jsonrpc":"2.0","result":"degrees","id":"b"}]"#));
+}
+
+#[test]
+fn eval_error_test() {
+	let mut server = Server::new();
+	let mut error_data = |text: &str| {
+		let line = format!(r#"{{"jsonrpc": "2.0", "method": "evaluate", "params": {{"text": "{}"}}, "id": 1}}"#, text);
+		let response = json::parse(&server.handle_line(&line).unwrap()).unwrap();
+		response.get("error").and_then(|e| e.get("data")).unwrap().to_compact_string()
+	};
+	assert_eq!(error_data("2+$"), r#"{"kind":"illegal_character","text":"2+$","position":2}"#);
+	assert_eq!(error_data("(1+2"), r#"{"kind":"unmatched_bracket","text":"(1+2","position":4}"#);
+	assert_eq!(error_data("1+q"), r#"{"kind":"undefined_variable","text":"1+q","part":"q"}"#);
+}
+
+#[test]
+fn function_test() {
+	fn request(server: &mut Server, method: &str, params: &str) -> String {
+		let line = format!(r#"{{"jsonrpc": "2.0", "method": "{}", "params": {}, "id": 1}}"#, method, params);
+		server.handle_line(&line).unwrap()
+	}
+	fn result(value: &str) -> String {
+		format!(r#"{{"jsonrpc":"2.0","result":{{"value":"{0}","text":"{0}"}},"id":1}}"#, value)
+	}
+
+	let mut server = Server::new();
+	let null = r#"{"jsonrpc":"2.0","result":null,"id":1}"#;
+	assert_eq!(request(&mut server, "define_function", r#"{"name": "f", "params": ["x", "y"], "body": "x*2+y"}"#), null);
+	request(&mut server, "set_var", r#"{"name": "x", "value": "10"}"#);
+	// The arguments are evaluated before any is assigned, and the parameters are put back afterwards
+	assert_eq!(request(&mut server, "call_function", r#"{"name": "f", "args": ["1", "x"]}"#), result("12"));
+	assert_eq!(request(&mut server, "get_var", r#"{"name": "x"}"#), result("10"));
+	assert!(request(&mut server, "get_var", r#"{"name": "y"}"#).contains("undefined_variable"));
+
+	assert!(request(&mut server, "call_function", r#"{"name": "f", "args": ["1"]}"#).contains("takes 2 arguments, not 1"));
+	assert!(request(&mut server, "call_function", r#"{"name": "g", "args": []}"#).contains("no function named 'g'"));
+	assert!(request(&mut server, "define_function", r#"{"name": "2f", "params": [], "body": "1"}"#).contains("not a valid function name"));
+	assert!(request(&mut server, "define_function", r#"{"name": "g", "params": ["x", "x"], "body": "x"}"#).contains("can't be a parameter"));
+	assert!(request(&mut server, "define_function", r#"{"name": "sin", "params": [], "body": "1"}"#).contains("already the name"));
+
+	// Calls in expressions are replaced by the body, so the variables aren't touched
+	assert_eq!(request(&mut server, "evaluate", r#"{"text": "f(3,1)+1"}"#), result("8"));
+	assert_eq!(request(&mut server, "evaluate", r#"{"text": "2f(x,x-9)"}"#), result("42"));
+	request(&mut server, "define_function", r#"{"name": "sq", "params": ["x₁"], "body": "x₁^2"}"#);
+	request(&mut server, "define_function", r#"{"name": "g", "params": ["y"], "body": "sq(y)+f(y,0)"}"#);
+	assert_eq!(request(&mut server, "evaluate", r#"{"text": "g(1+2)"}"#), result("15"));
+	assert_eq!(request(&mut server, "evaluate", r#"{"text": "sq(sq(2))-f(sq(1),0)"}"#), result("14"));
+	assert_eq!(request(&mut server, "set_var", r#"{"name": "z", "value": "sq(5)"}"#), result("25"));
+	assert_eq!(request(&mut server, "call_function", r#"{"name": "g", "args": ["2"]}"#), result("8"));
+	assert_eq!(request(&mut server, "get_var", r#"{"name": "x"}"#), result("10"));
+
+	assert!(request(&mut server, "evaluate", r#"{"text": "sq(1,2)"}"#).contains("'sq' takes 1 arguments, not 2"));
+	request(&mut server, "define_function", r#"{"name": "h", "params": ["x"], "body": "h(x)+1"}"#);
+	let response = request(&mut server, "evaluate", r#"{"text": "h(1)"}"#);
+	assert!(response.contains("nested too deeply") && response.contains("function_call"));
+}
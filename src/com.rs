@@ -7,6 +7,9 @@ use consts::*;
 use vis::*;
 use func::*;
 use err::*;
+use val::Value;
+use interval::Interval;
+use uncertain::Uncertain;
 use edit;
 
 #[allow(non_snake_case)]
@@ -15,16 +18,20 @@ mod Com {
 }
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Command {
-	Var(char), // Pushes variable with char identifier to the stack
+	Var(VarName), // Pushes the variable to the stack
+	Ans(usize), // Pushes the nth most recent result to the stack. 0 is the same as 1.
+	Hist(usize), // Pushes the nth result in the history to the stack, counting from 1
 	Num(d128), // Pushes literal to the stack
 	Add, // A, B => A + B
 	Sub, // A, B => A - B
 	Mul, // A, B => A * B
 	Div, // A, B => A / B
 	Neg, // A => - A
+	PlusMinus, // A, B => A ± B -- A with the standard uncertainty B
 	Pow, // A, B => A ^ B -- Raises A to the power B
 	Func(FuncType), // A => func(A)
 	Root, // A, B => Ath root of B
+	List(usize), // A1, ..., An => {A1, ..., An}
 	Comma, // NOP. Should not be in the final vector. Used to indicate a seperation between arguments in a function.
 	ParenOpen, // NOP. Should not be in the final vector.
 	ParenClose, // NOP. Should not be in the final vector.
@@ -34,15 +41,18 @@ impl Command {
 	pub fn pops(&self) -> usize {
 		match self {
 			&Com::Var(_) => 0,
+			&Com::Ans(_) | &Com::Hist(_) => 0,
 			&Com::Num(_) => 0,
 			&Com::Add => 2,
 			&Com::Sub => 2,
 			&Com::Mul => 2,
 			&Com::Div => 2,
 			&Com::Neg => 1,
+			&Com::PlusMinus => 2,
 			&Com::Pow => 2,
-			&Com::Func(_) => 1,
+			&Com::Func(ref func) => func.arity(),
 			&Com::Root => 2,
+			&Com::List(n) => n,
 			&Com::Comma => 0,
 			&Com::ParenOpen => 0,
 			&Com::ParenClose => 0,
@@ -52,15 +62,18 @@ impl Command {
 	pub fn pushes(&self) -> usize {
 		match self {
 			&Com::Var(_) => 1,
+			&Com::Ans(_) | &Com::Hist(_) => 1,
 			&Com::Num(_) => 1,
 			&Com::Add => 1,
 			&Com::Sub => 1,
 			&Com::Mul => 1,
 			&Com::Div => 1,
 			&Com::Neg => 1,
+			&Com::PlusMinus => 1,
 			&Com::Pow => 1,
 			&Com::Func(_) => 1,
 			&Com::Root => 1,
+			&Com::List(_) => 1,
 			&Com::Comma => 0,
 			&Com::ParenOpen => 0,
 			&Com::ParenClose => 0,
@@ -68,46 +81,47 @@ impl Command {
 	}
 	pub fn is_operator(&self) -> bool {
 		match self {
-			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Neg | &Com::Pow | &Com::Func(_) | &Com::Root => true,
-			&Com::Var(_) | &Com::Num(_) | &Com::Comma | &Com::ParenOpen | &Com::ParenClose => false
+			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Neg | &Com::PlusMinus | &Com::Pow | &Com::Func(_) | &Com::Root | &Com::List(_) => true,
+			&Com::Var(_) | &Com::Ans(_) | &Com::Hist(_) | &Com::Num(_) | &Com::Comma | &Com::ParenOpen | &Com::ParenClose => false
 		}
 	}
 	pub fn prescedence(&self) -> Option<u32> {
 		match self {
-			&Com::Pow => Some(4),
+			&Com::Pow => Some(5),
 			&Com::Add | &Com::Sub => Some(2),
 			&Com::Mul | &Com::Div => Some(3),
-			&Com::Neg => Some(4),
-			&Com::Func(_) | &Com::Root => Some(5),
-			&Com::Var(_) | &Com::Num(_) | &Com::Comma | &Com::ParenOpen | &Com::ParenClose => None
+			&Com::PlusMinus => Some(4), // Binds tighter than × so that `2 × 1.5 ± 0.1` is twice the measurement
+			&Com::Neg => Some(5),
+			&Com::Func(_) | &Com::Root | &Com::List(_) => Some(6),
+			&Com::Var(_) | &Com::Ans(_) | &Com::Hist(_) | &Com::Num(_) | &Com::Comma | &Com::ParenOpen | &Com::ParenClose => None
 		}
 	}
 	pub fn is_left_associative(&self) -> bool {
 		match self {
-			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Func(_) | &Com::Root | &Com::Neg => true,
+			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Func(_) | &Com::Root | &Com::List(_) | &Com::Neg | &Com::PlusMinus => true,
 			&Com::Pow => false,
-			&Com::Var(_) | &Com::Num(_) | &Com::Comma | &Com::ParenOpen | &Com::ParenClose => false,
+			&Com::Var(_) | &Com::Ans(_) | &Com::Hist(_) | &Com::Num(_) | &Com::Comma | &Com::ParenOpen | &Com::ParenClose => false,
 		}
 	}
 	pub fn is_right_associative(&self) -> bool {
 		match self {
-			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Func(_) | &Com::Root | &Com::Neg => false,
+			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Func(_) | &Com::Root | &Com::List(_) | &Com::Neg | &Com::PlusMinus => false,
 			&Com::Pow => true,
-			&Com::Var(_) | &Com::Num(_) | &Com::Comma | &Com::ParenOpen | &Com::ParenClose => false,
+			&Com::Var(_) | &Com::Ans(_) | &Com::Hist(_) | &Com::Num(_) | &Com::Comma | &Com::ParenOpen | &Com::ParenClose => false,
 		}
 	}
 	/// If an implicit multiplication is performed if this command is on the left, and the other command is_automul_right()
 	pub fn is_left_automul(&self) -> bool {
 		match self {
-			&Com::Var(_) | &Com::Num(_) | &Com::Func(_) | &Com::Root | &Com::ParenClose => true,
-			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Neg | &Com::Pow | &Com::Comma | &Com::ParenOpen => false
+			&Com::Var(_) | &Com::Ans(_) | &Com::Hist(_) | &Com::Num(_) | &Com::Func(_) | &Com::Root | &Com::List(_) | &Com::ParenClose => true,
+			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Neg | &Com::PlusMinus | &Com::Pow | &Com::Comma | &Com::ParenOpen => false
 		}
 	}
 	/// If an implicit multiplication is performed if this command is on the right, and the other command is_automul_left()
 	pub fn is_right_automul(&self) -> bool {
 		match self {
-			&Com::Var(_) | &Com::Num(_) | &Com::Func(_) | &Com::Root | &Com::ParenOpen => true,
-			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Pow | &Com::Neg | &Com::Comma | &Com::ParenClose => false
+			&Com::Var(_) | &Com::Ans(_) | &Com::Hist(_) | &Com::Num(_) | &Com::Func(_) | &Com::Root | &Com::List(_) | &Com::ParenOpen => true,
+			&Com::Add | &Com::Sub | &Com::Mul | &Com::Div | &Com::Pow | &Com::Neg | &Com::PlusMinus | &Com::Comma | &Com::ParenClose => false
 		}
 	}
 }
@@ -120,42 +134,233 @@ impl Display for Command {
 	}
 }
 
+/// How the VM evaluates numbers
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EvalMode {
+	/// Every number is a single `d128`, rounded after each operation.
+	Normal,
+	/// Every number is an interval that is guaranteed to contain the exact result.
+	Interval,
+}
+
+/// The units that angles are in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrigMode {
+	Radians,
+	Degrees,
+	Gradians
+}
+
+/// Returns true if `c` is one of the constants, which can't be assigned to.
+pub fn is_constant(c: char) -> bool {
+	c == 'π' || c == 'e' || c == 'φ'
+}
+
+/// The name of a variable: a letter, with the number of its subscript if it has one (e.g. `x₁`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VarName {
+	pub letter: char,
+	pub sub: Option<u32>,
+}
+impl VarName {
+	pub fn new(letter: char) -> VarName {
+		VarName{ letter: letter, sub: None }
+	}
+	pub fn with_sub(letter: char, sub: u32) -> VarName {
+		VarName{ letter: letter, sub: Some(sub) }
+	}
+	/// Returns true if the name is one of the constants. A subscripted letter is never a constant.
+	pub fn is_constant(&self) -> bool {
+		self.sub.is_none() && is_constant(self.letter)
+	}
+	/// Reads a name written as it is displayed, e.g. `x` or `x₁`. A subscript with a leading zero, such as in `x₀₁`, isn't
+	/// a name, as it would be another way of writing `x₁`.
+	pub fn from_text(s: &str) -> Option<VarName> {
+		let mut chars = s.chars();
+		let letter = match chars.next() {
+			Some(c) if c.is_alphabetic() => c,
+			_ => return None,
+		};
+		let digits: String = chars.collect();
+		if digits.is_empty() {
+			return Some(VarName::new(letter));
+		}
+		if digits.starts_with('₀') && digits.chars().count() > 1 {
+			return None;
+		}
+		let mut n: u32 = 0;
+		for c in digits.chars() {
+			let d = match "₀₁₂₃₄₅₆₇₈₉".chars().position(|d| d == c) {
+				Some(d) => d as u32,
+				None => return None,
+			};
+			n = match n.checked_mul(10).and_then(|n| n.checked_add(d)) {
+				Some(n) => n,
+				None => return None,
+			};
+		}
+		Some(VarName::with_sub(letter, n))
+	}
+}
+impl Display for VarName {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self.sub {
+			Some(n) => write!(f, "{}{}", self.letter, subscript_digits(n as usize)),
+			None => write!(f, "{}", self.letter),
+		}
+	}
+}
+
+/// Returns the number that the subscript `ex` is, if it is made of nothing but digits. Digits with a leading zero, as
+/// in `x₀₁`, aren't a number, so that `x₁` is the only way to write that variable.
+pub fn subscript_number(ex: &VExprRef) -> Option<u32> {
+	let tokens = &ex.borrow().tokens;
+	match tokens.first() {
+		None => return None,
+		Some(&VToken::Digit('0')) if tokens.len() > 1 => return None,
+		_ => {},
+	}
+	let mut n: u32 = 0;
+	for tok in tokens.iter() {
+		let d = match tok {
+			&VToken::Digit(c) => match c.to_digit(10) { Some(d) => d, None => return None },
+			_ => return None,
+		};
+		n = match n.checked_mul(10).and_then(|n| n.checked_add(d)) {
+			Some(n) => n,
+			None => return None,
+		};
+	}
+	Some(n)
+}
+
+/// Returns the name of the variable starting at `tokens[i]` and the number of tokens it takes up, if there is one.
+/// Subscripts that aren't a number give `None`.
+pub fn var_name_at(tokens: &[VToken], i: usize) -> Option<(VarName, usize)> {
+	match (tokens.get(i), tokens.get(i + 1)) {
+		(Some(&VToken::Char(c)), Some(&VToken::Sub(ref sub))) if c.is_alphabetic() =>
+			subscript_number(sub).map(|n| (VarName::with_sub(c, n), 2)),
+		(Some(&VToken::Char(c)), _) if c.is_alphabetic() => Some((VarName::new(c), 1)),
+		_ => None,
+	}
+}
+
+/// If `ex` is an assignment such as `a=2x` or `x₁=3`, returns the variable assigned to and a copy of the expression of
+/// its value.
+pub fn split_assignment(ex: &VExprRef) -> Option<(VarName, VExprRef)> {
+	let tokens = &ex.borrow().tokens;
+	let (id, len) = match var_name_at(tokens, 0) {
+		Some(v) => v,
+		None => return None,
+	};
+	match tokens.get(len) {
+		Some(&VToken::Char('=')) if !id.is_constant() => {
+			let value = VExpr::new_ref();
+			let copied = copy_tokens(&tokens[len + 1..], &value);
+			value.borrow_mut().tokens = copied;
+			Some((id, value))
+		},
+		_ => None,
+	}
+}
+
+/// A calculation in the history: the expression that was calculated and its result.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+	pub expr: VExprRef,
+	pub result: Value,
+}
+
 /// Holds state
 /// e.g. stack, variable states.
 #[derive(Debug)]
 pub struct VM {
-	stack: Vec<d128>,
-	vars : HashMap<char, d128>,
+	mode : EvalMode,
+	trig_mode: TrigMode,
+	stack: Vec<Value>,
+	vars : HashMap<VarName, Value>,
 	num  : usize, // number of commands executed on this VM
-	last_result: Result<d128, ParseError>,
+	last_result: Result<Value, ParseError>,
+	history: Vec<HistoryEntry>, // previous calculations, oldest first
+	history_start: usize, // number of the oldest calculations dropped from the history
+	sources: usize, // number of independent sources of uncertainty created
 }
 impl VM {
 	pub fn new() -> VM {
 		let mut hm = HashMap::new();
-		hm.insert('π', *DPI);
-		hm.insert('e', *DE);
-		hm.insert('φ', *DGOLDEN_RATIO);
-		VM{stack:Vec::new(), vars:hm, num:0, last_result:Err(NoLastResult)}
+		hm.insert(VarName::new('π'), Value::Num(*DPI));
+		hm.insert(VarName::new('e'), Value::Num(*DE));
+		hm.insert(VarName::new('φ'), Value::Num(*DGOLDEN_RATIO));
+		VM{mode:EvalMode::Normal, trig_mode:TrigMode::Radians, stack:Vec::new(), vars:hm, num:0, last_result:Err(NoLastResult), history:Vec::new(), history_start:0, sources:0}
+	}
+	#[inline(always)]
+	pub fn set_mode(&mut self, mode: EvalMode) {
+		self.mode = mode;
+	}
+	#[inline(always)]
+	pub fn get_mode(&self) -> EvalMode {
+		self.mode
+	}
+	#[inline(always)]
+	pub fn set_trig_mode(&mut self, mode: TrigMode) {
+		self.trig_mode = mode;
+	}
+	#[inline(always)]
+	pub fn get_trig_mode(&self) -> TrigMode {
+		self.trig_mode
 	}
+	/// Pushes `v` to the stack. In interval mode any numbers are converted to intervals first.
 	#[inline(always)]
-	pub fn push(&mut self, v: d128) {
+	pub fn push(&mut self, v: Value) {
+		let v = match self.mode {
+			EvalMode::Normal => v,
+			EvalMode::Interval => v.into_interval(),
+		};
 		self.stack.push(v);
 	}
 	#[inline(always)]
-	pub fn pop(&mut self) -> Option<d128> {
+	pub fn pop(&mut self) -> Option<Value> {
 		self.stack.pop()
 	}
 	#[inline(always)]
-	pub fn peek(&mut self) -> Option<d128> {
-		self.stack.get(0).map(|f| {*f})
+	pub fn peek(&mut self) -> Option<Value> {
+		self.stack.get(0).cloned()
 	}
-	#[inline(always)]
-	pub fn set_var(&mut self, id: char, v: d128) {
+	pub fn set_var(&mut self, id: VarName, v: Value) {
+		self.reserve_sources(&v);
 		self.vars.insert(id, v);
 	}
+	/// Makes sure that the sources of uncertainty `v` depends on aren't used for new values. Values loaded from a file,
+	/// or made by another VM, may depend on sources that this one hasn't created.
+	fn reserve_sources(&mut self, v: &Value) {
+		fn max_source(v: &Value) -> Option<usize> {
+			match v {
+				&Value::Uncertain(ref u) => u.terms.last().map(|&(s, _)| s),
+				&Value::List(ref vs) => vs.iter().filter_map(max_source).max(),
+				&Value::Num(_) | &Value::Interval(_) => None,
+			}
+		}
+		if let Some(next) = max_source(v).and_then(|s| s.checked_add(1)) {
+			self.sources = ::std::cmp::max(self.sources, next);
+		}
+	}
 	#[inline(always)]
-	pub fn get_var(&mut self, id: char) -> Option<d128> {
-		self.vars.get(&id).map(|f: &d128| *f)
+	pub fn get_var(&mut self, id: VarName) -> Option<Value> {
+		self.vars.get(&id).cloned()
+	}
+	/// Removes the variable, returning the value it had.
+	#[inline(always)]
+	pub fn remove_var(&mut self, id: VarName) -> Option<Value> {
+		self.vars.remove(&id)
+	}
+	/// Returns the variables that have been assigned to, sorted by name. Constants are not included.
+	pub fn get_vars(&self) -> Vec<(VarName, Value)> {
+		let mut vars: Vec<(VarName, Value)> = self.vars.iter()
+			.filter(|&(k, _)| !k.is_constant())
+			.map(|(&k, v)| (k, v.clone()))
+			.collect();
+		vars.sort_by(|a, b| a.0.cmp(&b.0));
+		vars
 	}
 	#[inline(always)]
 	pub fn clear_stack(&mut self) {
@@ -166,7 +371,7 @@ impl VM {
 	pub fn stack_size(&self) -> usize {
 		self.stack.len()
 	}
-	pub fn get_result(&mut self, coms: &[Command]) -> Result<d128, ParseError> {
+	pub fn get_result(&mut self, coms: &[Command]) -> Result<Value, ParseError> {
 		match self.execute_all(coms) {
 			Ok(_) => {},
 			Err(e) => {
@@ -179,15 +384,100 @@ impl VM {
 		} else if self.stack_size() > 1 {
 			Err(SyntaxError)
 		} else {
-			Ok(self.stack[0])
+			Ok(self.stack[0].clone())
 		};
 		self.last_result = res.clone();
 		res
 	}
 	#[inline(always)]
-	pub fn get_last_result(&self) -> Result<d128, ParseError> {
+	pub fn get_last_result(&self) -> Result<Value, ParseError> {
 		self.last_result.clone()
 	}
+	#[inline(always)]
+	pub fn set_last_result(&mut self, res: Result<Value, ParseError>) {
+		self.last_result = res;
+	}
+	/// Evaluates the expression. If it is an assignment, such as `a=2x`, the value is also stored in the variable.
+	pub fn evaluate(&mut self, ex: &VExprRef) -> Result<Value, ParseError> {
+		self.clear_stack();
+		match split_assignment(ex) {
+			Some((id, value)) => {
+				let v = try!(self.get_result(&try!(expr_to_commands(value))));
+				self.set_var(id, v.clone());
+				Ok(v)
+			},
+			None => self.get_result(&try!(expr_to_commands(ex.clone()))),
+		}
+	}
+	/// Appends a calculation to the history, so that its result can be referenced by `Ans` and `#n`
+	pub fn push_history(&mut self, expr: VExprRef, v: Value) {
+		self.reserve_sources(&v);
+		self.history.push(HistoryEntry{ expr: expr, result: v });
+	}
+	/// The calculations in the history, oldest first, without those that have been dropped from the start of it. The
+	/// first is `#(history_start() + 1)`.
+	pub fn get_history(&self) -> &[HistoryEntry] {
+		&self.history
+	}
+	/// The number of calculations that have been dropped from the start of the history.
+	pub fn history_start(&self) -> usize {
+		self.history_start
+	}
+	/// Replaces the history with `entries`, the first of which is `#(start + 1)`.
+	pub fn set_history(&mut self, start: usize, entries: Vec<HistoryEntry>) {
+		for e in entries.iter() {
+			self.reserve_sources(&e.result);
+		}
+		self.history = entries;
+		self.history_start = start;
+	}
+	/// Drops the oldest calculations from the history, keeping the last `keep`. `#n` still counts from the first
+	/// calculation, so it refers to nothing once the nth has been dropped.
+	pub fn trim_history(&mut self, keep: usize) {
+		let drop = self.history.len().saturating_sub(keep);
+		if drop > 0 {
+			self.history.drain(..drop);
+			self.history_start += drop;
+		}
+	}
+	/// Gets the nth most recent result. `Ans` and `Ans₁` both refer to the most recent.
+	pub fn get_ans(&self, n: usize) -> Option<Value> {
+		let n = if n == 0 { 1 } else { n };
+		if n > self.history.len() {
+			None
+		} else {
+			Some(self.history[self.history.len() - n].result.clone())
+		}
+	}
+	/// Gets the nth calculation in the history, counting from 1, unless it has been dropped.
+	pub fn get_hist_entry(&self, n: usize) -> Option<&HistoryEntry> {
+		if n <= self.history_start {
+			None
+		} else {
+			self.history.get(n - 1 - self.history_start)
+		}
+	}
+	/// Gets the nth result in the history, counting from 1.
+	pub fn get_hist(&self, n: usize) -> Option<Value> {
+		self.get_hist_entry(n).map(|e| e.result.clone())
+	}
+	/// Prepares the evaluation of `ex` using the VM's current variables and history, so that it can be run on another
+	/// thread. Assignments are not made.
+	pub fn job(&self, ex: &VExprRef) -> Result<EvalJob, ParseError> {
+		let value = match split_assignment(ex) {
+			Some((_, value)) => value,
+			None => ex.clone(),
+		};
+		Ok(EvalJob {
+			commands: try!(expr_to_commands(value)),
+			vars: self.vars.iter().map(|(&k, v)| (k, v.clone())).collect(),
+			history: self.history.iter().map(|e| e.result.clone()).collect(),
+			history_start: self.history_start,
+			mode: self.mode,
+			trig_mode: self.trig_mode,
+			sources: self.sources,
+		})
+	}
 	pub fn execute_all(&mut self, coms: &[Command]) -> Result<(), ParseError> {
 		let debug_print: bool = unsafe { debug_print_stage3 };
 		
@@ -234,46 +524,91 @@ impl VM {
 					Some(v) => v,
 					None => return Err(UndefVar(id, pos)),
 				};
+				// The built in constants are rounded, so in interval mode they are widened
+				let constant = if id.is_constant() { Interval::constant(id.letter) } else { None };
+				let val = match (self.mode, val.to_num(pos).ok(), constant) {
+					(EvalMode::Interval, Some(v), Some(i)) if i.contains(v) => Value::Interval(i),
+					_ => val,
+				};
+				self.push(val);
+			},
+			&Com::Ans(n) => {
+				let val = match self.get_ans(n) {
+					Some(v) => v,
+					None => return Err(NoHistoryEntry(pos)),
+				};
+				self.push(val);
+			},
+			&Com::Hist(n) => {
+				let val = match self.get_hist(n) {
+					Some(v) => v,
+					None => return Err(NoHistoryEntry(pos)),
+				};
+				self.push(val);
+			},
+			&Com::Num(v) => {
+				let val = match self.mode {
+					EvalMode::Normal => Value::Num(v),
+					EvalMode::Interval => Value::Interval(Interval::from_literal(v)),
+				};
 				self.push(val);
 			},
-			&Com::Num(v) => self.push(v),
 			&Com::Add => {
 				let b = self.pop().unwrap(); // Intentional B first.
 				let a = self.pop().unwrap();
-				self.push(a + b);
+				self.push(try!(a.zip_with(b, pos, |a, b| a + b, |a, b| a + b, |a, b| a + b)));
 			},
 			&Com::Sub => {
 				let b = self.pop().unwrap(); // Intentional B first.
 				let a = self.pop().unwrap();
-				self.push(a - b);
+				self.push(try!(a.zip_with(b, pos, |a, b| a - b, |a, b| a - b, |a, b| a - b)));
 			},
 			&Com::Mul => {
 				let b = self.pop().unwrap(); // Intentional B first.
 				let a = self.pop().unwrap();
-				self.push(a * b);
+				self.push(try!(a.zip_with(b, pos, |a, b| a * b, |a, b| a * b, |a, b| a * b)));
 			},
 			&Com::Div => {
 				let b = self.pop().unwrap(); // Intentional B first.
 				let a = self.pop().unwrap();
-				self.push(a / b);
+				self.push(try!(a.zip_with(b, pos, |a, b| a / b, |a, b| a / b, |a, b| a / b)));
 			},
 			&Com::Neg => {
 				let a = self.pop().unwrap();
-				self.push(-a);
+				self.push(a.map(|a| -a, |a| -a, |a| -a));
+			},
+			&Com::PlusMinus => {
+				let b = self.pop().unwrap(); // Intentional B first.
+				let a = self.pop().unwrap();
+				let sigma = try!(b.to_uncertain(pos)).val.abs();
+				let source = self.sources;
+				self.sources += 1;
+				self.push(Value::Uncertain(try!(a.to_uncertain(pos)) + Uncertain::new(*D0, sigma, source)));
 			},
 			&Com::Pow => {
 				let b = self.pop().unwrap(); // Intentional B first.
 				let a = self.pop().unwrap();
-				self.push(a.pow(b));
+				self.push(try!(a.zip_with(b, pos, |a, b| a.pow(b), |a, b| a.pow(b), |a, b| a.pow(&b))));
 			},
 			&Com::Func(ref func) => {
-				let a = self.pop().unwrap();
-				self.push(func.execute(a));
+				let n = func.arity();
+				let at = self.stack_size() - n;
+				let args: Vec<Value> = self.stack.drain(at..).collect();
+				let trig = self.trig_mode;
+				self.push(try!(func.execute(&args, trig, pos)));
 			},
 			&Com::Root => {
 				let b = self.pop().unwrap(); // Intentional B first.
 				let a = self.pop().unwrap();
-				self.push(b.pow(d128!(1) / a));
+				self.push(try!(a.zip_with(b, pos, |a, b| b.pow(d128!(1) / a), |a, b| b.pow(Interval::point(d128!(1)) / a), |a, b| b.pow(&(Uncertain::exact(d128!(1)) / a)))));
+			},
+			&Com::List(n) => {
+				let at = self.stack_size() - n;
+				let list: Vec<Value> = self.stack.drain(at..).collect();
+				if list.iter().any(|v| v.is_list()) {
+					return Err(ExpectedNum(pos));
+				}
+				self.push(Value::List(list));
 			},
 			&Com::Comma | &Com::ParenOpen | &Com::ParenClose => return Err(IllegalCommand(com.clone(), self.num)),
 		}
@@ -282,26 +617,104 @@ impl VM {
 	}
 }
 
+/// A calculation that holds everything needed to run it, so that it can be sent to another thread.
+pub struct EvalJob {
+	commands: Vec<Command>,
+	vars: Vec<(VarName, Value)>,
+	history: Vec<Value>,
+	history_start: usize,
+	mode: EvalMode,
+	trig_mode: TrigMode,
+	sources: usize,
+}
+impl EvalJob {
+	/// Gives the variable `id` the value `v` for the calculation, instead of the one it had in the VM.
+	pub fn set_var(&mut self, id: VarName, v: Value) {
+		self.vars.push((id, v));
+	}
+	/// Runs the calculation on a VM of its own.
+	pub fn run(self) -> Result<Value, ParseError> {
+		let mut vm = VM::new();
+		vm.mode = self.mode;
+		vm.trig_mode = self.trig_mode;
+		vm.sources = self.sources;
+		vm.vars.extend(self.vars.into_iter());
+		for v in self.history.into_iter() {
+			vm.history.push(HistoryEntry{ expr: VExpr::new_ref(), result: v });
+		}
+		vm.history_start = self.history_start;
+		vm.get_result(&self.commands)
+	}
+}
+
 pub static mut debug_print_stage1: bool = false;
 pub static mut debug_print_stage2: bool = false;
 pub static mut debug_print_stage3: bool = false;
+/// Prints the infix and postfix commands of each expression compiled. Programs that use stdout for their own output
+/// turn this off.
+pub static mut debug_print_commands: bool = true;
+
+/// A command, with the part of the expression that it came from.
+pub type LocatedCommand = (Command, edit::Span);
 
 // Changes ex into a vector of commands to execute to get the value of the expression.
 pub fn expr_to_commands(ex: VExprRef) -> Result<Vec<Command>, ParseError> {
+	let located = try!(expr_to_located_commands(&ex, 0));
+	Ok(located.into_iter().map(|(com, _)| com).collect())
+}
+
+/// Changes the tokens of `ex` from `start` on into commands, each with the span of `ex` or of an inner expression that
+/// it came from. These are what error positions from running the commands refer to.
+pub fn expr_to_located_commands(ex: &VExprRef, start: usize) -> Result<Vec<LocatedCommand>, ParseError> {
 	let mut infix = Vec::new();
-	if ex.borrow().tokens.len() == 0 {
+	if ex.borrow().tokens.len() <= start {
 		return Err(ExpressionEmpty);
 	}
-	try!(expr_to_infix(ex, &mut infix));
-	print!("infix  : ");
-	print_commands(&infix, true);
+	try!(expr_to_infix(ex.clone(), start, &mut infix));
+	let print = unsafe { debug_print_commands };
+	if print {
+		print!("infix  : ");
+		print_commands(&strip_locations(&infix), true);
+	}
 	let postfix = try!(infix_to_postfix(&infix));
-	print!("postfix: ");
-	print_commands(&postfix, true);
+	if print {
+		print!("postfix: ");
+		print_commands(&strip_locations(&postfix), true);
+	}
 	Ok(postfix)
 }
 
-fn expr_to_infix(ex: VExprRef, infix: &mut Vec<Command>) -> Result<(), ParseError> {
+fn strip_locations(coms: &[LocatedCommand]) -> Vec<Command> {
+	coms.iter().map(|&(ref com, _)| com.clone()).collect()
+}
+
+/// Finds the part of `ex` that an error from evaluating it is about. `None` means that it is about the whole expression.
+pub fn error_span(ex: &VExprRef, err: &ParseError) -> Option<edit::Span> {
+	match err {
+		&NumParseError(ref num_ex, from, to) => Some(edit::Span::new(num_ex.clone(), from, to + 1)),
+		&IllegalToken(_, ref cur) => Some(edit::Span::new(cur.ex.clone(), cur.pos, cur.pos + 1)),
+		&CommandExecuteError(_, pos) | &StackExhausted(pos) | &UndefVar(_, pos) | &ExpectedNum(pos) | &LengthMismatch(pos)
+			| &EmptyList(pos) | &DomainError(_, pos) | &NoHistoryEntry(pos) | &IncompatibleValues(pos) => {
+			// The commands were made from the value of an assignment, so skip the variable and `=` too
+			let start = match split_assignment(ex) {
+				Some(_) => var_name_at(&ex.borrow().tokens, 0).map_or(0, |(_, len)| len + 1),
+				None => 0,
+			};
+			let coms = match expr_to_located_commands(ex, start) {
+				Ok(coms) => coms,
+				Err(_) => return None,
+			};
+			match err {
+				// Variables given no value by the worksheet aren't found by running the commands, so have no position
+				&UndefVar(id, _) => coms.iter().find(|&&(ref com, _)| *com == Com::Var(id)).map(|&(_, ref span)| span.clone()),
+				_ => coms.get(pos).map(|&(_, ref span)| span.clone()),
+			}
+		},
+		_ => None,
+	}
+}
+
+fn expr_to_infix(ex: VExprRef, start: usize, infix: &mut Vec<LocatedCommand>) -> Result<(), ParseError> {
 	let mut num_buf = String::new();
 	
 	let debug_print: bool = unsafe { debug_print_stage1 };
@@ -311,13 +724,14 @@ fn expr_to_infix(ex: VExprRef, infix: &mut Vec<Command>) -> Result<(), ParseErro
 		println!("-------------------|---------------------------|--------------------|---------------------");
 	}
 	let mut num_buf_start: usize = 0;
-	let mut i = 0;
-	for tok in ex.borrow().tokens.iter() {
+	let mut i = start;
+	for tok in ex.borrow().tokens.iter().skip(start) {
 		let mut _last_tok = if infix.len() >= 1 {
-			infix.get(infix.len() - 1).map(|c| { c.clone() })
+			infix.get(infix.len() - 1).map(|c| { c.0.clone() })
 		} else {
 			None
 		};
+		let here = edit::Span::new(ex.clone(), i, i + 1);
 		
 		match tok {
 			&VToken::Digit(ref dgt) => {
@@ -331,19 +745,20 @@ fn expr_to_infix(ex: VExprRef, infix: &mut Vec<Command>) -> Result<(), ParseErro
 			},
 			_ if num_buf.len() >= 1 => {
 				// Flush buffer
-				infix.push(try!(parse_num_buf(&num_buf, &edit::Cursor::new_ex(ex.clone(), num_buf_start))));
+				let com = try!(parse_num_buf(&num_buf, &edit::Cursor::new_ex(ex.clone(), num_buf_start)));
+				infix.push((com, edit::Span::new(ex.clone(), num_buf_start, i)));
 				num_buf.clear();
 			},
 			_ => {}
 		}
 		
 		_last_tok = if infix.len() >= 1 {
-			infix.get(infix.len() - 1).map(|c| { c.clone() })
+			infix.get(infix.len() - 1).map(|c| { c.0.clone() })
 		} else {
 			None
 		};
 		
-		let prev_tok = if i != 0 {
+		let prev_tok = if i != start {
 			ex.borrow().tokens.get(i - 1).map(|c| { c.clone() })
 		} else {
 			None
@@ -354,21 +769,34 @@ fn expr_to_infix(ex: VExprRef, infix: &mut Vec<Command>) -> Result<(), ParseErro
 				return Err(IllegalToken(VToken::Space, edit::Cursor::new_ex(ex.clone(), i)));
 			},
 			&VToken::Digit(_) => {},
+			&VToken::Ans(n) => infix.push((Com::Ans(n), here.clone())),
+			&VToken::Hist(n) => infix.push((Com::Hist(n), here.clone())),
 			&VToken::Char(ref chr) => {
 				match chr {
-					&'(' => infix.push(Com::ParenOpen),
-					&')' => infix.push(Com::ParenClose),
+					&',' => infix.push((Com::Comma, here.clone())),
 					&'.' => {},
 					_ => {
-						infix.push(Com::Var(*chr));
+						match var_name_at(&ex.borrow().tokens, i) {
+							Some((id, len)) => infix.push((Com::Var(id), edit::Span::new(ex.clone(), i, i + len))),
+							// The letter has a subscript that isn't a number
+							None if chr.is_alphabetic() => return Err(IllegalToken(ex.borrow().tokens[i + 1].clone(), edit::Cursor::new_ex(ex.clone(), i + 1))),
+							None => infix.push((Com::Var(VarName::new(*chr)), here.clone())),
+						}
 					},
 				}
 			},
+			&VToken::Sub(_) => {
+				// Subscripts are part of the name of the variable before them, and can't be used anywhere else
+				let named = i != 0 && match ex.borrow().tokens[i - 1] { VToken::Char(c) => c.is_alphabetic(), _ => false };
+				if !named {
+					return Err(IllegalToken(tok.clone(), edit::Cursor::new_ex(ex.clone(), i)));
+				}
+			},
 			&VToken::Op(ref op) => {
-				infix.push(match op {
+				let com = match op {
 					&OpType::Add => Com::Add,
 					&OpType::Sub => {
-						if prev_tok.is_none() || (match prev_tok.unwrap() { VToken::Op(_) => true, _ => false }) {
+						if prev_tok.is_none() || (match prev_tok.unwrap() { VToken::Op(_) | VToken::Char(',') => true, _ => false }) {
 							Com::Neg
 						} else {
 							Com::Sub
@@ -376,38 +804,56 @@ fn expr_to_infix(ex: VExprRef, infix: &mut Vec<Command>) -> Result<(), ParseErro
 					},
 					&OpType::Mul => Com::Mul,
 					&OpType::Div => Com::Div,
-				});
+					&OpType::PlusMinus => Com::PlusMinus,
+				};
+				infix.push((com, here.clone()));
 			},
 			&VToken::Pow(ref inner_ex) => {
-				infix.push(Com::Pow);
-				infix.push(Com::ParenOpen);
-				try!(expr_to_infix(inner_ex.clone(), infix));
-				infix.push(Com::ParenClose);
+				infix.push((Com::Pow, here.clone()));
+				infix.push((Com::ParenOpen, here.clone()));
+				try!(expr_to_infix(inner_ex.clone(), 0, infix));
+				infix.push((Com::ParenClose, here.clone()));
 			},
 			&VToken::Root(ref degree_ex, ref inner_ex) => {
-				infix.push(Com::Root);
-				infix.push(Com::ParenOpen);
-				try!(expr_to_infix(degree_ex.clone(), infix));
-				infix.push(Com::Comma);
-				try!(expr_to_infix(inner_ex.clone(), infix));
-				infix.push(Com::ParenClose);
+				infix.push((Com::Root, here.clone()));
+				infix.push((Com::ParenOpen, here.clone()));
+				try!(expr_to_infix(degree_ex.clone(), 0, infix));
+				infix.push((Com::Comma, here.clone()));
+				try!(expr_to_infix(inner_ex.clone(), 0, infix));
+				infix.push((Com::ParenClose, here.clone()));
 			},
 			&VToken::Func(ref func, ref inner_ex) => {
-				infix.push(Com::Func(func.clone()));
-				infix.push(Com::ParenOpen);
-				try!(expr_to_infix(inner_ex.clone(), infix));
-				infix.push(Com::ParenClose);
+				infix.push((Com::Func(func.clone()), here.clone()));
+				infix.push((Com::ParenOpen, here.clone()));
+				try!(expr_to_infix(inner_ex.clone(), 0, infix));
+				infix.push((Com::ParenClose, here.clone()));
+			},
+			&VToken::Delim(delim, ref inner_ex) => {
+				if let Some(func) = delim.func() {
+					infix.push((Com::Func(func), here.clone()));
+				}
+				infix.push((Com::ParenOpen, here.clone()));
+				try!(expr_to_infix(inner_ex.clone(), 0, infix));
+				infix.push((Com::ParenClose, here.clone()));
+			},
+			&VToken::List(ref inner_ex) => {
+				infix.push((Com::List(count_args(&inner_ex)), here.clone()));
+				infix.push((Com::ParenOpen, here.clone()));
+				if inner_ex.borrow().tokens.len() != 0 {
+					try!(expr_to_infix(inner_ex.clone(), 0, infix));
+				}
+				infix.push((Com::ParenClose, here.clone()));
 			},
 			&VToken::Frac(ref num_ex, ref den_ex) => {
-				infix.push(Com::ParenOpen);
-				infix.push(Com::ParenOpen);
-				try!(expr_to_infix(num_ex.clone(), infix));
-				infix.push(Com::ParenClose);
-				infix.push(Com::Div);
-				infix.push(Com::ParenOpen);
-				try!(expr_to_infix(den_ex.clone(), infix));
-				infix.push(Com::ParenClose);
-				infix.push(Com::ParenClose);
+				infix.push((Com::ParenOpen, here.clone()));
+				infix.push((Com::ParenOpen, here.clone()));
+				try!(expr_to_infix(num_ex.clone(), 0, infix));
+				infix.push((Com::ParenClose, here.clone()));
+				infix.push((Com::Div, here.clone()));
+				infix.push((Com::ParenOpen, here.clone()));
+				try!(expr_to_infix(den_ex.clone(), 0, infix));
+				infix.push((Com::ParenClose, here.clone()));
+				infix.push((Com::ParenClose, here.clone()));
 			}
 		}
 		if debug_print {
@@ -417,13 +863,14 @@ fn expr_to_infix(ex: VExprRef, infix: &mut Vec<Command>) -> Result<(), ParseErro
 			};
 			
 			println!("{: >18} | {: <25} | {: <18} | {: <20}",
-				commands_to_string(&infix, true), format!("{:?}", tok).as_str(), last, num_buf);
+				commands_to_string(&strip_locations(infix), true), format!("{:?}", tok).as_str(), last, num_buf);
 		}
 		i += 1;
 	}
 	if num_buf.len() >= 1 {
 		// Flush buffer
-		infix.push(try!(parse_num_buf(&num_buf, &edit::Cursor::new_ex(ex.clone(), num_buf_start))));
+		let com = try!(parse_num_buf(&num_buf, &edit::Cursor::new_ex(ex.clone(), num_buf_start)));
+		infix.push((com, edit::Span::new(ex.clone(), num_buf_start, i)));
 		num_buf.clear();
 	}
 	
@@ -435,14 +882,24 @@ fn expr_to_infix(ex: VExprRef, infix: &mut Vec<Command>) -> Result<(), ParseErro
 	
 	let mut i = 1;
 	while i < infix.len() {
-		let com_pair = (infix.get(i - 1).unwrap().clone(), infix.get(i).unwrap().clone());
+		let com_pair = (infix[i - 1].0.clone(), infix[i].0.clone());
 		if debug_print {
 			println!("|{: <25}, {: <25}| {: <5} i = {}", com_pair.0, com_pair.1, should_automul(com_pair.0.clone(), com_pair.1.clone()).ok().unwrap_or(false), i);
 		}
 		
 		match should_automul(com_pair.0, com_pair.1) {
 			Ok(true) => {
-				infix.insert(i, Com::Mul);
+				// The multiplication covers both of its operands if they are side by side
+				let span = {
+					let left = &infix[i - 1].1;
+					let right = &infix[i].1;
+					if is_equal_reference(&left.ex, &right.ex) && left.start < right.end {
+						edit::Span::new(left.ex.clone(), left.start, right.end)
+					} else {
+						right.clone()
+					}
+				};
+				infix.insert(i, (Com::Mul, span));
 				i += 1;
 			},
 			Ok(false) => {},
@@ -458,15 +915,25 @@ fn should_automul(left: Command, right: Command) -> Result<bool, ParseError> {
 	if right == Com::ParenOpen { // TODO: Add some more cases here
 		if let Com::Func(_) = left {
 			return Ok(false);
+		} else if let Com::List(_) = left {
+			return Ok(false);
 		} else if left == Com::Root {
 			return Ok(false);
-		} else if left == Com::Comma {
-			return Err(SyntaxError);
 		}
 	}
 	Ok(left.is_left_automul() && right.is_right_automul())
 }
 
+/// Counts the number of comma seperated arguments in `ex`. Commas inside brackets are in an inner expression, so they
+/// are not counted.
+pub fn count_args(ex: &VExprRef) -> usize {
+	let ex = ex.borrow();
+	if ex.tokens.len() == 0 {
+		return 0;
+	}
+	1 + ex.tokens.iter().filter(|tok| match tok { &&VToken::Char(',') => true, _ => false }).count()
+}
+
 fn parse_num_buf(num_buf: &str, start: &edit::Cursor) -> Result<Command, ParseError> {
 	// Flush buffer
 	let com = match num_buf.parse() {
@@ -478,9 +945,9 @@ fn parse_num_buf(num_buf: &str, start: &edit::Cursor) -> Result<Command, ParseEr
 }
 
 /// What follows is the "shunting yard algorithm" (https://en.wikipedia.org/wiki/Shunting-yard_algorithm)
-fn infix_to_postfix(infix: &[Command]) -> Result<Vec<Command>, ParseError> {
-	let mut postfix: Vec<Command> = Vec::new();
-	let mut stack: Vec<Command> = Vec::new();
+fn infix_to_postfix(infix: &[LocatedCommand]) -> Result<Vec<LocatedCommand>, ParseError> {
+	let mut postfix: Vec<LocatedCommand> = Vec::new();
+	let mut stack: Vec<LocatedCommand> = Vec::new();
 	
 	let debug_print: bool = unsafe { debug_print_stage2 };
 	
@@ -489,26 +956,27 @@ fn infix_to_postfix(infix: &[Command]) -> Result<Vec<Command>, ParseError> {
 		println!("----------------|--------------------|-------------------");
 	}
 	let mut i = 0;
-	for tok in infix {
+	for located in infix {
+		let tok = &located.0;
 		// Read a token.
 		match tok {
-			&Com::Var(_) | &Com::Num(_) => postfix.push(tok.clone()), // If the token is a number, then add it to the output queue.
-			&Com::Func(_) => stack.push(tok.clone()), // If the token is a function token, then push it onto the stack.
+			&Com::Var(_) | &Com::Ans(_) | &Com::Hist(_) | &Com::Num(_) => postfix.push(located.clone()), // If the token is a number, then add it to the output queue.
+			&Com::Func(_) | &Com::List(_) => stack.push(located.clone()), // If the token is a function token, then push it onto the stack.
 			&Com::Comma => { // If the token is a function argument separator (e.g., a comma):
 				loop { // Until the token at the top of the stack is a left parenthesis, pop operators off the stack onto the output queue.
 					match stack.pop() {
-						Some(Com::ParenOpen) => { stack.push(Com::ParenOpen); break; },
+						Some(pop) if pop.0 == Com::ParenOpen => { stack.push(pop); break; },
 						Some(pop) => postfix.push(pop),
 						None => return Err(UnmatchedParen(i)), //If no left parentheses are encountered, either the separator was misplaced or parentheses were mismatched.
 					}
 				}
 			},
-			&Com::ParenOpen => stack.push(Com::ParenOpen),  // If the token is a left parenthesis (i.e. "("), then push it onto the stack.
+			&Com::ParenOpen => stack.push(located.clone()),  // If the token is a left parenthesis (i.e. "("), then push it onto the stack.
 			&Com::ParenClose => { // If the token is a right parenthesis (i.e. ")"):
 				// Until the token at the top of the stack is a left parenthesis, pop operators off the stack onto the output queue.
 				loop {
 					match stack.pop() { // Pop the left parenthesis from the stack, but not onto the output queue.
-						Some(Com::ParenOpen) => break,
+						Some(ref pop) if pop.0 == Com::ParenOpen => break,
 						Some(v) => postfix.push(v), // If the token at the top of the stack is a function token, pop it onto the output queue.
 						None => return Err(UnmatchedParen(i)), // If the stack runs out without finding a left parenthesis, then there are mismatched parentheses.
 					}
@@ -524,11 +992,11 @@ fn infix_to_postfix(infix: &[Command]) -> Result<Vec<Command>, ParseError> {
 					//     - o1 is right associative, and has precedence less than that of o2,
 					// then pop o2 off the operator stack, onto the output queue;
 					let peek = stack[stack.len() - 1].clone();
-					if peek.is_operator() {
-						if (tok.is_left_associative() && tok.prescedence() <= peek.prescedence())
-							|| (tok.is_right_associative() && tok.prescedence() < peek.prescedence()) {
+					if peek.0.is_operator() {
+						if (tok.is_left_associative() && tok.prescedence() <= peek.0.prescedence())
+							|| (tok.is_right_associative() && tok.prescedence() < peek.0.prescedence()) {
 							stack.pop();
-							postfix.push(peek.clone());
+							postfix.push(peek);
 						} else {
 							break;
 						}
@@ -536,16 +1004,16 @@ fn infix_to_postfix(infix: &[Command]) -> Result<Vec<Command>, ParseError> {
 						break;
 					}
 				}
-				stack.push(tok.clone());
+				stack.push(located.clone());
 			},
 			_ => return Err(IllegalCommand(tok.clone(), i))
 		}
-		if debug_print { println!("{: >15} | {: <18} | {: <18}", format!("{}", tok), commands_to_string(&stack, true), commands_to_string(&postfix, true)); }
+		if debug_print { println!("{: >15} | {: <18} | {: <18}", format!("{}", tok), commands_to_string(&strip_locations(&stack), true), commands_to_string(&strip_locations(&postfix), true)); }
 		i += 1;
 	}
 	while stack.len() > 0 {
 		let pop = stack.pop().unwrap();
-		if pop == Com::ParenOpen || pop == Com::ParenClose {
+		if pop.0 == Com::ParenOpen || pop.0 == Com::ParenClose {
 			return Err(UnmatchedParen(i));
 		}
 		postfix.push(pop);
@@ -562,16 +1030,20 @@ pub fn commands_to_string(coms: &[Command], spaces: bool) -> String {
 	let mut s = String::new();
 	for com in coms.iter() {
 		match com {
-			&Com::Var(ref var) => s.push(*var),
+			&Com::Var(ref var) => { let _ = write!(s, "{}", var); },
+			&Com::Ans(n) => { let _ = write!(s, "Ans{}", subscript(n)); },
+			&Com::Hist(n) => { let _ = write!(s, "#{}", n); },
 			&Com::Num(ref v) => { let _ = write!(s, "{}", v); },
 			&Com::Add => s.push(CHAR_ADD),
 			&Com::Sub => s.push(CHAR_SUB),
 			&Com::Mul => s.push(CHAR_MUL_SIMPLE),
 			&Com::Div => s.push(CHAR_DIV),
 			&Com::Neg => s.push_str("neg"),
+			&Com::PlusMinus => s.push(CHAR_PLUS_MINUS),
 			&Com::Pow => s.push('^'),
 			&Com::Func(ref func) => { let _ = write!(s, "{}", *func); },
 			&Com::Root => s.push_str("root"),
+			&Com::List(n) => { let _ = write!(s, "list{}", n); },
 			&Com::Comma => s.push(','),
 			&Com::ParenOpen => s.push('('),
 			&Com::ParenClose => {
@@ -591,28 +1063,35 @@ pub fn commands_to_string(coms: &[Command], spaces: bool) -> String {
 
 #[test]
 fn commands_test() {
-	fn test_one(coms: &[Command], expected: Option<Num>) {
+	fn test_one(coms: &[Command], expected: Option<Value>) {
 		let res = VM::new().get_result(coms).ok();
-		print!("{} = ", commands_to_string(coms, true));
-		if res.is_some() {
-			print!("Some({}) (", res.unwrap());
-		} else {
-			print!("None (");
-		}
-		if expected.is_some() {
-			print!("Some({})) ? ", expected.unwrap());
-		} else {
-			print!("None) ? ");
-		}
-		println!("{}", res == expected);
+		println!("{} = {:?} ({:?}) ? {}", commands_to_string(coms, true), res, expected, res == expected);
 		assert_eq!(res, expected);
 	}
 	
-	test_one(&[Com::Num(Num::new(5, 0)), Com::Num(Num::new(3, 0)), Com::Num(Num::new(2, 0)), Com::Add, Com::Mul], Some(Num::new(25, 0)));
-	test_one(&[Com::Num(Num::new(5, 0)), Com::Num(Num::new(10, 0)), Com::Div], Some(Num::new(5, -1)));
-	test_one(&[Com::Num(Num::new(5, 0)), Com::Num(Num::new(10, 0)), Com::Sub], Some(Num::new(-5, 0)));
-	test_one(&[Com::Num(Num::new(5, 0)), Com::Num(Num::new(2, 0)), Com::Pow], Some(Num::new(25, 0)));
-	test_one(&[Com::Num(Num::new(5, 0)), Com::Num(Num::new(3, 0)), Com::Pow], Some(Num::new(5*5*5, 0)));
-	test_one(&[Com::Num(Num::new(25, 0)), Com::Func(FuncType::Sqrt)], Some(Num::new(5, 0)));
-	//test_one(&[Com::Num(Num::new(3, 0)), Com::Num(Num::new(5*5*5, 0)), Com::Root], Some(Num::new(5, 0)));
+	test_one(&[Com::Num(d128!(5)), Com::Num(d128!(3)), Com::Num(d128!(2)), Com::Add, Com::Mul], Some(Value::Num(d128!(25))));
+	test_one(&[Com::Num(d128!(5)), Com::Num(d128!(10)), Com::Div], Some(Value::Num(d128!(0.5))));
+	test_one(&[Com::Num(d128!(5)), Com::Num(d128!(10)), Com::Sub], Some(Value::Num(d128!(-5))));
+	test_one(&[Com::Num(d128!(5)), Com::Num(d128!(2)), Com::Pow], Some(Value::Num(d128!(25))));
+	test_one(&[Com::Num(d128!(5)), Com::Num(d128!(3)), Com::Pow], Some(Value::Num(d128!(125))));
+	test_one(&[Com::Num(d128!(25)), Com::Func(FuncType::Sqrt)], Some(Value::Num(d128!(5))));
+	//test_one(&[Com::Num(d128!(3)), Com::Num(d128!(125)), Com::Root], Some(Value::Num(d128!(5))));
+}
+
+#[test]
+fn var_name_test() {
+	use parse::parse_text;
+	// Each variable has one name, so subscripts with leading zeros aren't names
+	assert_eq!(VarName::from_text("x₁"), Some(VarName::with_sub('x', 1)));
+	assert_eq!(VarName::from_text("x₀"), Some(VarName::with_sub('x', 0)));
+	assert_eq!(VarName::from_text("x₁₀"), Some(VarName::with_sub('x', 10)));
+	assert_eq!(VarName::from_text("x₀₁"), None);
+	for &(text, id) in [("x_1", Some(VarName::with_sub('x', 1))), ("x_0", Some(VarName::with_sub('x', 0))), ("x_01", None)].iter() {
+		let ex = parse_text(text).unwrap();
+		assert_eq!(var_name_at(&ex.borrow().tokens, 0).map(|(id, _)| id), id, "the name in '{}'", text);
+	}
+	let mut vm = VM::new();
+	vm.set_var(VarName::with_sub('x', 1), Value::Num(d128!(2)));
+	assert!(vm.evaluate(&parse_text("x_1").unwrap()).is_ok());
+	assert!(vm.evaluate(&parse_text("x_01").unwrap()).is_err());
 }
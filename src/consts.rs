@@ -5,6 +5,7 @@ pub const CHAR_SUB: char = '−';
 pub const CHAR_MUL: char = '×'; // ×
 pub const CHAR_MUL_SIMPLE: char = '*';
 pub const CHAR_DIV: char = '÷';
+pub const CHAR_PLUS_MINUS: char = '±';
 pub const CHAR_BOX: char = '□';
 pub const CHAR_HLBOX: char = '■';
 
@@ -25,7 +26,7 @@ lazy_static! {
 	pub static ref D1: d128 = d128::from(1);
 	pub static ref D2: d128 = d128::from(2);
 	
-	pub static ref DP5: d128 = d128::from(5);
+	pub static ref DP5: d128 = d128!(0.5);
 	
 	pub static ref D90: d128 = d128::from(90);
 	pub static ref D180: d128 = d128::from(180);
@@ -37,6 +38,14 @@ lazy_static! {
 	pub static ref DPI: d128 = d128!(3.14159265358979323846264338327950288419716939937510582097494459230781640628620899862803);
 	/// π/2
 	pub static ref DPI2: d128 = *DPI / d128::from(2);
+	/// √π
+	pub static ref DSQRT_PI: d128 = d128!(1.772453850905516027298167483341145182797549456122387128213807789852911284591025748);
+	/// √2
+	pub static ref DSQRT_2: d128 = d128!(1.414213562373095048801688724209698078569671875376948073176679737990732478462107038850);
+	/// ln(2π)
+	pub static ref DLN_2PI: d128 = d128!(1.837877066409345483560659472811235279722794947275566825634303080965531391854519);
+	/// Relative precision that the iterative functions in `num` aim for
+	pub static ref DEPSILON: d128 = d128!(1e-34);
 	/// φ
 	pub static ref DGOLDEN_RATIO: d128 = d128!(1.6180339887498948482045868343656381177203091798057628621354486227052604628189024497072);
 	/// ∞
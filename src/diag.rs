@@ -0,0 +1,109 @@
+/// Diagnostics: the problems found with an expression, each with a message and the part of the expression it is about.
+use std::fmt::{self, Display, Formatter};
+
+use com::{self, VarName};
+use edit::Span;
+use err::*;
+use vis::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+	/// The expression can't be calculated.
+	Error,
+	/// The expression can be calculated, but probably doesn't mean what was intended.
+	Warning,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiagKind {
+	EmptyBox,
+	MissingOperand,
+	MissingBase,
+	MissingHistoryIndex,
+	MisplacedSubscript,
+	InvalidSubscript,
+	MisplacedComma,
+	MisplacedEquals,
+	DigitAfterLetter,
+	/// An error from calculating the expression.
+	Evaluation,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+	pub kind: DiagKind,
+	pub severity: Severity,
+	pub message: String,
+	pub span: Span,
+	/// The error from calculating the expression, for diagnostics of kind `Evaluation`.
+	pub cause: Option<ParseError>,
+	/// Changes that would fix the problem, best first.
+	pub fixes: Vec<Fix>,
+}
+
+impl Diagnostic {
+	pub fn error(kind: DiagKind, span: Span, message: String) -> Diagnostic {
+		Diagnostic{ kind: kind, severity: Severity::Error, message: message, span: span, cause: None, fixes: Vec::new() }
+	}
+	pub fn warning(kind: DiagKind, span: Span, message: String) -> Diagnostic {
+		Diagnostic{ kind: kind, severity: Severity::Warning, message: message, span: span, cause: None, fixes: Vec::new() }
+	}
+	pub fn with_fix(mut self, fix: Fix) -> Diagnostic {
+		self.fixes.push(fix);
+		self
+	}
+
+	/// Makes a diagnostic for an error from calculating `root_ex`. Errors that aren't about any particular part of it
+	/// are put on the whole expression. Returns `None` for errors that are already shown some other way.
+	pub fn from_eval_error(root_ex: &VExprRef, e: &ParseError) -> Option<Diagnostic> {
+		match e {
+			&ExpressionEmpty | &NoLastResult => return None,
+			_ => {},
+		}
+		let span = com::error_span(root_ex, e).unwrap_or_else(|| Span::new(root_ex.clone(), 0, root_ex.borrow().tokens.len()));
+		let mut diag = Diagnostic::error(DiagKind::Evaluation, span, format!("{}", e));
+		diag.cause = Some(e.clone());
+		if let &UndefVar(id, _) = e {
+			if !id.is_constant() {
+				diag.fixes.push(Fix::DefineVariable(id));
+			}
+		}
+		Some(diag)
+	}
+}
+
+impl Display for Diagnostic {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self.severity {
+			Severity::Error => write!(f, "error: {}", self.message),
+			Severity::Warning => write!(f, "warning: {}", self.message),
+		}
+	}
+}
+
+/// A change to the expression that fixes a problem with it.
+#[derive(Clone, Debug)]
+pub enum Fix {
+	/// Puts an empty box at `span.start`, for an operand that is missing.
+	InsertOperand(Span),
+	/// Removes the tokens covered, such as an operator typed twice.
+	Remove(Span),
+	/// Makes the digits covered a subscript of the letter before them.
+	MakeSubscript(Span),
+	/// Assigns to the variable on a new row above.
+	DefineVariable(VarName),
+}
+
+impl Display for Fix {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			&Fix::InsertOperand(_) => write!(f, "Insert a box"),
+			&Fix::Remove(ref span) => match span.ex.borrow().tokens.get(span.start..span.end) {
+				Some(tokens) => write!(f, "Remove '{}'", tokens_to_text(tokens)),
+				None => write!(f, "Remove it"),
+			},
+			&Fix::MakeSubscript(_) => write!(f, "Make it a subscript"),
+			&Fix::DefineVariable(id) => write!(f, "Define '{}'", id),
+		}
+	}
+}
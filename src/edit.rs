@@ -1,21 +1,63 @@
 use std::fmt;
 use std::time::{Duration, Instant};
 
-use gdk::enums::key;
-use gdk::{EventKey, self};
-
 use consts::*;
-use gui;
-use com;
-use render;
+use com::{self, VarName};
+use extent::Extent;
 use err::*;
 use vis::*;
 use func::*;
+use parse::{parse_text, close_brackets};
+use action::{Action, Motion};
+use diag::{Diagnostic, DiagKind, Fix};
+use session::Context;
 
 fn flash_duration() -> Duration {
 	Duration::from_millis(1000)
 }
 
+/// The maximum number of steps that can be undone.
+const UNDO_LIMIT: usize = 100;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+/// The buttons of the calculator, and the constructs that can be inserted with them.
+pub enum ButtonID {
+	Null,
+	Pow,
+	Square,
+	Sin,
+	Cos,
+	Tan,
+	Arsin,
+	Arcos,
+	Artan,
+	Sinh,
+	Cosh,
+	Tanh,
+	Arsinh,
+	Arcosh,
+	Artanh,
+	Sqrt,
+	Cbrt,
+	Frac,
+	E,
+	Ln,
+	Fact,
+	Abs,
+	List,
+	Comma,
+	Func(FuncType),
+	Ans,
+	Hist,
+	PlusMinus,
+	Undo,
+	Redo,
+	RecallExpr(usize),   // Loads the expression of the nth calculation in the history, counting from 1
+	RecallResult(usize), // Inserts a reference to the result of the nth calculation in the history
+	Var(char),
+	Const(char),
+}
+
 #[derive(Clone, Debug)]
 pub struct Cursor {
 	pub ex: VExprRef,
@@ -232,6 +274,13 @@ impl Cursor {
 		};
 		let current_token = parent_ex.borrow().tokens[i].clone();
 		let exprs = current_token.get_inner_expr();
+		// From a subscript, move up into the power above it
+		let after = parent_ex.borrow().tokens.get(i + 1).cloned();
+		if let (&VToken::Sub(_), Some(VToken::Pow(pow_ex))) = (&current_token, after) {
+			self.ex  = pow_ex;
+			self.pos = self.ex.borrow().tokens.len();
+			return true;
+		}
 		if j == 0 {
 			false
 		} else {
@@ -254,6 +303,13 @@ impl Cursor {
 		};
 		let current_token = parent_ex.borrow().tokens[i].clone();
 		let exprs = current_token.get_inner_expr();
+		// From a power, move down into the subscript below it
+		let before = if i == 0 { None } else { parent_ex.borrow().tokens.get(i - 1).cloned() };
+		if let (&VToken::Pow(_), Some(VToken::Sub(sub_ex))) = (&current_token, before) {
+			self.ex  = sub_ex;
+			self.pos = 0;
+			return true;
+		}
 		if j >= exprs.len() - 1 {
 			false
 		} else {
@@ -262,6 +318,141 @@ impl Cursor {
 			true
 		}
 	}
+	
+	/// Moves the cursor left over a whole number or name, or one position if there isn't one before it.
+	pub fn move_word_left(&mut self) -> bool {
+		let word = if self.pos == 0 { None } else { self.ex.borrow().tokens.get(self.pos - 1).and_then(word_kind) };
+		match word {
+			Some(word) => {
+				self.changed();
+				while self.pos > 0 && word_kind(&self.ex.borrow().tokens[self.pos - 1]) == Some(word) {
+					self.pos -= 1;
+				}
+				true
+			},
+			None => self.move_left(),
+		}
+	}
+	
+	/// Moves the cursor right over a whole number or name, or one position if there isn't one after it.
+	pub fn move_word_right(&mut self) -> bool {
+		let word = self.ex.borrow().tokens.get(self.pos).and_then(word_kind);
+		match word {
+			Some(word) => {
+				self.changed();
+				while self.ex.borrow().tokens.get(self.pos).and_then(word_kind) == Some(word) {
+					self.pos += 1;
+				}
+				true
+			},
+			None => self.move_right(),
+		}
+	}
+	
+	/// Moves the cursor to the start of the expression it is in. Returns true if it moved.
+	pub fn move_start(&mut self) -> bool {
+		self.changed();
+		let moved = self.pos != 0;
+		self.pos = 0;
+		moved
+	}
+	
+	/// Moves the cursor to the end of the expression it is in. Returns true if it moved.
+	pub fn move_end(&mut self) -> bool {
+		self.changed();
+		let len = self.ex.borrow().tokens.len();
+		let moved = self.pos != len;
+		self.pos = len;
+		moved
+	}
+	
+	/// Moves the cursor to the start of the root expression. Returns true if it moved.
+	pub fn move_root_start(&mut self) -> bool {
+		let mut moved = false;
+		while self.move_out() {
+			moved = true;
+		}
+		self.move_start() || moved
+	}
+	
+	/// Moves the cursor to the end of the root expression. Returns true if it moved.
+	pub fn move_root_end(&mut self) -> bool {
+		let mut moved = false;
+		while self.move_out() {
+			moved = true;
+		}
+		self.move_end() || moved
+	}
+	
+	/// Moves the cursor out of the token it is in, to just after it. Returns true if the operation was successful.
+	pub fn move_after_parent(&mut self) -> bool {
+		if self.move_out() {
+			self.pos += 1;
+			true
+		} else {
+			false
+		}
+	}
+	
+	/// Moves the cursor to the next empty expression, which is drawn as a box, going round to the start after the end.
+	/// If `back` is true, moves to the previous one instead. Returns false if there are no others.
+	pub fn move_to_slot(&mut self, back: bool) -> bool {
+		let mut root = self.ex.clone();
+		loop {
+			let parent = root.borrow().get_parent();
+			match parent {
+				Some(parent) => root = parent,
+				None => break,
+			}
+		}
+		let mut positions = Vec::new();
+		cursor_positions(&root, &mut positions);
+		let here = match positions.iter().position(|&(ref ex, pos)| is_equal_reference(ex, &self.ex) && pos == self.pos) {
+			Some(i) => i,
+			None => return false,
+		};
+		let n = positions.len();
+		for step in 1..n {
+			let i = if back { (here + n - step) % n } else { (here + step) % n };
+			let (ref ex, pos) = positions[i];
+			if ex.borrow().tokens.is_empty() && !is_equal_reference(ex, &self.ex) {
+				self.changed();
+				self.ex = ex.clone();
+				self.pos = pos;
+				return true;
+			}
+		}
+		false
+	}
+}
+
+/// The kinds of words that the cursor can move over in one go.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum WordKind {
+	Number,
+	Name, // Letters, and their subscripts
+}
+
+fn word_kind(tok: &VToken) -> Option<WordKind> {
+	match tok {
+		&VToken::Digit(_) | &VToken::Char('.') => Some(WordKind::Number),
+		&VToken::Char(c) if c.is_alphabetic() => Some(WordKind::Name),
+		&VToken::Sub(_) => Some(WordKind::Name),
+		_ => None,
+	}
+}
+
+/// Adds every position that the cursor can be at in `ex` to `positions`, in the order that moving right goes through
+/// them.
+fn cursor_positions(ex: &VExprRef, positions: &mut Vec<(VExprRef, usize)>) {
+	let tokens = ex.borrow().tokens.clone();
+	for (i, tok) in tokens.iter().enumerate() {
+		positions.push((ex.clone(), i));
+		for inner in tok.get_inner_expr().iter() {
+			cursor_positions(inner, positions);
+		}
+	}
+	positions.push((ex.clone(), tokens.len()));
 }
 
 #[derive(Clone, Debug)]
@@ -288,11 +479,36 @@ impl Span {
 	}
 }
 
+/// A copy of the expression, and of where the cursor was in it, that the editor can go back to.
+#[derive(Clone, Debug)]
+struct Snapshot {
+	root_ex: VExprRef,
+	path: Vec<(usize, usize)>, // Route to the expression the cursor was in. See `vexpr_path`.
+	pos: usize,
+}
+
+/// The kind of the last change made to the expression, so that consecutive characters typed can be undone together.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum EditKind {
+	None, // Not a change, or the cursor has been moved since the last one.
+	Typing,
+	Other,
+}
+
 pub struct Editor {
 	pub root_ex: VExprRef,
 	pub cursor: Cursor,
-	pub errors: Vec<Span>,
-	pub hitboxes: Box<[(render::Extent, Cursor)]>,
+	/// The problems with the expression, including the error from calculating it, if there was one.
+	pub diagnostics: Vec<Diagnostic>,
+	pub hitboxes: Box<[(Extent, Cursor)]>,
+	/// The boxes drawn around problems, with the index of the diagnostic for each.
+	pub error_boxes: Box<[(Extent, usize)]>,
+	/// The other end of the selection from the cursor, if there is one.
+	pub anchor: Option<Cursor>,
+	clipboard: Option<(Vec<VToken>, String)>, // Tokens last cut or copied, and the text put on the system clipboard for them
+	undo_stack: Vec<Snapshot>,
+	redo_stack: Vec<Snapshot>,
+	last_edit: EditKind,
 }
 
 impl Editor {
@@ -301,82 +517,390 @@ impl Editor {
 		Editor::with_expression(ex, 0)
 	}
 	pub fn with_expression(ex: VExprRef, pos: usize) -> Self {
-		Editor{ root_ex: ex.clone(), cursor: Cursor::new_ex(ex, pos), errors: Vec::new(), hitboxes: box [] }
+		Editor{ root_ex: ex.clone(), cursor: Cursor::new_ex(ex, pos), diagnostics: Vec::new(), hitboxes: box [], error_boxes: box [], anchor: None,
+			clipboard: None, undo_stack: Vec::new(), redo_stack: Vec::new(), last_edit: EditKind::None }
 	}
 	
-	pub fn update_hitboxes(&mut self, new_hbs: Box<[(render::Extent, Cursor)]>) {
+	fn snapshot(&self) -> Snapshot {
+		Snapshot{ root_ex: copy_vexpr(&self.root_ex, None), path: vexpr_path(&self.cursor.ex), pos: self.cursor.pos }
+	}
+	
+	fn restore(&mut self, snap: Snapshot) {
+		let ex = vexpr_at_path(&snap.root_ex, &snap.path).unwrap_or(snap.root_ex.clone());
+		let pos = ::std::cmp::min(snap.pos, ex.borrow().tokens.len());
+		self.root_ex = snap.root_ex;
+		self.cursor = Cursor::new_ex(ex, pos);
+		self.anchor = None;
+		self.hitboxes = box [];
+		self.error_boxes = box [];
+	}
+	
+	/// Records the state of the editor before a change of kind `kind`, so that the change can be undone.
+	/// Nothing is recorded if the expression hasn't actually changed.
+	fn record(&mut self, before: Snapshot, kind: EditKind) {
+		if kind == EditKind::None || vexpr_eq(&before.root_ex, &self.root_ex) {
+			self.last_edit = EditKind::None;
+			return;
+		}
+		// Carry on the step already recorded if this continues a run of characters being typed.
+		if !(kind == EditKind::Typing && self.last_edit == EditKind::Typing) {
+			self.undo_stack.push(before);
+			if self.undo_stack.len() > UNDO_LIMIT {
+				self.undo_stack.remove(0);
+			}
+		}
+		self.redo_stack.clear();
+		self.last_edit = kind;
+	}
+	
+	/// Replaces the expression being edited with a copy of `ex`, putting the cursor at the end.
+	pub fn set_expression(&mut self, ex: &VExprRef) {
+		let ex = copy_vexpr(ex, None);
+		let pos = ex.borrow().tokens.len();
+		self.root_ex = ex.clone();
+		self.cursor = Cursor::new_ex(ex, pos);
+		self.anchor = None;
+		self.hitboxes = box [];
+		self.error_boxes = box [];
+	}
+	
+	pub fn can_undo(&self) -> bool {
+		self.undo_stack.len() != 0
+	}
+	pub fn can_redo(&self) -> bool {
+		self.redo_stack.len() != 0
+	}
+	
+	/// Reverts the last change made to the expression. Returns true if there was one to revert.
+	pub fn undo(&mut self) -> bool {
+		match self.undo_stack.pop() {
+			Some(snap) => {
+				let current = self.snapshot();
+				self.redo_stack.push(current);
+				self.restore(snap);
+				self.last_edit = EditKind::None;
+				true
+			},
+			None => false,
+		}
+	}
+	
+	/// Reapplies the last change undone. Returns true if there was one to reapply.
+	pub fn redo(&mut self) -> bool {
+		match self.redo_stack.pop() {
+			Some(snap) => {
+				let current = self.snapshot();
+				self.undo_stack.push(current);
+				self.restore(snap);
+				self.last_edit = EditKind::None;
+				true
+			},
+			None => false,
+		}
+	}
+	
+	pub fn update_hitboxes(&mut self, new_hbs: Box<[(Extent, Cursor)]>, new_errs: Box<[(Extent, usize)]>) {
 		self.hitboxes = new_hbs;
+		self.error_boxes = new_errs;
 	}
 	
+	/// Checks the expression for problems. The error from calculating it is cleared, as it may no longer apply.
 	pub fn update_errors(&mut self) {
 		let mut errs = Vec::new();
 		get_errors(&self.root_ex, &mut errs);
-		self.errors = errs;
+		self.diagnostics = errs;
+	}
+	
+	/// Records the outcome of calculating the expression, replacing the error from the last calculation.
+	pub fn set_eval_result<T>(&mut self, res: &Result<T, ParseError>) {
+		self.diagnostics.retain(|d| d.kind != DiagKind::Evaluation);
+		if let &Err(ref e) = res {
+			if let Some(diag) = Diagnostic::from_eval_error(&self.root_ex, e) {
+				self.diagnostics.push(diag);
+			}
+		}
+	}
+	
+	/// Returns the diagnostic whose box is at `(x, y)`, if there is one.
+	pub fn diagnostic_at(&self, x: f64, y: f64) -> Option<&Diagnostic> {
+		self.error_boxes.iter().rev().find(|&&(ext, _)| ext.contains(x, y)).and_then(|&(_, i)| self.diagnostics.get(i))
+	}
+	
+	/// Returns the tokens selected, if any.
+	///
+	/// The selection always covers a range of tokens in a single expression, so if the cursor and the anchor are in
+	/// different expressions, the selection grows to cover the whole of the tokens that they are in.
+	pub fn selection(&self) -> Option<Span> {
+		let anchor = match self.anchor {
+			Some(ref a) => a,
+			None => return None,
+		};
+		let (a_path, c_path) = (vexpr_path(&anchor.ex), vexpr_path(&self.cursor.ex));
+		let common = a_path.iter().zip(c_path.iter()).take_while(|&(a, c)| a == c).count();
+		let ex = match vexpr_at_path(&self.root_ex, &a_path[..common]) {
+			Some(ex) => ex,
+			None => return None,
+		};
+		// The range covered by each end in the common expression
+		let range = |path: &[(usize, usize)], pos: usize| match path.get(common) {
+			Some(&(i, _)) => (i, i + 1),
+			None => (pos, pos),
+		};
+		let (a_start, a_end) = range(&a_path, anchor.pos);
+		let (c_start, c_end) = range(&c_path, self.cursor.pos);
+		let (start, end) = (::std::cmp::min(a_start, c_start), ::std::cmp::max(a_end, c_end));
+		if start == end {
+			None
+		} else {
+			Some(Span::new(ex, start, end))
+		}
+	}
+
+	/// Selects the tokens covered by `span`. If it covers none, the cursor is just moved to it.
+	pub fn select_span(&mut self, span: &Span) {
+		let len = span.ex.borrow().tokens.len();
+		let (start, end) = (::std::cmp::min(span.start, len), ::std::cmp::min(span.end, len));
+		self.anchor = if start < end { Some(Cursor::new_ex(span.ex.clone(), start)) } else { None };
+		self.cursor = Cursor::new_ex(span.ex.clone(), end);
+		self.last_edit = EditKind::None;
+	}
+
+	/// Returns the first fix for a problem at the cursor, if there is one.
+	pub fn fix_at_cursor(&self) -> Option<Fix> {
+		self.diagnostics.iter().filter(|d| d.span.contains(&self.cursor)).flat_map(|d| d.fixes.iter()).next().cloned()
+	}
+
+	/// Applies a fix to the expression, which can be undone like any other change. Fixes that need more than the
+	/// expression, such as defining a variable, are left to the worksheet.
+	/// Returns true if the expression was changed.
+	pub fn apply_fix(&mut self, fix: &Fix) -> bool {
+		let before = self.snapshot();
+		let span = match fix {
+			&Fix::InsertOperand(ref span) | &Fix::Remove(ref span) | &Fix::MakeSubscript(ref span) => span.clone(),
+			&Fix::DefineVariable(_) => return false,
+		};
+		// The fix may be from before the expression last changed
+		let len = span.ex.borrow().tokens.len();
+		if span.start > len || span.end > len {
+			return false;
+		}
+		self.anchor = None;
+		self.cursor = Cursor::new_ex(span.ex.clone(), span.start);
+		match fix {
+			&Fix::InsertOperand(_) => self.insert_delim(DelimType::Paren),
+			&Fix::Remove(_) => {
+				span.ex.borrow_mut().tokens.drain(span.start..span.end);
+			},
+			&Fix::MakeSubscript(_) => {
+				let sub = VExpr::with_parent(span.ex.clone()).to_ref();
+				let digits: Vec<VToken> = span.ex.borrow_mut().tokens.drain(span.start..span.end).collect();
+				sub.borrow_mut().tokens = digits;
+				span.ex.borrow_mut().tokens.insert(span.start, VToken::Sub(sub));
+				self.cursor.pos += 1;
+			},
+			&Fix::DefineVariable(_) => {},
+		}
+		self.record(before, EditKind::Other);
+		true
+	}
+	
+	/// Removes the tokens selected, leaving the cursor where they were. Returns true if there were any.
+	pub fn delete_selection(&mut self) -> bool {
+		let sel = self.selection();
+		self.anchor = None;
+		match sel {
+			Some(span) => {
+				span.ex.borrow_mut().tokens.drain(span.start..span.end);
+				self.cursor = Cursor::new_ex(span.ex, span.start);
+				true
+			},
+			None => false,
+		}
+	}
+	
+	/// Copies the tokens selected, putting them on the system clipboard as plain text, or as LaTeX if `latex` is true.
+	/// Returns true if anything was selected.
+	pub fn copy(&mut self, ctx: &mut Context, latex: bool) -> bool {
+		let span = match self.selection() {
+			Some(span) => span,
+			None => return false,
+		};
+		let tokens = span.ex.borrow().tokens[span.start..span.end].to_vec();
+		let text = if latex { tokens_to_latex(&tokens) } else { tokens_to_text(&tokens) };
+		ctx.frontend.set_clipboard_text(&text);
+		self.clipboard = Some((copy_tokens(&tokens, &VExpr::new_ref()), text));
+		true
+	}
+	
+	/// Copies the tokens selected, then removes them. Returns true if anything was selected.
+	pub fn cut(&mut self, ctx: &mut Context) -> bool {
+		self.copy(ctx, false) && self.delete_selection()
 	}
 	
-	/// Handles the keypress given, inserting the key pressed at the cursor's position.
-	/// Returns true if the key has been handled.
-	pub fn handle_keypress(&mut self, e: &EventKey) -> bool {
+	/// Replaces the selection with the contents of the clipboard. If the system clipboard still holds what was last cut
+	/// or copied from the editor, the tokens themselves are pasted, otherwise its text is parsed.
+	/// Returns true if anything was inserted.
+	pub fn paste(&mut self, ctx: &mut Context) -> bool {
+		let text = ctx.frontend.clipboard_text();
+		let tokens = match (&self.clipboard, &text) {
+			(&Some((ref tokens, ref copied)), &Some(ref text)) if copied == text => Some(tokens.clone()),
+			(&Some((ref tokens, _)), &None) => Some(tokens.clone()),
+			_ => None,
+		};
+		self.delete_selection();
+		match (tokens, text) {
+			(Some(tokens), _) => self.insert_tokens(&tokens),
+			// Text that is only missing brackets at the end has them added
+			(None, Some(text)) => match parse_text(&text).or_else(|e| close_brackets(&text).map_or(Err(e), |t| parse_text(&t))) {
+				Ok(ex) => {
+					let tokens = ex.borrow().tokens.clone();
+					self.insert_tokens(&tokens)
+				},
+				// Anything else is inserted as if it were typed
+				Err(_) => self.insert_str(&text),
+			},
+			(None, None) => false,
+		}
+	}
+	
+	/// Inserts copies of `tokens` at the cursor, and moves the cursor past them. Returns true if there were any.
+	pub fn insert_tokens(&mut self, tokens: &[VToken]) -> bool {
+		let tokens = copy_tokens(tokens, &self.cursor.ex);
+		let n = tokens.len();
+		{
+			let mut ex = self.cursor.ex.borrow_mut();
+			for (i, tok) in tokens.into_iter().enumerate() {
+				ex.tokens.insert(self.cursor.pos + i, tok);
+			}
+		}
+		self.cursor.pos += n;
+		n != 0
+	}
+	
+	/// Moves the cursor with `f`. If `select` is true the selection is extended to the new position, otherwise it is
+	/// cleared.
+	fn move_cursor<F>(&mut self, select: bool, f: F) where F: Fn(&mut Cursor) -> bool {
+		if select {
+			if self.anchor.is_none() {
+				self.anchor = Some(self.cursor.clone());
+			}
+		} else {
+			self.anchor = None;
+		}
+		f(&mut self.cursor);
+	}
+	
+	/// Performs an action that a key is bound to. Actions that aren't about the expression are left to the front end.
+	/// Returns true if the action has been handled.
+	pub fn handle_action(&mut self, ctx: &mut Context, action: Action) -> bool {
 		let mut dirty_exp = false;
-		let mut dirty_gui = true;
+		let before = self.snapshot();
+		let mut kind = EditKind::Other;
 		
-		match e.get_keyval() {
-			key::Left => {
-				self.cursor.move_left();
+		match action {
+			Action::Move(dir) | Action::Select(dir) => {
+				let select = match action { Action::Select(_) => true, _ => false };
+				match dir {
+					Motion::Left  => self.move_cursor(select, Cursor::move_left),
+					Motion::Right => self.move_cursor(select, Cursor::move_right),
+					Motion::Up    => self.move_cursor(select, Cursor::move_up),
+					Motion::Down  => self.move_cursor(select, Cursor::move_down),
+					Motion::WordLeft  => self.move_cursor(select, Cursor::move_word_left),
+					Motion::WordRight => self.move_cursor(select, Cursor::move_word_right),
+					Motion::Start     => self.move_cursor(select, Cursor::move_start),
+					Motion::End       => self.move_cursor(select, Cursor::move_end),
+					Motion::RootStart => self.move_cursor(select, Cursor::move_root_start),
+					Motion::RootEnd   => self.move_cursor(select, Cursor::move_root_end),
+					Motion::Out       => self.move_cursor(select, Cursor::move_after_parent),
+				}
 			},
-			key::Right => {
-				self.cursor.move_right();
+			Action::NextSlot => {
+				self.move_cursor(false, |c| c.move_to_slot(false));
 			},
-			key::Up => {
-				self.cursor.move_up();
+			Action::PrevSlot => {
+				self.move_cursor(false, |c| c.move_to_slot(true));
 			},
-			key::Down => {
-				self.cursor.move_down();
+			Action::Undo => {
+				dirty_exp = self.undo();
+				kind = EditKind::None;
 			},
-			key::F1 => unsafe {
-				com::debug_print_stage1 = !com::debug_print_stage1;
-				if com::debug_print_stage1 { println!("command debug printing stage 1 (expr->infix) on."); 
-				} else {                     println!("command debug printing stage 1 (expr->infix) off."); }
+			Action::Redo => {
+				dirty_exp = self.redo();
+				kind = EditKind::None;
 			},
-			key::F2 => unsafe {
-				com::debug_print_stage2 = !com::debug_print_stage2;
-				if com::debug_print_stage2 { println!("command debug printing stage 2 (infix->postfix) on."); 
-				} else {                     println!("command debug printing stage 2 (infix->postfix) off."); }
+			Action::Copy => {
+				self.copy(ctx, false);
 			},
-			key::F3 => unsafe {
-				com::debug_print_stage3 = !com::debug_print_stage3;
-				if com::debug_print_stage3 { println!("command debug printing stage 3 (calculation) on."); 
-				} else {                     println!("command debug printing stage 3 (calculation) off."); }
+			Action::CopyLatex => {
+				self.copy(ctx, true);
 			},
-			key::F4 => {
-				render::toggle_debug_view();
+			Action::Cut => {
+				dirty_exp = self.cut(ctx);
 			},
-			key::Delete => {
-				self.cursor.delete();
-				dirty_exp = true;
+			Action::Paste => {
+				dirty_exp = self.paste(ctx);
+			},
+			Action::Insert(id) => {
+				// Constructs are put around the selection, and anything else replaces it
+				if !wraps_term(id) && self.delete_selection() {
+					self.record(before, kind);
+				}
+				return self.handle_button_click(ctx, id);
 			},
-			key::BackSpace => {
-				self.cursor.backspace();
+			Action::Delete => {
+				if !self.delete_selection() {
+					self.cursor.delete();
+				}
 				dirty_exp = true;
 			},
-			key::Return => {
-				gui::do_calc();
-				dirty_exp = false;
-			}
-			_ => {
-				if let Some(c) = gdk::keyval_to_unicode(e.get_keyval()) {
-					dirty_exp = self.insert_char(c);
-				} else {
-					dirty_gui = false;
+			Action::Backspace => {
+				if !self.delete_selection() {
+					self.cursor.backspace();
 				}
+				dirty_exp = true;
+			},
+			_ => return false,
+		}
+		if dirty_exp {
+			self.record(before, kind);
+			ctx.frontend.dirty_expression();
+		} else {
+			self.last_edit = EditKind::None;
+			ctx.frontend.dirty_view();
+		}
+		true
+	}
+	
+	/// Handles a character typed with a key that isn't bound to an action, inserting it at the cursor's position.
+	/// While the front end is storing, a letter stores the last result in that variable instead.
+	/// Returns true if the character has been handled.
+	pub fn handle_char(&mut self, ctx: &mut Context, c: char) -> bool {
+		if ctx.frontend.storing() && c != 'e' && match c { 'a' ... 'z' | 'A' ... 'Z' => true, _ => false } {
+			// Store the last result in the variable
+			match ctx.vm.get_last_result() {
+				Ok(val) => ctx.vm.set_var(VarName::new(c), val),
+				Err(_) => {},
 			}
+			ctx.frontend.stop_storing();
+			return true;
+		}
+		let before = self.snapshot();
+		let mut kind = EditKind::Other;
+		// Typing replaces the selection
+		let deleted = self.delete_selection();
+		let dirty_exp = self.insert_char(c) || deleted;
+		// Characters that only add a single token are undone together
+		if c != '^' && c != '{' && c != '/' {
+			kind = EditKind::Typing;
 		}
 		if dirty_exp {
-			gui::dirty_expression();
-		} else if dirty_gui {
-			gui::dirty_gui();
+			self.record(before, kind);
+			ctx.frontend.dirty_expression();
+		} else {
+			self.last_edit = EditKind::None;
+			ctx.frontend.dirty_view();
 		}
-		return dirty_exp || dirty_gui;
+		true
 	}
 	
 	/// Inserts the token at `self.pos`.
@@ -392,6 +916,21 @@ impl Editor {
 					Ok(())
 				}
 			},
+			VToken::Sub(_) => {
+				let mut cursor_ex = self.cursor.ex.borrow_mut();
+				let mut pos = self.cursor.pos;
+				// A subscript goes before the power of the same base, so `x²` becomes `x₁²`
+				if pos != 0 && match cursor_ex.tokens[pos - 1] { VToken::Pow(_) => true, _ => false } {
+					pos -= 1;
+				}
+				if pos != 0 && match cursor_ex.tokens[pos - 1] { VToken::Sub(_) => true, _ => false } {
+					// There's already a subscript
+					Err(IllegalToken(tok, self.cursor.clone()))
+				} else {
+					cursor_ex.tokens.insert(pos, tok);
+					Ok(())
+				}
+			},
 			_ => {
 				self.cursor.ex.borrow_mut().tokens.insert(self.cursor.pos, tok);
 				Ok(())
@@ -399,26 +938,57 @@ impl Editor {
 		}
 	}
 	
-	/// Handles a click at the position (x, y), relative the the top left corner of the DrawingArea.
-	/// Returns if the expression should be updated.
-	pub fn handle_click(&mut self, x: f64, y: f64) -> bool {
+	/// Returns the position of the cursor that is at (x, y), relative to the top left corner of the DrawingArea.
+	fn hit_test(&self, x: f64, y: f64) -> Option<Cursor> {
 		let mut sel_area = INFINITY;
-		let mut dirty = false;
-		let mut selection = self.cursor.clone();
+		let mut selection = None;
 		
 		for &(ex, ref cur) in self.hitboxes.iter() {
 			if ex.contains(x, y) {
-				dirty = true;
 				//self.highlit_extents.push(ex);
 				let ex_area = ex.w() * ex.h();
 				if ex_area < sel_area {
 					sel_area = ex_area;
-					selection = cur.clone();
+					selection = Some(cur.clone());
 				}
 			}
 		}
-		
-		self.cursor = selection.clone();
+		selection
+	}
+	
+	/// Returns the x coordinate of the cursor when the expression was last drawn.
+	pub fn cursor_x(&self) -> Option<f64> {
+		self.hitboxes.iter()
+			.find(|&&(_, ref cur)| is_equal_reference(&cur.ex, &self.cursor.ex) && cur.pos == self.cursor.pos)
+			.map(|&(ex, _)| ex.x0())
+	}
+	
+	/// Moves the cursor to the position in the outermost expression that was drawn nearest to `x`, or the start of it
+	/// if it hasn't been drawn yet.
+	pub fn move_to_x(&mut self, x: f64) {
+		let mut best = (INFINITY, 0);
+		for &(ex, ref cur) in self.hitboxes.iter() {
+			let d = (ex.x0() - x).abs();
+			if is_equal_reference(&cur.ex, &self.root_ex) && d < best.0 {
+				best = (d, cur.pos);
+			}
+		}
+		let pos = ::std::cmp::min(best.1, self.root_ex.borrow().tokens.len());
+		self.cursor = Cursor::new_ex(self.root_ex.clone(), pos);
+		self.anchor = None;
+		self.last_edit = EditKind::None;
+	}
+	
+	/// Handles a click at the position (x, y), relative the the top left corner of the DrawingArea. If `select` is
+	/// true, the selection is extended to the position clicked.
+	/// Returns if the expression should be updated.
+	pub fn handle_click(&mut self, x: f64, y: f64, select: bool) -> bool {
+		let hit = self.hit_test(x, y);
+		let dirty = hit.is_some();
+		if let Some(cur) = hit {
+			self.move_cursor(select, |c| { *c = cur.clone(); true });
+		}
+		self.last_edit = EditKind::None;
 		
 		if dirty == true {
 			println!("mouse click: cursor moved");
@@ -429,14 +999,43 @@ impl Editor {
 		dirty
 	}
 	
+	/// Handles the mouse being dragged to (x, y) with the button held, selecting from where it was pressed.
+	/// Returns if the selection has changed.
+	pub fn handle_drag(&mut self, x: f64, y: f64) -> bool {
+		match self.hit_test(x, y) {
+			Some(cur) => {
+				if is_equal_reference(&cur.ex, &self.cursor.ex) && cur.pos == self.cursor.pos {
+					return false;
+				}
+				self.move_cursor(true, |c| { *c = cur.clone(); true });
+				true
+			},
+			None => false,
+		}
+	}
+	
 	/// Returns true if the button press has been handled.
 	#[allow(unused_mut)]
-	pub fn handle_button_click(&mut self, id: gui::ButtonID) -> bool {
+	pub fn handle_button_click(&mut self, ctx: &mut Context, id: ButtonID) -> bool {
 		let mut unhandled = false;
+		let before = self.snapshot();
+		let mut kind = EditKind::Other;
+		// Constructs are put around the selection, or if there isn't one, around the term before the cursor
+		let sel = self.selection();
+		self.anchor = None;
 		match id {
-			gui::ButtonID::Null => {}
-			gui::ButtonID::Pow => {
+			ButtonID::Null => {}
+			ButtonID::Undo => {
+				self.undo();
+				kind = EditKind::None;
+			}
+			ButtonID::Redo => {
+				self.redo();
+				kind = EditKind::None;
+			}
+			ButtonID::Pow => {
 				// Insert ^
+				self.raise_selection(sel);
 				let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
 				let exp = VToken::Pow(inner_ref.clone());
 				
@@ -449,8 +1048,9 @@ impl Editor {
 					Err(e) => println!("error: {}", e),
 				}
 			},
-			gui::ButtonID::Square => {
+			ButtonID::Square => {
 				// ^2
+				self.raise_selection(sel);
 				let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
 				inner_ref.borrow_mut().tokens.push(VToken::Digit('2'));
 				let exp = VToken::Pow(inner_ref.clone());
@@ -464,7 +1064,7 @@ impl Editor {
 					Err(e) => println!("error: {}", e),
 				}
 			},
-			gui::ButtonID::E => {
+			ButtonID::E => {
 				// e^blank
 				let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
 				let exp = VToken::Pow(inner_ref.clone());
@@ -478,71 +1078,92 @@ impl Editor {
 					Err(e) => println!("error: {}", e),
 				}
 			},
-			gui::ButtonID::Ln => {
-				self.insert_func(FuncType::Ln);
+			ButtonID::Ln => {
+				self.wrap_func(sel, FuncType::Ln);
 			},
-			gui::ButtonID::Sin => {
-				self.insert_func(FuncType::Sin);
+			ButtonID::Sin => {
+				self.wrap_func(sel, FuncType::Sin);
 			},
-			gui::ButtonID::Cos => {
-				self.insert_func(FuncType::Cos);
+			ButtonID::Cos => {
+				self.wrap_func(sel, FuncType::Cos);
 			},
-			gui::ButtonID::Tan => {
-				self.insert_func(FuncType::Tan);
+			ButtonID::Tan => {
+				self.wrap_func(sel, FuncType::Tan);
 			},
-			gui::ButtonID::Arsin => {
-				self.insert_func(FuncType::Arsin);
+			ButtonID::Arsin => {
+				self.wrap_func(sel, FuncType::Arsin);
 			},
-			gui::ButtonID::Arcos => {
-				self.insert_func(FuncType::Arcos);
+			ButtonID::Arcos => {
+				self.wrap_func(sel, FuncType::Arcos);
 			},
-			gui::ButtonID::Artan => {
-				self.insert_func(FuncType::Artan);
+			ButtonID::Artan => {
+				self.wrap_func(sel, FuncType::Artan);
 			},
-			gui::ButtonID::Sinh => {
-				self.insert_func(FuncType::Sinh);
+			ButtonID::Sinh => {
+				self.wrap_func(sel, FuncType::Sinh);
 			},
-			gui::ButtonID::Cosh => {
-				self.insert_func(FuncType::Cosh);
+			ButtonID::Cosh => {
+				self.wrap_func(sel, FuncType::Cosh);
 			},
-			gui::ButtonID::Tanh => {
-				self.insert_func(FuncType::Tanh);
+			ButtonID::Tanh => {
+				self.wrap_func(sel, FuncType::Tanh);
 			},
-			gui::ButtonID::Arsinh => {
-				self.insert_func(FuncType::Arsinh);
+			ButtonID::Arsinh => {
+				self.wrap_func(sel, FuncType::Arsinh);
 			},
-			gui::ButtonID::Arcosh => {
-				self.insert_func(FuncType::Arcosh);
+			ButtonID::Arcosh => {
+				self.wrap_func(sel, FuncType::Arcosh);
 			},
-			gui::ButtonID::Artanh => {
-				self.insert_func(FuncType::Artanh);
+			ButtonID::Artanh => {
+				self.wrap_func(sel, FuncType::Artanh);
 			},
-			gui::ButtonID::Sqrt => {
-				self.insert_func(FuncType::Sqrt);
+			ButtonID::Sqrt => {
+				self.wrap_func(sel, FuncType::Sqrt);
 			},
-			gui::ButtonID::Abs => {
-				self.insert_func(FuncType::Abs);
+			ButtonID::Abs => {
+				self.wrap_func(sel, FuncType::Abs);
 			},
-			gui::ButtonID::Fact => {
-				self.insert_func(FuncType::Fact);
+			ButtonID::Fact => {
+				self.wrap_func(sel, FuncType::Fact);
 			}
-			gui::ButtonID::Frac => {
-				// Insert ^
-				let num_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
-				let den_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
-				let frac = VToken::Frac(den_ref.clone(), num_ref.clone());
-				
-				match self.insert_token(frac) {
-					Ok(_) => {
-						// Move cursor inside
-						self.cursor.ex = num_ref;
-						self.cursor.pos = 0;
-					},
-					Err(e) => println!("error: {}", e),
+			ButtonID::Func(func) => {
+				self.wrap_func(sel, func);
+			}
+			ButtonID::List => {
+				self.insert_list();
+			}
+			ButtonID::Comma => {
+				self.insert_char(',');
+			}
+			ButtonID::Ans => {
+				if self.insert_token(VToken::Ans(0)).is_ok() {
+					self.cursor.pos += 1;
+				}
+			}
+			ButtonID::Hist => {
+				self.insert_char('#');
+			}
+			ButtonID::RecallExpr(n) => {
+				let ex = ctx.vm.get_hist_entry(n).map(|e| e.expr.clone());
+				if let Some(ex) = ex {
+					self.set_expression(&ex);
+				}
+			}
+			ButtonID::RecallResult(n) => {
+				if self.insert_token(VToken::Hist(n)).is_ok() {
+					self.cursor.pos += 1;
 				}
+			}
+			ButtonID::PlusMinus => {
+				self.insert_char(CHAR_PLUS_MINUS);
+			}
+			ButtonID::Frac => {
+				let tokens = self.take_term(sel);
+				self.insert_frac_with(tokens);
 			},
-			gui::ButtonID::Cbrt => {
+			ButtonID::Cbrt => {
 				// Produce cube root (∛)
+				let tokens = self.take_term(sel);
 				let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
 				let degree_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
 				degree_ref.borrow_mut().tokens.push(VToken::Digit('3'));
@@ -553,41 +1174,53 @@ impl Editor {
 						// Move cursor inside
 						self.cursor.ex = inner_ref;
 						self.cursor.pos = 0;
+						self.fill_construct(&tokens);
+					},
+					Err(e) => {
+						// Put the term back where it was taken from
+						self.insert_tokens(&tokens);
+						println!("error: {}", e);
 					},
-					Err(e) => println!("error: {}", e),
 				}
 			},
-			gui::ButtonID::Var(id) => {
-				if gui::get_gui_state() == gui::GuiState::Store {
-					let res = ::get_vm().get_last_result();
+			ButtonID::Var(id) => {
+				if ctx.frontend.storing() {
+					let res = ctx.vm.get_last_result();
 					if res.is_ok() {
-						::get_vm().set_var(id, res.ok().unwrap());
+						ctx.vm.set_var(VarName::new(id), res.ok().unwrap());
 					}
-					gui::set_gui_state(gui::GuiState::Normal);
+					ctx.frontend.stop_storing();
 				} else {
 					if self.insert_token(VToken::Char(id)).is_ok() {
 						self.cursor.pos += 1;
 					}
 				}
 			},
-			gui::ButtonID::Const(id) => {
+			ButtonID::Const(id) => {
 				if self.insert_token(VToken::Char(id)).is_ok() {
 					self.cursor.pos += 1;
 				}
 			}
 		}
 		
+		self.record(before, kind);
+		
 		if unhandled {
 			println!("button clicked (unhandled): {:?}", id);
 		} else {
 			println!("button clicked (  handled): {:?}", id);
-			gui::dirty_expression();
+			ctx.frontend.dirty_expression();
 		}
 		
 		return true;
 	}
 	
 	pub fn insert_func(&mut self, func: FuncType) {
+		// Functions such as `abs` are written with delimiters instead
+		if let Some(delim) = DelimType::from_func(func) {
+			self.insert_delim(delim);
+			return;
+		}
 		let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
 		let func = VToken::Func(func, inner_ref.clone());
 		
@@ -598,6 +1231,60 @@ impl Editor {
 		}
 	}
 	
+	/// Inserts a pair of delimiters, and moves the cursor inside them.
+	pub fn insert_delim(&mut self, delim: DelimType) {
+		let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
+		
+		if self.insert_token(VToken::Delim(delim, inner_ref.clone())).is_ok() {
+			// Move cursor inside
+			self.cursor.ex = inner_ref;
+			self.cursor.pos = 0;
+		}
+	}
+	
+	/// Returns true if the cursor is directly inside delimiters of the type given.
+	fn in_delim(&self, delim: DelimType) -> bool {
+		let parent = match self.cursor.ex.borrow().get_parent() {
+			Some(p) => p,
+			None => return false,
+		};
+		let i = match find_vexpr(&self.cursor.ex, &parent) {
+			Some((i, _)) => i,
+			None => return false,
+		};
+		let is_delim = match parent.borrow().tokens[i] {
+			VToken::Delim(d, _) => d == delim,
+			_ => false,
+		};
+		is_delim
+	}
+	
+	/// Moves the cursor to just after the innermost token it is in that `is_target` is true for.
+	/// Returns true if there was one.
+	fn move_after_enclosing<F>(&mut self, is_target: F) -> bool where F: Fn(&VToken) -> bool {
+		let mut cur = self.cursor.clone();
+		while cur.move_out() {
+			let found = match cur.ex.borrow().tokens.get(cur.pos) { Some(tok) => is_target(tok), None => false };
+			if found {
+				cur.pos += 1;
+				self.cursor = cur;
+				return true;
+			}
+		}
+		false
+	}
+	
+	pub fn insert_list(&mut self) {
+		let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
+		let list = VToken::List(inner_ref.clone());
+		
+		if self.insert_token(list).is_ok() {
+			// Move cursor inside
+			self.cursor.ex = inner_ref;
+			self.cursor.pos = 0;
+		}
+	}
+	
 	/// Inserts the text at `pos` in the expression `ex`.
 	///
 	/// Returns true if at least one character in teh string has been inserted.
@@ -613,35 +1300,61 @@ impl Editor {
 	pub fn insert_char(&mut self, c: char) -> bool {
 		match c {
 			'a' ... 'z' | 'A' ... 'Z' => {
-				if gui::get_gui_state() == gui::GuiState::Store && c != 'e' {
-					// Store the last result in the variable
-					match ::get_vm().get_last_result() {
-						Ok(val) => ::get_vm().set_var(c, val),
-						Err(_) => {},
-					}
-					gui::set_gui_state(gui::GuiState::Normal);
-					false
+				if self.extend_func_name(c) {
+					true
 				} else {
 					if self.insert_token(VToken::Char(c)).is_ok() {
 						self.cursor.pos += 1;
+						self.structure_word();
 						true
 					} else {
 						false
 					}
 				}
 			},
-			'(' | ')' | '.' => {
+			'{' => {
+				self.insert_list();
+				true
+			},
+			'}' => {
+				// Move to just after the list the cursor is in, if there is one.
+				self.move_after_enclosing(|tok| match tok { &VToken::List(_) => true, _ => false })
+			},
+			'|' if self.cursor.pos == self.cursor.ex.borrow().tokens.len() && self.in_delim(DelimType::Abs) => {
+				// At the end of an absolute value, `|` closes it
+				self.move_after_enclosing(|tok| match tok { &VToken::Delim(DelimType::Abs, _) => true, _ => false })
+			},
+			_ if DelimType::from_open(c).is_some() => {
+				self.insert_delim(DelimType::from_open(c).unwrap());
+				true
+			},
+			_ if DelimType::from_close(c).is_some() => {
+				// Move to just after the brackets the cursor is in, if there are any.
+				let delim = DelimType::from_close(c).unwrap();
+				self.move_after_enclosing(|tok| match tok { &VToken::Delim(d, _) => d == delim, _ => false })
+			},
+			'.' | ',' | '=' => {
 				if self.insert_token(VToken::Char(c)).is_ok() {
 					self.cursor.pos += 1;
 					true
 				} else { false }
 			},
-			_ if c.is_digit(10) => {
-				if self.insert_token(VToken::Digit(c)).is_ok() {
+			'#' => {
+				// The index is typed afterwards
+				if self.insert_token(VToken::Hist(0)).is_ok() {
 					self.cursor.pos += 1;
 					true
 				} else { false }
 			},
+			_ if c.is_digit(10) => match self.extend_history_ref(c) {
+				Some(changed) => changed,
+				None => {
+					if self.insert_token(VToken::Digit(c)).is_ok() {
+						self.cursor.pos += 1;
+						true
+					} else { false }
+				},
+			},
 			'+' => {
 				if self.insert_token(VToken::Op(OpType::Add)).is_ok() {
 					self.cursor.pos += 1;
@@ -660,12 +1373,34 @@ impl Editor {
 					true
 				} else { false }
 			},
-			'/' | CHAR_DIV => {
+			'/' => {
+				self.insert_frac();
+				true
+			},
+			CHAR_DIV => {
 				if self.insert_token(VToken::Op(OpType::Div)).is_ok() {
 					self.cursor.pos += 1;
 					true
 				} else { false }
 			},
+			CHAR_PLUS_MINUS => {
+				if self.insert_token(VToken::Op(OpType::PlusMinus)).is_ok() {
+					self.cursor.pos += 1;
+					true
+				} else { false }
+			},
+			'_' => {
+				// Insert a subscript
+				let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
+				let sub = VToken::Sub(inner_ref.clone());
+				
+				if self.insert_token(sub).is_ok() {
+					// Move cursor inside
+					self.cursor.ex = inner_ref;
+					self.cursor.pos = 0;
+					true
+				} else { false }
+			},
 			'^' => {
 				// Insert ^()
 				let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
@@ -682,6 +1417,175 @@ impl Editor {
 		}
 	}
 	
+	/// Inserts a fraction, with the term before the cursor as the numerator. The cursor is moved into the denominator,
+	/// or into the numerator if there is no term before the cursor.
+	pub fn insert_frac(&mut self) {
+		let tokens = self.take_term(None);
+		self.insert_frac_with(tokens);
+	}
+	
+	/// Inserts a fraction at the cursor with `tokens` as the numerator, and moves the cursor into the denominator, or
+	/// into the numerator if it is empty.
+	fn insert_frac_with(&mut self, mut tokens: Vec<VToken>) {
+		let num_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
+		let den_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
+		// Brackets around the whole numerator aren't needed any more
+		let inner = match tokens.first() {
+			Some(&VToken::Delim(DelimType::Paren, ref inner)) if tokens.len() == 1 => Some(inner.borrow().tokens.clone()),
+			_ => None,
+		};
+		if let Some(inner) = inner {
+			tokens = inner;
+		}
+		num_ref.borrow_mut().tokens = copy_tokens(&tokens, &num_ref);
+		self.cursor.ex.borrow_mut().tokens.insert(self.cursor.pos, VToken::Frac(num_ref.clone(), den_ref.clone()));
+		self.cursor = Cursor::with_ex(if tokens.is_empty() { num_ref } else { den_ref });
+	}
+	
+	/// Removes the tokens that a construct being inserted is to be put around, and returns them: the tokens in `sel`,
+	/// or if it is `None`, the term just before the cursor. The cursor is left where they were.
+	fn take_term(&mut self, sel: Option<Span>) -> Vec<VToken> {
+		let span = match sel {
+			Some(span) => span,
+			None => {
+				let start = term_start(&self.cursor.ex.borrow().tokens, self.cursor.pos);
+				Span::new(self.cursor.ex.clone(), start, self.cursor.pos)
+			},
+		};
+		let tokens = span.ex.borrow_mut().tokens.drain(span.start..span.end).collect();
+		self.cursor = Cursor::new_ex(span.ex, span.start);
+		tokens
+	}
+	
+	/// Inserts `tokens` into the construct that the cursor has just been moved into, then moves the cursor to just after
+	/// the construct. Does nothing if there are no tokens, leaving the cursor inside.
+	fn fill_construct(&mut self, tokens: &[VToken]) {
+		if self.insert_tokens(tokens) {
+			self.cursor.move_after_parent();
+		}
+	}
+	
+	/// Inserts a function, with the selection or the term before the cursor as its argument, e.g. making `2x` into
+	/// `2sin(x)`. If there is no such term, the cursor is moved inside the function.
+	pub fn wrap_func(&mut self, sel: Option<Span>, func: FuncType) {
+		let tokens = self.take_term(sel);
+		self.insert_func(func);
+		self.fill_construct(&tokens);
+	}
+	
+	/// Puts the selection back where it was, so that a power can be inserted after it. If it is more than one term, it is
+	/// put in brackets first, so that the power applies to all of it.
+	fn raise_selection(&mut self, sel: Option<Span>) {
+		if sel.is_none() {
+			return;
+		}
+		let tokens = self.take_term(sel);
+		if term_start(&tokens, tokens.len()) == 0 {
+			self.insert_tokens(&tokens);
+		} else {
+			self.insert_delim(DelimType::Paren);
+			self.fill_construct(&tokens);
+		}
+	}
+	
+	/// If the letters and digits just before the cursor end with the name of a function, replaces them with that
+	/// function and moves the cursor inside it. Returns true if this was done.
+	fn structure_word(&mut self) -> bool {
+		// Find the longest name that ends at the cursor
+		let mut found: Option<(usize, String)> = None;
+		{
+			let ex = self.cursor.ex.borrow();
+			let mut word = String::new();
+			let mut i = self.cursor.pos;
+			while i > 0 {
+				match ex.tokens[i - 1] {
+					VToken::Char(c) | VToken::Digit(c) if c.is_alphanumeric() => word.insert(0, c),
+					_ => break,
+				}
+				i -= 1;
+				if word == "Ans" || word == "root" || FuncType::from_name(&word).is_some() {
+					found = Some((i, word.clone()));
+				}
+			}
+		}
+		let (start, name) = match found {
+			Some(v) => v,
+			None => return false,
+		};
+		self.cursor.ex.borrow_mut().tokens.drain(start..self.cursor.pos);
+		self.cursor.pos = start;
+		match name.as_str() {
+			"Ans" => {
+				self.cursor.ex.borrow_mut().tokens.insert(start, VToken::Ans(0));
+				self.cursor.pos += 1;
+			},
+			"root" => {
+				let degree_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
+				let inner_ref = VExpr::with_parent(self.cursor.ex.clone()).to_ref();
+				self.cursor.ex.borrow_mut().tokens.insert(start, VToken::Root(degree_ref.clone(), inner_ref));
+				self.cursor = Cursor::with_ex(degree_ref);
+			},
+			_ => match FuncType::from_name(&name) {
+				Some(func) => self.insert_func(func),
+				None => {},
+			},
+		}
+		true
+	}
+	
+	/// If the cursor is at the start of an empty function, and the function's name followed by `c` is the name of
+	/// another function, changes it to that function. This allows `sinh` to be typed, even though `sin` is changed into
+	/// a function as soon as it has been typed.
+	/// Returns true if this was done.
+	fn extend_func_name(&mut self, c: char) -> bool {
+		if self.cursor.pos != 0 || self.cursor.ex.borrow().tokens.len() != 0 {
+			return false;
+		}
+		let parent = match self.cursor.ex.borrow().get_parent() {
+			Some(p) => p,
+			None => return false,
+		};
+		let i = match find_vexpr(&self.cursor.ex, &parent) {
+			Some((i, _)) => i,
+			None => return false,
+		};
+		let mut parent = parent.borrow_mut();
+		let new_func = match parent.tokens[i] {
+			VToken::Func(func, _) if func != FuncType::Sqrt => FuncType::from_name(&format!("{}{}", func, c)),
+			_ => None,
+		};
+		match new_func {
+			Some(new_func) => {
+				parent.tokens[i] = VToken::Func(new_func, self.cursor.ex.clone());
+				true
+			},
+			None => false,
+		}
+	}
+	
+	/// If the token just before the cursor is `Ans` or `#n`, appends the digit `c` to its index. Returns `None` if there
+	/// is no such token, and otherwise whether it was changed, as a digit that would make the index too large is ignored.
+	fn extend_history_ref(&mut self, c: char) -> Option<bool> {
+		if self.cursor.pos == 0 {
+			return None;
+		}
+		let d = c.to_digit(10).unwrap_or(0) as usize;
+		let extend = |n: usize| n.checked_mul(10).and_then(|n| n.checked_add(d));
+		let mut ex = self.cursor.ex.borrow_mut();
+		let new_tok = match ex.tokens.get(self.cursor.pos - 1) {
+			Some(&VToken::Ans(n)) if n != 0 || d != 0 => extend(n).map(VToken::Ans),
+			Some(&VToken::Hist(n)) => extend(n).map(VToken::Hist),
+			_ => return None,
+		};
+		match new_tok {
+			Some(tok) => {
+				ex.tokens[self.cursor.pos - 1] = tok;
+				Some(true)
+			},
+			None => Some(false),
+		}
+	}
+	
 	/// Debug prints the editor's state to the screen.
 	pub fn print(&self) {
 		match self.to_string() {
@@ -696,21 +1600,23 @@ impl Editor {
 	pub fn to_string(&self) -> Result<(String, String), fmt::Error> {
 		let mut s = String::new();
 		let mut e = String::new();
-		try!(display_vexpr_errors(self.root_ex.clone(), &Some(self.cursor.clone()), &self.errors, &mut s, &mut e));
+		let spans: Vec<Span> = self.diagnostics.iter().map(|d| d.span.clone()).collect();
+		try!(display_vexpr_errors(self.root_ex.clone(), &Some(self.cursor.clone()), &spans, &mut s, &mut e));
 		Ok((s, e))
 	}
 }
 
-fn get_errors(ex: &VExprRef, errs: &mut Vec<Span>) {
+/// Finds the problems with `ex` and the expressions inside it that can be seen without calculating it.
+fn get_errors(ex: &VExprRef, errs: &mut Vec<Diagnostic>) {
 	// If empty, error
 	if ex.borrow().tokens.len() == 0 {
-		errs.push(Span::new(ex.clone(), 0, 0));
+		errs.push(Diagnostic::error(DiagKind::EmptyBox, Span::new(ex.clone(), 0, 0), "empty box".into()));
 		return;
 	}
 	
 	// Check each operator for valid inputs
-	let mut brackets: Vec<usize> = Vec::with_capacity(16);
 	let tokens = &ex.borrow().tokens;
+	let here = |i: usize| Span::new(ex.clone(), i, i + 1);
 	let mut i = 0;
 	while i < tokens.len() {
 		// Check inner expressions for errors too
@@ -723,36 +1629,80 @@ fn get_errors(ex: &VExprRef, errs: &mut Vec<Span>) {
 		match &tokens[i] {
 			&VToken::Op(ref op) => {
 				// Check the operator is valid at that position
-				if op.clone() != OpType::Sub && (i == 0 || !is_token_term_left(&tokens[i - 1]) || i == tokens.len() - 1 || !is_token_term_right(&tokens[i + 1])) {
-					errs.push(Span::new(ex.clone(), i, i + 1));
+				let left = i != 0 && is_token_term_left(&tokens[i - 1]);
+				let right = i != tokens.len() - 1 && is_token_term_right(&tokens[i + 1]);
+				if op.clone() != OpType::Sub && (!left || !right) {
+					let diag = Diagnostic::error(DiagKind::MissingOperand, here(i), format!("'{}' needs a term on each side", op));
+					// An operator next to another was probably typed by mistake, otherwise the operand is still to come
+					let doubled = (i != 0 && is_op(&tokens[i - 1])) || (i != tokens.len() - 1 && is_op(&tokens[i + 1]));
+					errs.push(if doubled {
+						diag.with_fix(Fix::Remove(here(i)))
+					} else {
+						let pos = if left { i + 1 } else { i };
+						diag.with_fix(Fix::InsertOperand(Span::new(ex.clone(), pos, pos))).with_fix(Fix::Remove(here(i)))
+					});
 				}
 			},
+			&VToken::Hist(0) => {
+				// No index has been typed
+				errs.push(Diagnostic::error(DiagKind::MissingHistoryIndex, here(i), "'#' needs the number of a result".into())
+					.with_fix(Fix::Remove(here(i))));
+			},
 			&VToken::Pow(_) => {
 				// Check that there is a valid token before the token
 				if i == 0 || !is_token_term_left(&tokens[i - 1]) {
-					// Error
-					errs.push(Span::new(ex.clone(), i, i + 1));
+					errs.push(Diagnostic::error(DiagKind::MissingBase, here(i), "nothing to raise to the power".into())
+						.with_fix(Fix::InsertOperand(Span::new(ex.clone(), i, i))));
 				}
 			},
-			&VToken::Char('(') => {
-				brackets.push(i);
+			&VToken::Sub(ref sub) => {
+				// Only variables can have subscripts, and only numbers are valid subscripts
+				let named = i != 0 && match tokens[i - 1] { VToken::Char(c) => c.is_alphabetic(), _ => false };
+				if !named {
+					errs.push(Diagnostic::error(DiagKind::MisplacedSubscript, here(i), "only variables can have subscripts".into())
+						.with_fix(Fix::Remove(here(i))));
+				} else if sub.borrow().tokens.len() != 0 && com::subscript_number(sub).is_none() {
+					errs.push(Diagnostic::error(DiagKind::InvalidSubscript, here(i), "subscripts must be whole numbers, without leading zeros".into()));
+				}
 			},
-			&VToken::Char(')') => {
-				match brackets.pop() {
-					Some(_) => {},
-					None => {
-						errs.push(Span::new(ex.clone(), i, i + 1));
-					}
+			&VToken::Char(',') => {
+				// Commas are only valid between two terms, inside a list or a function
+				let in_args = match ex.borrow().get_parent() {
+					Some(parent) => match find_vexpr(ex, &parent) {
+						Some((j, _)) => match &parent.borrow().tokens[j] {
+							&VToken::List(_) | &VToken::Func(_, _) => true,
+							_ => false,
+						},
+						None => false,
+					},
+					None => false,
+				};
+				if !in_args || i == 0 || !is_token_term_left(&tokens[i - 1]) || i == tokens.len() - 1 || !is_token_term_right(&tokens[i + 1]) {
+					errs.push(Diagnostic::error(DiagKind::MisplacedComma, here(i),
+						"commas only go between the items of a list or the arguments of a function".into()).with_fix(Fix::Remove(here(i))));
+				}
+			},
+			&VToken::Char('=') => {
+				// `=` is only valid straight after the variable being assigned to, at the start of the equation
+				let after_name = com::var_name_at(tokens, 0).map(|(_, len)| len) == Some(i);
+				if !after_name || i == tokens.len() - 1 || com::split_assignment(ex).is_none() || ex.borrow().get_parent().is_some() {
+					errs.push(Diagnostic::error(DiagKind::MisplacedEquals, here(i),
+						"'=' can only come straight after the variable being assigned to".into()).with_fix(Fix::Remove(here(i))));
+				}
+			},
+			&VToken::Char(c) if c.is_alphabetic() => {
+				// Digits straight after a letter multiply it, which was more likely meant as a subscript
+				let digits = tokens[i + 1..].iter().take_while(|tok| match tok { &&VToken::Digit(_) => true, _ => false }).count();
+				if digits != 0 {
+					let span = Span::new(ex.clone(), i + 1, i + 1 + digits);
+					errs.push(Diagnostic::warning(DiagKind::DigitAfterLetter, span.clone(),
+						format!("this multiplies '{}'; use a subscript to name a variable", c)).with_fix(Fix::MakeSubscript(span)));
 				}
 			},
 			_ => {},
 		}
 		i += 1
 	}
-	
-	for pos in brackets.into_iter() {
-		errs.push(Span::new(ex.clone(), pos, pos + 1));
-	}
 }
 
 pub fn is_cursor_in_spans(spans: &[Span], cursor: &Cursor) -> bool {
@@ -764,20 +1714,62 @@ pub fn is_cursor_in_spans(spans: &[Span], cursor: &Cursor) -> bool {
 	false
 }
 
-fn is_token_term_left(t: &VToken) -> bool {
+/// Returns true if the button given puts a construct around the selection or the term before the cursor, rather than
+/// replacing the selection.
+fn wraps_term(id: ButtonID) -> bool {
+	use self::ButtonID::*;
+	match id {
+		Pow | Square | Sin | Cos | Tan | Arsin | Arcos | Artan | Sinh | Cosh | Tanh | Arsinh | Arcosh | Artanh
+			| Sqrt | Cbrt | Frac | Ln | Fact | Abs | Func(_) => true,
+		_ => false,
+	}
+}
+
+/// Returns the position that the term ending at `pos` in `tokens` starts at, including any powers that it is raised to.
+/// If there is no term just before `pos`, returns `pos`.
+fn term_start(tokens: &[VToken], pos: usize) -> usize {
+	let mut i = pos;
+	while i > 0 && match tokens[i - 1] { VToken::Pow(_) | VToken::Sub(_) => true, _ => false } {
+		i -= 1;
+	}
+	if i == 0 {
+		return i;
+	}
+	match tokens[i - 1] {
+		// A number's digits and decimal point are all one term
+		VToken::Digit(_) | VToken::Char('.') => {
+			while i > 0 && match tokens[i - 1] { VToken::Digit(_) | VToken::Char('.') => true, _ => false } {
+				i -= 1;
+			}
+			i
+		},
+		VToken::Op(_) | VToken::Char(',') | VToken::Char('=') | VToken::Space => i,
+		_ => i - 1,
+	}
+}
+
+fn is_op(t: &VToken) -> bool {
 	match t {
-		&VToken::Space | &VToken::Char(')') | &VToken::Digit(_) | &VToken::Pow(_)
-			| &VToken::Frac(_, _) | &VToken::Root(_, _)  => true,
-		&VToken::Char(ref c) if *c != '(' => true,
+		&VToken::Op(_) => true,
+		_ => false,
+	}
+}
+
+/// Returns true if `t` can be the term on the left of an operator, or the base of a power.
+pub fn is_token_term_left(t: &VToken) -> bool {
+	match t {
+		&VToken::Space | &VToken::Digit(_) | &VToken::Pow(_) | &VToken::Sub(_)
+			| &VToken::Frac(_, _) | &VToken::Root(_, _) | &VToken::List(_) | &VToken::Delim(_, _) | &VToken::Ans(_) | &VToken::Hist(_) => true,
+		&VToken::Char(ref c) if *c != ',' && *c != '=' => true,
 		&VToken::Op(_) | &VToken::Func(_, _) => false,
 		_ => false,
 	}
 }
 fn is_token_term_right(t: &VToken) -> bool {
 	match t {
-		&VToken::Space | &VToken::Char('(') | &VToken::Digit(_) | &VToken::Pow(_)
-			| &VToken::Frac(_, _) | &VToken::Root(_, _) | &VToken::Func(_, _) => true,
-		&VToken::Char(ref c) if *c != ')' => true,
+		&VToken::Space | &VToken::Digit(_) | &VToken::Pow(_) | &VToken::Sub(_)
+			| &VToken::Frac(_, _) | &VToken::Root(_, _) | &VToken::Func(_, _) | &VToken::List(_) | &VToken::Delim(_, _) | &VToken::Ans(_) | &VToken::Hist(_) => true,
+		&VToken::Char(ref c) if *c != ',' && *c != '=' => true,
 		&VToken::Op(_) => false,
 		_ => false,
 	}
@@ -792,4 +1784,81 @@ fn is_token_term_right(t: &VToken) -> bool {
 	Frac(VExprRef, VExprRef), // (numerator, denominator)
 	Root(VExprRef, VExprRef),
 	Func(FuncType, VExprRef),
+	List(VExprRef),
+	Ans(usize),
+	Hist(usize),
 }*/
+
+#[test]
+fn frac_test() {
+	use session::Session;
+	// Typing `/` makes the term before the cursor the numerator
+	for &(typed, numerator) in [("12/", "12"), ("3.5/", "3.5"), ("2+0.25/", "0.25"), ("4./", "4."), ("2x/", "x")].iter() {
+		let mut session = Session::new();
+		for c in typed.chars() {
+			session.sheet.editor().handle_char(&mut session.ctx, c);
+		}
+		let tokens = session.editor().root_ex.borrow().tokens.clone();
+		match tokens.last() {
+			Some(&VToken::Frac(ref num, _)) => assert_eq!(tokens_to_text(&num.borrow().tokens), numerator),
+			_ => panic!("typing '{}' didn't make a fraction", typed),
+		}
+	}
+}
+
+#[test]
+fn wrap_test() {
+	use session::Session;
+	fn inner_text(tok: Option<&VToken>) -> String {
+		match tok {
+			Some(&VToken::Func(_, ref inner)) | Some(&VToken::Root(_, ref inner)) => tokens_to_text(&inner.borrow().tokens),
+			_ => panic!("expected a function or a root, found {:?}", tok),
+		}
+	}
+	// Functions and roots are put around the term before the cursor
+	for &id in [ButtonID::Sin, ButtonID::Cbrt].iter() {
+		let mut session = Session::new();
+		for c in "1+2.5".chars() {
+			session.sheet.editor().handle_char(&mut session.ctx, c);
+		}
+		session.sheet.editor().handle_action(&mut session.ctx, Action::Insert(id));
+		let tokens = session.editor().root_ex.borrow().tokens.clone();
+		assert_eq!(tokens.len(), 3);
+		assert_eq!(inner_text(tokens.last()), "2.5");
+	}
+}
+
+#[test]
+fn history_ref_test() {
+	use session::Session;
+	let mut session = Session::new();
+	for c in "#12".chars() {
+		session.sheet.editor().handle_char(&mut session.ctx, c);
+	}
+	assert_eq!(tokens_to_text(&session.editor().root_ex.borrow().tokens), "#12");
+	// Digits that would make the index too large are ignored
+	for _ in 0..30 {
+		session.sheet.editor().handle_char(&mut session.ctx, '9');
+	}
+	let tokens = session.editor().root_ex.borrow().tokens.clone();
+	assert_eq!(tokens.len(), 1);
+	match tokens[0] {
+		VToken::Hist(n) => assert!(n > 12),
+		_ => panic!("expected a history reference, found {:?}", tokens[0]),
+	}
+}
+
+#[test]
+fn word_test() {
+	let ex = parse_text("12.5+x₁").unwrap();
+	let mut cursor = Cursor::new_ex(ex.clone(), 0);
+	// The number and the name with its subscript are moved over in one go
+	for &pos in [4, 5, 7].iter() {
+		assert!(cursor.move_word_right());
+		assert_eq!(cursor.pos, pos);
+	}
+	for &pos in [5, 4, 0].iter() {
+		assert!(cursor.move_word_left());
+		assert_eq!(cursor.pos, pos);
+	}
+}
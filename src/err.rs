@@ -3,6 +3,7 @@ use std::fmt::{self, Display, Formatter};
 pub use self::ParseError::*;
 use com;
 use edit;
+use func;
 use vis;
 
 #[derive(Debug, Clone)]
@@ -12,13 +13,45 @@ pub enum ParseError {
 	SyntaxError,
 	CommandExecuteError(com::Command, usize),
 	StackExhausted(usize),
-	UndefVar(char, usize),
+	UndefVar(com::VarName, usize),
 	IllegalChar(char, usize),
 	IllegalCommand(com::Command, usize),
 	IllegalToken(vis::VToken, edit::Cursor),
 	UnmatchedParen(usize),
 	ExpressionEmpty,
 	NoLastResult,
+	ExpectedNum(usize),
+	LengthMismatch(usize),
+	EmptyList(usize),
+	DomainError(func::FuncType, usize),
+	NoHistoryEntry(usize),
+	IncompatibleValues(usize),
+}
+
+impl ParseError {
+	/// A name for the kind of error that doesn't change, for programs that handle errors differently by kind.
+	pub fn name(&self) -> &'static str {
+		match self {
+			&GeneralError              => "general",
+			&NumParseError(_, _, _)    => "invalid_number",
+			&SyntaxError               => "syntax",
+			&CommandExecuteError(_, _) => "command_failed",
+			&StackExhausted(_)         => "missing_operand",
+			&UndefVar(_, _)            => "undefined_variable",
+			&IllegalChar(_, _)         => "illegal_character",
+			&IllegalCommand(_, _)      => "illegal_command",
+			&IllegalToken(_, _)        => "illegal_token",
+			&UnmatchedParen(_)         => "unmatched_bracket",
+			&ExpressionEmpty           => "empty_expression",
+			&NoLastResult              => "no_last_result",
+			&ExpectedNum(_)            => "expected_number",
+			&LengthMismatch(_)         => "length_mismatch",
+			&EmptyList(_)              => "empty_list",
+			&DomainError(_, _)         => "domain",
+			&NoHistoryEntry(_)         => "no_history_entry",
+			&IncompatibleValues(_)     => "incompatible_values",
+		}
+	}
 }
 
 impl Display for ParseError {
@@ -36,6 +69,12 @@ impl Display for ParseError {
 			&UnmatchedParen(_)         => write!(f, "unmatched parenthesis encountered"),
 			&ExpressionEmpty           => write!(f, "expression empty"),
 			&NoLastResult              => write!(f, "no last result calculated"),
+			&ExpectedNum(_)            => write!(f, "expected a number, found a list"),
+			&LengthMismatch(_)         => write!(f, "lists are of different lengths"),
+			&EmptyList(_)              => write!(f, "not enough values in list"),
+			&DomainError(ref func, _)  => write!(f, "invalid arguments to '{}'", func),
+			&NoHistoryEntry(_)         => write!(f, "no such result in history"),
+			&IncompatibleValues(_)     => write!(f, "intervals and uncertain values cannot be combined"),
 		}
 	}
 }
@@ -0,0 +1,72 @@
+/// Rectangles on the screen, which is where each part of an expression is drawn.
+use std::mem;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Extent {
+	pub x0: f64,
+	pub y0: f64,
+	pub x1: f64,
+	pub y1: f64
+}
+impl Extent {
+	pub fn new(ex: (f64, f64, f64, f64)) -> Extent {
+		if mem::size_of::<Extent>() == mem::size_of::<(f64, f64, f64, f64)>() {
+			unsafe { mem::transmute(ex) }
+		} else {
+			Extent { x0:ex.0, y0:ex.1, x1:ex.2, y1:ex.3 }
+		}
+	}
+	pub const fn x0(self) -> f64 {
+		self.x0
+	}
+	pub const fn y0(self) -> f64 {
+		self.y0
+	}
+	pub const fn y1(self) -> f64 {
+		self.y1
+	}
+	pub const fn w(self) -> f64 {
+		self.x1 - self.x0
+	}
+	pub const fn h(self) -> f64 {
+		self.y1 - self.y0
+	}
+	pub fn enclosing(self, other: &Extent) -> Extent {
+		Extent{
+			x0: self.x0.min(other.x0), // min x
+			y0: self.y0.min(other.y0), // min y
+			x1: self.x1.max(other.x1), // max x
+			y1: self.y1.max(other.y1)  // max y
+		}
+	}
+	pub const fn translate(self, x: f64, y: f64) -> Extent {
+		Extent {
+			x0:self.x0 + x,
+			y0:self.y0 + y,
+			x1:self.x1 + x,
+			y1:self.y1 + y,
+		}
+	}
+	/// Returns if the rectangle contains the point (x, y)
+	pub const fn contains(self, x: f64, y: f64) -> bool {
+		(x >= self.x0 && x </*=*/ self.x1) && (y >= self.y0 && y </*=*/ self.y1)
+	}
+	
+	/// Splits the extent into two equal sized extents, one on the left, one on the right.
+	/// Returns (left, right)
+	pub fn split_lr(self) -> (Extent, Extent) {
+		let w  = self.w();
+		let l = Extent{x0:self.x0, y0:self.y0, x1:self.x0 + w / 2.0, y1:self.y1};
+		let r = Extent{x0:l.x0   , y0:self.y0, x1:self.x0 + w      , y1:self.y1};
+		(l, r)
+	}
+	
+	/// Splits the extent into two equal sized extents, one on the top, one on the bottom.
+	/// Returns (top, bottom)
+	pub fn split_tb(self) -> (Extent, Extent) {
+		let h  = self.h();
+		let t = Extent{x0:self.x0, y0:self.y0, x1:self.x1, y1:self.y0 + h / 2.0};
+		let b = Extent{x0:self.x0, y0:t.y0   , x1:self.x1, y1:self.y1};
+		(t, b)
+	}
+}
@@ -3,7 +3,12 @@ use std::fmt::Error;
 
 use consts::*;
 use num::*;
-use gui;
+use val::Value;
+use interval::{self, Interval};
+use uncertain::Uncertain;
+use err::*;
+use stat;
+use com::TrigMode;
 
 use self::FuncType::*;
 
@@ -25,6 +30,37 @@ pub enum FuncType {
 	Ln,
 	Fact,
 	Abs,
+	Floor,
+	Ceil,
+	Sum,
+	Mean,
+	Median,
+	Mode,
+	Variance,
+	SampleVariance,
+	StdDev,
+	SampleStdDev,
+	Quartiles,
+	Min,
+	Max,
+	LinReg,
+	Erf,
+	Erfc,
+	NormPdf,
+	NormCdf,
+	InvNorm,
+	BinomPdf,
+	BinomCdf,
+	InvBinom,
+	PoissPdf,
+	PoissCdf,
+	InvPoiss,
+	TPdf,
+	TCdf,
+	InvT,
+	Chi2Pdf,
+	Chi2Cdf,
+	InvChi2,
 }
 
 impl Display for FuncType {
@@ -46,58 +82,425 @@ impl Display for FuncType {
 			&Ln     => "ln",
 			&Fact   => "fact",
 			&Abs    => "abs",
+			&Floor  => "floor",
+			&Ceil   => "ceil",
+			&Sum    => "sum",
+			&Mean   => "mean",
+			&Median => "median",
+			&Mode   => "mode",
+			&Variance       => "var",
+			&SampleVariance => "svar",
+			&StdDev         => "stdev",
+			&SampleStdDev   => "sstdev",
+			&Quartiles      => "quart",
+			&Min    => "min",
+			&Max    => "max",
+			&LinReg => "linreg",
+			&Erf    => "erf",
+			&Erfc   => "erfc",
+			&NormPdf  => "normpdf",
+			&NormCdf  => "normcdf",
+			&InvNorm  => "invnorm",
+			&BinomPdf => "binompdf",
+			&BinomCdf => "binomcdf",
+			&InvBinom => "invbinom",
+			&PoissPdf => "poisspdf",
+			&PoissCdf => "poisscdf",
+			&InvPoiss => "invpoiss",
+			&TPdf     => "tpdf",
+			&TCdf     => "tcdf",
+			&InvT     => "invt",
+			&Chi2Pdf  => "chi2pdf",
+			&Chi2Cdf  => "chi2cdf",
+			&InvChi2  => "invchi2",
 		};
 		return f.write_str(s);
 	}
 }
 impl FuncType {
-	pub fn execute(&self, val: d128) -> d128 {
+	/// Every function, in the order they are declared.
+	pub fn all() -> &'static [FuncType] {
+		static ALL: [FuncType; 47] = [
+			Sqrt, Sin, Cos, Tan, Arsin, Arcos, Artan, Sinh, Cosh, Tanh, Arsinh, Arcosh, Artanh,
+			Ln, Fact, Abs, Floor, Ceil,
+			Sum, Mean, Median, Mode, Variance, SampleVariance, StdDev, SampleStdDev, Quartiles, Min, Max, LinReg,
+			Erf, Erfc,
+			NormPdf, NormCdf, InvNorm, BinomPdf, BinomCdf, InvBinom, PoissPdf, PoissCdf, InvPoiss, TPdf, TCdf, InvT, Chi2Pdf, Chi2Cdf, InvChi2,
+		];
+		&ALL
+	}
+	
+	/// Finds the function with the name given, as it is displayed. `sqrt` is also accepted for `√`.
+	pub fn from_name(name: &str) -> Option<FuncType> {
+		if name == "sqrt" {
+			return Some(Sqrt);
+		}
+		FuncType::all().iter().cloned().find(|func| func.to_string() == name)
+	}
+	
+	/// Number of arguments that this function takes
+	pub fn arity(&self) -> usize {
+		match self {
+			&LinReg => 2,
+			&PoissPdf | &PoissCdf | &InvPoiss | &TPdf | &TCdf | &InvT | &Chi2Pdf | &Chi2Cdf | &InvChi2 => 2,
+			&NormPdf | &NormCdf | &InvNorm | &BinomPdf | &BinomCdf | &InvBinom => 3,
+			_ => 1,
+		}
+	}
+	
+	/// Returns true if the function operates on a whole list, rather than on each element.
+	pub fn is_list_func(&self) -> bool {
+		match self {
+			&Sum | &Mean | &Median | &Mode | &Variance | &SampleVariance | &StdDev | &SampleStdDev
+				| &Quartiles | &Min | &Max | &LinReg => true,
+			_ => false,
+		}
+	}
+	
+	/// Executes the function on the arguments given. `args.len()` must be equal to `self.arity()`. Angles are in the
+	/// units of `trig`. `pos` is the position of the command, used for errors.
+	pub fn execute(&self, args: &[Value], trig: TrigMode, pos: usize) -> Result<Value, ParseError> {
+		if args.len() != self.arity() {
+			return Err(StackExhausted(pos));
+		}
+		if !self.is_list_func() {
+			if self.arity() == 1 {
+				let f = *self;
+				return Ok(args[0].clone().map(|v| f.execute_num(v, trig), |v| f.execute_interval(v, trig), |v| f.execute_uncertain(v, trig)));
+			}
+			
+			if args.iter().any(|arg| arg.is_interval()) {
+				let a = try!(elements(args, |arg| arg.to_interval(pos)));
+				return self.execute_dist_interval(&a).map(Value::Interval).ok_or(DomainError(*self, pos));
+			} else if args.iter().any(|arg| arg.is_uncertain()) {
+				let a = try!(elements(args, |arg| arg.to_uncertain(pos)));
+				return self.execute_dist_uncertain(&a).map(Value::Uncertain).ok_or(DomainError(*self, pos));
+			}
+			let a = try!(elements(args, |arg| arg.to_num(pos)));
+			return self.execute_dist(&a).map(Value::Num).ok_or(DomainError(*self, pos));
+		}
+		
+		if args.iter().any(|arg| arg.is_interval()) {
+			return self.execute_list_interval(args, pos);
+		} else if args.iter().any(|arg| arg.is_uncertain()) {
+			let xs = try!(elements(&args[0].to_list(), |v| v.to_uncertain(pos)));
+			let ys = try!(elements(&args[args.len() - 1].to_list(), |v| v.to_uncertain(pos)));
+			return self.execute_stat(&xs, &ys, pos);
+		}
+		let xs = try!(elements(&args[0].to_list(), |v| v.to_num(pos)));
+		let ys = try!(elements(&args[args.len() - 1].to_list(), |v| v.to_num(pos)));
+		self.execute_stat(&xs, &ys, pos)
+	}
+	
+	/// Executes one of the list functions. `ys` is only used by `LinReg`.
+	fn execute_stat<T>(&self, xs: &[T], ys: &[T], pos: usize) -> Result<Value, ParseError> where T: stat::Number + Into<Value> {
+		let res = match self {
+			&Sum    => stat::sum(xs).map(T::into),
+			&Mean   => stat::mean(xs).map(T::into),
+			&Median => stat::median(xs).map(T::into),
+			&Mode   => stat::mode(xs).map(T::into),
+			&Variance       => stat::variance(xs, false).map(T::into),
+			&SampleVariance => stat::variance(xs, true).map(T::into),
+			&StdDev         => stat::std_dev(xs, false).map(T::into),
+			&SampleStdDev   => stat::std_dev(xs, true).map(T::into),
+			&Quartiles      => stat::quartiles(xs).map(|(q1, q2, q3)| Value::List(vec![q1.into(), q2.into(), q3.into()])),
+			&Min    => stat::min(xs).map(T::into),
+			&Max    => stat::max(xs).map(T::into),
+			&LinReg => {
+				if xs.len() != ys.len() {
+					return Err(LengthMismatch(pos));
+				}
+				stat::lin_reg(xs, ys).map(|(a, b, r)| Value::List(vec![a.into(), b.into(), r.into()]))
+			},
+			_ => unreachable!(),
+		};
+		res.ok_or(EmptyList(pos))
+	}
+	
+	fn execute_list_interval(&self, args: &[Value], pos: usize) -> Result<Value, ParseError> {
+		let xs = try!(elements(&args[0].to_list(), |v| v.to_interval(pos)));
+		let res = match self {
+			&Sum    => interval::sum(&xs).map(Value::Interval),
+			&Mean   => interval::mean(&xs).map(Value::Interval),
+			&Median => interval::median(&xs).map(Value::Interval),
+			&Mode   => interval::mode(&xs).map(Value::Interval),
+			&Variance       => interval::variance(&xs, false).map(Value::Interval),
+			&SampleVariance => interval::variance(&xs, true).map(Value::Interval),
+			&StdDev         => interval::std_dev(&xs, false).map(Value::Interval),
+			&SampleStdDev   => interval::std_dev(&xs, true).map(Value::Interval),
+			&Quartiles      => interval::quartiles(&xs).map(|(q1, q2, q3)| Value::List(vec![q1.into(), q2.into(), q3.into()])),
+			&Min    => interval::min(&xs).map(Value::Interval),
+			&Max    => interval::max(&xs).map(Value::Interval),
+			&LinReg => {
+				let ys = try!(elements(&args[1].to_list(), |v| v.to_interval(pos)));
+				if xs.len() != ys.len() {
+					return Err(LengthMismatch(pos));
+				}
+				interval::lin_reg(&xs, &ys).map(|(a, b, r)| Value::List(vec![a.into(), b.into(), r.into()]))
+			},
+			_ => unreachable!(),
+		};
+		res.ok_or(EmptyList(pos))
+	}
+	
+	/// Executes one of the functions taking multiple numbers. Returns `None` if the arguments are invalid.
+	fn execute_dist(&self, a: &[d128]) -> Option<d128> {
+		match self {
+			&NormPdf  => norm_pdf(a[0], a[1], a[2]),
+			&NormCdf  => norm_cdf(a[0], a[1], a[2]),
+			&InvNorm  => inv_norm(a[0], a[1], a[2]),
+			&BinomPdf => binom_pdf(a[0], a[1], a[2]),
+			&BinomCdf => binom_cdf(a[0], a[1], a[2]),
+			&InvBinom => inv_binom(a[0], a[1], a[2]),
+			&PoissPdf => poiss_pdf(a[0], a[1]),
+			&PoissCdf => poiss_cdf(a[0], a[1]),
+			&InvPoiss => inv_poiss(a[0], a[1]),
+			&TPdf     => t_pdf(a[0], a[1]),
+			&TCdf     => t_cdf(a[0], a[1]),
+			&InvT     => inv_t(a[0], a[1]),
+			&Chi2Pdf  => chi2_pdf(a[0], a[1]),
+			&Chi2Cdf  => chi2_cdf(a[0], a[1]),
+			&InvChi2  => inv_chi2(a[0], a[1]),
+			_ => unreachable!(),
+		}
+	}
+	
+	/// Interval version of `execute_dist`.
+	fn execute_dist_interval(&self, a: &[Interval]) -> Option<Interval> {
+		match self {
+			&NormPdf | &NormCdf | &InvNorm => {
+				// These are all simple transformations of the standard normal distribution
+				if a[2].lo <= *D0 {
+					return None;
+				}
+				let (mu, sigma) = (a[1], a[2]);
+				return match self {
+					&NormPdf => {
+						// φ(z) is decreasing in |z|
+						let z = ((a[0] - mu) / sigma).abs();
+						interval::monotonic(&[z], &[false], |z| norm_pdf(z[0], *D0, *D1)).map(|p| p / sigma)
+					},
+					&NormCdf => {
+						let z = (a[0] - mu) / sigma;
+						interval::monotonic(&[z], &[true], |z| norm_cdf(z[0], *D0, *D1))
+					},
+					_ => interval::monotonic(&[a[0]], &[true], |p| inv_norm(p[0], *D0, *D1)).map(|z| mu + sigma * z),
+				};
+			},
+			_ => {},
+		}
+		
+		// The direction that each function is monotonic in for each argument, or `None` if it isn't
+		let dirs: &[Option<bool>] = match self {
+			&BinomPdf => &[None, None, None],
+			&BinomCdf => &[Some(false), Some(false), Some(true)],
+			&InvBinom => &[Some(true), Some(true), Some(true)],
+			&PoissPdf => &[None, None],
+			&PoissCdf => &[Some(false), Some(true)],
+			&InvPoiss => &[Some(true), Some(true)],
+			&TPdf     => &[None, None],
+			&TCdf     => &[Some(true), None],
+			&InvT     => &[Some(true), None],
+			&Chi2Pdf  => &[None, None],
+			&Chi2Cdf  => &[Some(true), Some(false)],
+			&InvChi2  => &[Some(true), Some(true)],
+			_ => unreachable!(),
+		};
+		if a.iter().zip(dirs.iter()).all(|(x, dir)| dir.is_some() || x.is_point()) {
+			let inc: Vec<bool> = dirs.iter().map(|dir| dir.unwrap_or(true)).collect();
+			return interval::monotonic(a, &inc, |x| self.execute_dist(x));
+		}
+		
+		// Otherwise, fall back to the whole range of the function
+		Some(match self {
+			&BinomPdf | &BinomCdf | &PoissPdf | &TPdf | &TCdf => Interval::new(*D0, *D1),
+			&InvT => Interval::entire(),
+			_ => Interval::new(*D0, *DINFINITY),
+		})
+	}
+	
+	fn execute_num(&self, val: d128, trig: TrigMode) -> d128 {
 		let mut v = val;
 		if self.is_trigonometric_in() {
 			// Convert whatever is the current mode to radians
-			v = match gui::get_trig_mode() {
-				gui::TrigMode::Radians  => v,
-				gui::TrigMode::Degrees  => v / *D180 * *DPI,
-				gui::TrigMode::Gradians => v / *D200 * *DPI,
+			v = match trig {
+				TrigMode::Radians  => v,
+				TrigMode::Degrees  => v / *D180 * *DPI,
+				TrigMode::Gradians => v / *D200 * *DPI,
+			};
+		}
+		
+		v = self.execute_num_rad(v);
+		
+		if self.is_trigonometric_out() {
+			// Convert whatever is the current mode to radians
+			v = match trig {
+				TrigMode::Radians  => v,
+				TrigMode::Degrees  => v * *D180 / *DPI,
+				TrigMode::Gradians => v * *D200 / *DPI,
+			};
+		}
+		
+		v
+	}
+	
+	/// Executes the function on `x`, without converting between radians and the current trigonometric mode
+	fn execute_num_rad(&self, x: d128) -> d128 {
+		match self {
+			&Sqrt   => sqrt(x),
+			&Sin    => sin(x),
+			&Cos    => cos(x),
+			&Tan    => tan(x),
+			&Arsin  => asin(x),
+			&Arcos  => acos(x),
+			&Artan  => atan(x),
+			&Sinh   => sinh(x),
+			&Cosh   => cosh(x),
+			&Tanh   => tanh(x),
+			&Arsinh => asinh(x),
+			&Arcosh => acosh(x),
+			&Artanh => atanh(x),
+			&Ln     => ln(x),
+			&Fact   => factorial(x),
+			&Abs    => x.abs(),
+			&Floor  => floor(x),
+			&Ceil   => ceil(x),
+			&Erf    => erf(x),
+			&Erfc   => erfc(x),
+			_ => unreachable!(),
+		}
+	}
+	
+	/// Interval version of `execute_num`
+	fn execute_interval(&self, val: Interval, trig: TrigMode) -> Interval {
+		let mut v = val;
+		let pi = Interval::around(*DPI);
+		if self.is_trigonometric_in() {
+			// Convert whatever is the current mode to radians
+			v = match trig {
+				TrigMode::Radians  => v,
+				TrigMode::Degrees  => v / Interval::point(*D180) * pi,
+				TrigMode::Gradians => v / Interval::point(*D200) * pi,
 			};
 		}
 		
 		v = match self {
-			&Sqrt   => v.pow(*DP5),
-			&Sin    => sin(v),
-			&Cos    => cos(v),
-			&Tan    => tan(v),
-			&Arsin  => unimplemented!(),// asin(v),
-			&Arcos  => unimplemented!(),// acos(v),
-			&Artan  => unimplemented!(),// atan(v),
-			&Sinh   => unimplemented!(),// sinh(v),
-			&Cosh   => unimplemented!(),// cosh(v),
-			&Tanh   => unimplemented!(),// tanh(v),
-			&Arsinh => unimplemented!(),// asinh(v),
-			&Arcosh => unimplemented!(),// acosh(v),
-			&Artanh => unimplemented!(),// atanh(v),
-			&Ln     => unimplemented!(),// ln(v),
-			&Fact   => factorial(v),
+			&Sqrt   => v.sqrt(),
+			&Sin    => v.sin(),
+			&Cos    => v.cos(),
+			&Tan    => v.tan(),
+			&Arsin  => v.asin(),
+			&Arcos  => v.acos(),
+			&Artan  => v.atan(),
+			&Sinh   => v.sinh(),
+			&Cosh   => v.cosh(),
+			&Tanh   => v.tanh(),
+			&Arsinh => v.asinh(),
+			&Arcosh => v.acosh(),
+			&Artanh => v.atanh(),
+			&Ln     => v.ln(),
+			&Fact   => v.fact(),
 			&Abs    => v.abs(),
+			&Floor  => Interval::new(floor(v.lo), floor(v.hi)),
+			&Ceil   => Interval::new(ceil(v.lo), ceil(v.hi)),
+			&Erf    => v.erf(),
+			&Erfc   => v.erfc(),
+			_ => unreachable!(),
 		};
 		
 		if self.is_trigonometric_out() {
+			// Convert radians to whatever is the current mode
+			v = match trig {
+				TrigMode::Radians  => v,
+				TrigMode::Degrees  => v * Interval::point(*D180) / pi,
+				TrigMode::Gradians => v * Interval::point(*D200) / pi,
+			};
+		}
+		
+		v
+	}
+	
+	/// Uncertain version of `execute_dist`. The partial derivatives are found numerically.
+	fn execute_dist_uncertain(&self, a: &[Uncertain]) -> Option<Uncertain> {
+		let vals: Vec<d128> = a.iter().map(|x| x.val).collect();
+		let val = match self.execute_dist(&vals) { Some(v) => v, None => return None };
+		let mut partials = Vec::with_capacity(a.len());
+		for (i, x) in a.iter().enumerate() {
+			if x.terms.is_empty() {
+				continue;
+			}
+			// Central difference
+			let h = (x.val.abs() + *D1) * d128!(1e-12);
+			let mut above = vals.clone();
+			above[i] = vals[i] + h;
+			let mut below = vals.clone();
+			below[i] = vals[i] - h;
+			match (self.execute_dist(&above), self.execute_dist(&below)) {
+				(Some(fa), Some(fb)) => partials.push((x, (fa - fb) / (*D2 * h))),
+				_ => return None,
+			}
+		}
+		Some(Uncertain::propagate(val, &partials))
+	}
+	
+	/// Uncertain version of `execute_num`
+	fn execute_uncertain(&self, val: Uncertain, trig: TrigMode) -> Uncertain {
+		let mut v = val;
+		if self.is_trigonometric_in() {
 			// Convert whatever is the current mode to radians
-			v = match gui::get_trig_mode() {
-				gui::TrigMode::Radians  => v,
-				gui::TrigMode::Degrees  => v * *D180 / *DPI,
-				gui::TrigMode::Gradians => v * *D200 / *DPI,
+			v = match trig {
+				TrigMode::Radians  => v,
+				TrigMode::Degrees  => v * Uncertain::exact(*DPI / *D180),
+				TrigMode::Gradians => v * Uncertain::exact(*DPI / *D200),
+			};
+		}
+		
+		let f = *self;
+		v = v.apply(|x| f.execute_num_rad(x), |x| f.derivative(x));
+		
+		if self.is_trigonometric_out() {
+			// Convert radians to whatever is the current mode
+			v = match trig {
+				TrigMode::Radians  => v,
+				TrigMode::Degrees  => v * Uncertain::exact(*D180 / *DPI),
+				TrigMode::Gradians => v * Uncertain::exact(*D200 / *DPI),
 			};
 		}
 		
 		v
 	}
 	
+	/// The derivative of the function at `x`, in radians
+	fn derivative(&self, x: d128) -> d128 {
+		match self {
+			&Sqrt   => *D1 / (*D2 * sqrt(x)),
+			&Sin    => cos(x),
+			&Cos    => -sin(x),
+			&Tan    => *D1 / (cos(x) * cos(x)),
+			&Arsin  => *D1 / sqrt(*D1 - x * x),
+			&Arcos  => -*D1 / sqrt(*D1 - x * x),
+			&Artan  => *D1 / (*D1 + x * x),
+			&Sinh   => cosh(x),
+			&Cosh   => sinh(x),
+			&Tanh   => *D1 / (cosh(x) * cosh(x)),
+			&Arsinh => *D1 / sqrt(x * x + *D1),
+			&Arcosh => *D1 / sqrt(x * x - *D1),
+			&Artanh => *D1 / (*D1 - x * x),
+			&Ln     => *D1 / x,
+			&Fact   => factorial(x) * digamma(x + *D1),
+			&Abs    => if x.is_negative() { -*D1 } else { *D1 },
+			&Floor | &Ceil => *D0,
+			&Erf    => *D2 / *DSQRT_PI * exp(-x * x),
+			&Erfc   => -*D2 / *DSQRT_PI * exp(-x * x),
+			_ => unreachable!(),
+		}
+	}
+	
 	// This function takes in radians, gives out arbritrary numbers
 	fn is_trigonometric_in(&self) -> bool {
 		match self {
 			&Sin | &Cos | &Tan => true,
-			&Arsin | &Arcos | &Artan | &Sqrt | &Sinh | &Cosh | &Tanh | &Arsinh | &Arcosh | &Artanh | &Ln | &Fact | &Abs => false,
+			_ => false,
 		}
 	}
 	
@@ -105,7 +508,16 @@ impl FuncType {
 	fn is_trigonometric_out(&self) -> bool {
 		match self {
 			&Arsin | &Arcos | &Artan => true,
-			&Sin | &Cos | &Tan | &Sqrt | &Sinh | &Cosh | &Tanh | &Arsinh | &Arcosh | &Artanh | &Ln | &Fact | &Abs => false,
+			_ => false,
 		}
 	}
 }
+
+/// Converts each value using `f`
+fn elements<T, F>(vals: &[Value], f: F) -> Result<Vec<T>, ParseError> where F: Fn(&Value) -> Result<T, ParseError> {
+	let mut ret = Vec::with_capacity(vals.len());
+	for v in vals.iter() {
+		ret.push(try!(f(v)));
+	}
+	Ok(ret)
+}
@@ -1,58 +1,86 @@
 use gtk::prelude::*;
-use gtk::{Frame, ButtonBox, Window, EventBox, Grid, RadioButton, Button, DrawingArea, CheckButton, Orientation, ReliefStyle};
+use gtk::{self, Frame, ButtonBox, ButtonBoxStyle, Clipboard, Window, EventBox, Grid, RadioButton, Button, DrawingArea, CheckButton, Orientation, ReliefStyle};
+use gtk::{ListBox, ScrolledWindow, PolicyType, Continue};
+use gtk::{FileChooserDialog, FileChooserAction, FileFilter, MessageDialog, MessageType, ButtonsType, ResponseType};
+use gtk::{Dialog, Label, SpinButton, ColorButton};
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
 
 use gdk;
 use gdk::enums::key;
 
 use cairo::Context;
 
-use edit::Editor;
-use render::{Render, Extent, render_result};
-use com::expr_to_commands;
+use func::FuncType;
+use render::{self, Render, Extent, ExportOptions, ImageFormat, render_result, render_sheet};
+use val::Value;
+use err::ParseError;
+use sheet::Worksheet;
+use com::{self, EvalMode, TrigMode};
+use vis::vexpr_to_text;
+use save;
+use keymap::{self, Keymap, KeyResult, Chord, keys_to_string};
+use action::{Action, Mode};
+use edit::ButtonID;
+use session::Frontend;
+
+thread_local! {
+	// The list in the history panel, so that it can be refilled after each calculation.
+	static HISTORY_LIST: RefCell<Option<ListBox>> = RefCell::new(None);
+	// The list in the problems panel, so that it can be refilled when the diagnostics change.
+	static PROBLEMS_LIST: RefCell<Option<ListBox>> = RefCell::new(None);
+	// The file that the workspace was last opened from or saved to.
+	static WORKSPACE_PATH: RefCell<Option<PathBuf>> = RefCell::new(None);
+	// The key bindings, which are loaded when the gui is set up.
+	static KEYMAP: RefCell<Keymap> = RefCell::new(Keymap::new());
+	// How the expression was last exported, and whether with its result, which the export dialog starts with.
+	static EXPORT_SETTINGS: RefCell<(ExportOptions, bool)> = RefCell::new((ExportOptions::new(), true));
+	static LIVE: RefCell<LiveState> = RefCell::new(LiveState{ edit: 0, running: false, pending: false, result: None, stale: false });
+}
+
+/// How long to wait after an edit before evaluating the expression live, in milliseconds.
+const LIVE_DELAY: u32 = 300;
+/// How often to check whether a live evaluation has finished, in milliseconds.
+const LIVE_POLL: u32 = 50;
+
+/// The state of live evaluation, which evaluates the expression on another thread as it is typed.
+struct LiveState {
+	edit: u64, // Counts the edits made, so that results for old versions of the expression can be ignored
+	running: bool,
+	pending: bool, // The expression was changed while an evaluation was running
+	result: Option<Value>, // The last valid result
+	stale: bool, // The expression has changed since `result` was calculated
+}
 
 pub struct CheckButtons {
 	shift_btn: CheckButton,
 	ctrl_btn : CheckButton,
 	store_btn: CheckButton,
+	interval_btn: CheckButton,
+	live_btn    : CheckButton,
+	radians_btn : RadioButton,
+	degrees_btn : RadioButton,
+	gradians_btn: RadioButton,
 }
 impl CheckButtons {
 	pub fn new() -> CheckButtons {
+		let radians_btn = RadioButton::new_with_mnemonic("Radians");
 		CheckButtons {
 			shift_btn: CheckButton::new_with_label("SHIFT"),
 			ctrl_btn : CheckButton::new_with_label("CTRL" ),
 			store_btn: CheckButton::new_with_label("STORE"),
+			interval_btn: CheckButton::new_with_label("Intervals"),
+			live_btn    : CheckButton::new_with_label("Live"),
+			degrees_btn : RadioButton::new_with_mnemonic_from_widget(&radians_btn, "Degrees"),
+			gradians_btn: RadioButton::new_with_mnemonic_from_widget(&radians_btn, "Gradians"),
+			radians_btn : radians_btn,
 		}
 	}
 }
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
-pub enum ButtonID {
-	Null,
-	Pow,
-	Square,
-	Sin,
-	Cos,
-	Tan,
-	Arsin,
-	Arcos,
-	Artan,
-	Sinh,
-	Cosh,
-	Tanh,
-	Arsinh,
-	Arcosh,
-	Artanh,
-	Sqrt,
-	Cbrt,
-	Frac,
-	E,
-	Ln,
-	Fact,
-	Abs,
-	Var(char),
-	Const(char),
-}
-
 // The expression has changed - flush the output and queue a redraw
 // Also check the equation for errors...
 pub fn dirty_expression() {
@@ -61,30 +89,488 @@ pub fn dirty_expression() {
 	::get_editor().update_errors();
 	::get_editor().print();
 	::get_vm().clear_stack();
+	refresh_problems();
+	
+	if ::get_check_buttons().live_btn.get_active() {
+		let edit = LIVE.with(|live| {
+			let mut live = live.borrow_mut();
+			live.edit += 1;
+			live.stale = true;
+			live.edit
+		});
+		// Wait for typing to pause before evaluating
+		gtk::timeout_add(LIVE_DELAY, move || {
+			if LIVE.with(|live| live.borrow().edit) == edit {
+				start_live_eval();
+			}
+			Continue(false)
+		});
+	}
+}
+
+/// Evaluates the expression being edited on another thread. Only one evaluation is run at once, so if one is running
+/// the expression is evaluated again when it finishes.
+fn start_live_eval() {
+	let (edit, running) = LIVE.with(|live| {
+		let mut live = live.borrow_mut();
+		live.pending = live.running;
+		(live.edit, live.running)
+	});
+	if running {
+		return;
+	}
+	// Incomplete expressions can't be evaluated, so the last valid result stays.
+	let sheet = ::get_sheet();
+	let job = match sheet.job(::get_vm(), sheet.current) {
+		Ok(job) => job,
+		Err(_) => return,
+	};
+	LIVE.with(|live| live.borrow_mut().running = true);
+	let (tx, rx) = mpsc::channel();
+	thread::spawn(move || {
+		let _ = tx.send(job.run().ok());
+	});
+	gtk::timeout_add(LIVE_POLL, move || {
+		let res = match rx.try_recv() {
+			Ok(res) => res,
+			Err(TryRecvError::Empty) => return Continue(true),
+			Err(TryRecvError::Disconnected) => None,
+		};
+		let pending = LIVE.with(|live| {
+			let mut live = live.borrow_mut();
+			live.running = false;
+			if let (Some(v), true) = (res, live.edit == edit) {
+				live.result = Some(v);
+				live.stale = false;
+			}
+			live.pending
+		});
+		if pending {
+			start_live_eval();
+		}
+		::get_window().queue_draw();
+		Continue(false)
+	});
 }
 
 // Actually do the calculation of the current equation with the caching of the equation in history
 pub fn do_calc() {
 	println!("=== CALCULATING EQUATION ===");
 	::get_window().queue_draw();
-	::get_editor().update_errors();
 	::get_editor().print();
-	::get_vm().clear_stack();
 	
-	if ::get_editor().errors.len() != 0 {
-		println!("error(s) found: {:?}", ::get_editor().errors);
+	let res = ::get_session().evaluate();
+	refresh_problems();
+	match res {
+		Ok(v)  => {
+			println!("result : {}", v);
+			if let Err(e) = save::save_history(::get_vm()) {
+				println!("Warning: Could not save history: {}", e);
+			}
+			refresh_history();
+		},
+		Err(e) => println!("result : error: {}", e),
+	}
+}
+
+/// Fills the history panel with the calculations in the VM's history, most recent first.
+pub fn refresh_history() {
+	HISTORY_LIST.with(|list| {
+		let list = list.borrow();
+		let list = match list.as_ref() {
+			Some(list) => list,
+			None => return,
+		};
+		for child in list.get_children() {
+			list.remove(&child);
+		}
+		let start = ::get_vm().history_start();
+		for (i, entry) in ::get_vm().get_history().iter().enumerate() {
+			let n = start + i + 1;
+			let row = gtk::Box::new(Orientation::Horizontal, 3);
+			let expr_btn = Button::new_with_label(&vexpr_to_text(&entry.expr));
+			expr_btn.set_focus_on_click(false);
+			expr_btn.set_relief(ReliefStyle::None);
+			expr_btn.set_tooltip_text(Some("Edit this expression"));
+			expr_btn.connect_clicked(move |_| {
+				::get_editor().handle_button_click(::get_context(), ButtonID::RecallExpr(n));
+			});
+			let res_btn = Button::new_with_label(&format!("= {}", entry.result));
+			res_btn.set_focus_on_click(false);
+			res_btn.set_relief(ReliefStyle::None);
+			res_btn.set_tooltip_text(Some(&format!("Insert #{}", n)));
+			res_btn.connect_clicked(move |_| {
+				::get_editor().handle_button_click(::get_context(), ButtonID::RecallResult(n));
+			});
+			row.pack_start(&expr_btn, false, false, 0);
+			row.pack_end(&res_btn, false, false, 0);
+			list.prepend(&row);
+		}
+		list.show_all();
+	});
+}
+
+/// Fills the problems panel with the diagnostics of every row. Clicking one selects the part of the expression that it
+/// is about, and the buttons beside it apply the fixes for it.
+pub fn refresh_problems() {
+	PROBLEMS_LIST.with(|list| {
+		let list = list.borrow();
+		let list = match list.as_ref() {
+			Some(list) => list,
+			None => return,
+		};
+		for child in list.get_children() {
+			list.remove(&child);
+		}
+		let sheet = ::get_sheet();
+		let many = sheet.rows.len() > 1;
+		for (i, row) in sheet.rows.iter().enumerate() {
+			for diag in row.editor.diagnostics.iter() {
+				let text = if many { format!("Row {}: {}", i + 1, diag) } else { format!("{}", diag) };
+				let btn = Button::new_with_label(&text);
+				btn.set_focus_on_click(false);
+				btn.set_relief(ReliefStyle::None);
+				let span = diag.span.clone();
+				btn.connect_clicked(move |_| {
+					let sheet = ::get_sheet();
+					sheet.move_to(::get_vm(), i, None);
+					sheet.editor().select_span(&span);
+					dirty_expression();
+				});
+				let row = gtk::Box::new(Orientation::Horizontal, 3);
+				row.pack_start(&btn, false, false, 0);
+				for fix in diag.fixes.iter().rev() {
+					let fix_btn = Button::new_with_label(&fix.to_string());
+					fix_btn.set_focus_on_click(false);
+					let fix = fix.clone();
+					fix_btn.connect_clicked(move |_| {
+						if ::get_sheet().apply_fix(::get_vm(), i, &fix) {
+							dirty_expression();
+						}
+					});
+					row.pack_end(&fix_btn, false, false, 0);
+				}
+				list.add(&row);
+			}
+		}
+		list.show_all();
+	});
+}
+
+/// Performs an action that a key is bound to. Actions on the expression are passed on to the worksheet.
+/// Returns true if the action has been handled.
+pub fn do_action(action: Action) -> bool {
+	match action {
+		Action::Evaluate => do_calc(),
+		Action::Store => toggle_gui_state(GuiState::Store),
+		Action::Open => open_workspace(),
+		Action::Save => save_workspace(false),
+		Action::SaveAs => save_workspace(true),
+		Action::Export => export_image(),
+		Action::ShowKeys => show_keys(),
+		Action::Toggle(Mode::Shift) => toggle_gui_state(GuiState::Shift),
+		Action::Toggle(Mode::Ctrl) => toggle_gui_state(GuiState::Ctrl),
+		Action::Toggle(Mode::Intervals) => {
+			let but = &::get_check_buttons().interval_btn;
+			but.set_active(!but.get_active());
+		},
+		Action::Toggle(Mode::Live) => {
+			let but = &::get_check_buttons().live_btn;
+			but.set_active(!but.get_active());
+		},
+		Action::Toggle(Mode::Angle) => {
+			let buttons = ::get_check_buttons();
+			match ::get_vm().get_trig_mode() {
+				TrigMode::Radians  => buttons.degrees_btn.set_active(true),
+				TrigMode::Degrees  => buttons.gradians_btn.set_active(true),
+				TrigMode::Gradians => buttons.radians_btn.set_active(true),
+			}
+		},
+		Action::Toggle(Mode::DebugStage(n)) => unsafe {
+			let (on, name) = match n {
+				1 => { com::debug_print_stage1 = !com::debug_print_stage1; (com::debug_print_stage1, "stage 1 (expr->infix)") },
+				2 => { com::debug_print_stage2 = !com::debug_print_stage2; (com::debug_print_stage2, "stage 2 (infix->postfix)") },
+				_ => { com::debug_print_stage3 = !com::debug_print_stage3; (com::debug_print_stage3, "stage 3 (calculation)") },
+			};
+			println!("command debug printing {} {}.", name, if on { "on" } else { "off" });
+		},
+		Action::Toggle(Mode::DebugView) => {
+			render::toggle_debug_view();
+			dirty_gui();
+		},
+		_ => return ::get_sheet().handle_action(::get_context(), action),
+	}
+	true
+}
+
+/// Shows a dialog listing the key bindings.
+fn show_keys() {
+	let win: &Window = ::get_window();
+	let dialog = Dialog::new_with_buttons(Some("Keys"), Some(win), gtk::DIALOG_MODAL, &[("Close", ResponseType::Close.into())]);
+	let grid = Grid::new();
+	grid.set_row_spacing(3);
+	grid.set_column_spacing(12);
+	KEYMAP.with(|k| {
+		for (i, &(ref keys, action)) in k.borrow().bindings().iter().enumerate() {
+			let keys_label = Label::new(Some(keys_to_string(keys).as_str()));
+			keys_label.set_xalign(0.0);
+			let action_label = Label::new(Some(action.to_string().as_str()));
+			action_label.set_xalign(0.0);
+			grid.attach(&keys_label  , 0, i as i32, 1, 1);
+			grid.attach(&action_label, 1, i as i32, 1, 1);
+		}
+	});
+	let scroll = ScrolledWindow::new(None, None);
+	scroll.set_policy(PolicyType::Never, PolicyType::Automatic);
+	scroll.set_min_content_height(300);
+	scroll.add(&grid);
+	dialog.get_content_area().pack_start(&scroll, true, true, 0);
+	dialog.show_all();
+	dialog.run();
+	dialog.destroy();
+}
+
+/// Shows a message box with an error in it.
+fn show_error(msg: &str) {
+	let win: &Window = ::get_window();
+	let dialog = MessageDialog::new(Some(win), gtk::DIALOG_MODAL, MessageType::Error, ButtonsType::Close, msg);
+	dialog.run();
+	dialog.destroy();
+}
+
+/// Asks the user for a workspace file to open or save to. Returns `None` if they cancel.
+fn choose_workspace_file(action: FileChooserAction) -> Option<PathBuf> {
+	let (title, accept) = match action {
+		FileChooserAction::Save => ("Save Workspace", "Save"),
+		_ => ("Open Workspace", "Open"),
+	};
+	let win: &Window = ::get_window();
+	let dialog = FileChooserDialog::new(Some(title), Some(win), action);
+	dialog.add_buttons(&[("Cancel", ResponseType::Cancel.into()), (accept, ResponseType::Accept.into())]);
+	dialog.set_do_overwrite_confirmation(true);
+	let filter = FileFilter::new();
+	filter.set_name("Equator workspaces (*.json)");
+	filter.add_pattern("*.json");
+	dialog.add_filter(&filter);
+	if action == FileChooserAction::Save {
+		dialog.set_current_name("workspace.json");
+	}
+	let accept: i32 = ResponseType::Accept.into();
+	let path = if dialog.run() == accept { dialog.get_filename() } else { None };
+	dialog.destroy();
+	path
+}
+
+/// Sets the file that the workspace is saved to, and shows its name in the title of the window.
+fn set_workspace_path(path: Option<PathBuf>) {
+	WORKSPACE_PATH.with(|p| *p.borrow_mut() = path);
+	update_title();
+}
+
+/// Shows the name of the workspace file in the title of the window, followed by the keys pressed so far of a sequence
+/// of keys, if one has been started, so that it can be seen that the next key finishes it.
+fn update_title() {
+	let mut title = WORKSPACE_PATH.with(|p| match p.borrow().as_ref().and_then(|p| p.file_name()) {
+		Some(name) => format!("Equator - {}", name.to_string_lossy()),
+		None => "Equator".to_string(),
+	});
+	let pending = KEYMAP.with(|k| keys_to_string(k.borrow().pending()));
+	if !pending.is_empty() {
+		title.push_str(&format!(" [{} ...]", pending));
+	}
+	::get_window().set_title(&title);
+}
+
+/// Asks for a workspace file, and replaces the expression, variables, settings and history with those in it.
+pub fn open_workspace() {
+	let path = match choose_workspace_file(FileChooserAction::Open) {
+		Some(path) => path,
+		None => return,
+	};
+	let ws = match save::load_workspace(&path) {
+		Ok(ws) => ws,
+		Err(e) => {
+			println!("error: could not open '{}': {}", path.display(), e);
+			show_error(&format!("Could not open '{}':\n{}", path.display(), e));
+			return;
+		},
+	};
+	*::get_vm() = ws.to_vm();
+	*::get_sheet() = Worksheet::with_rows(ws.rows);
+	::get_sheet().evaluate_all(::get_vm());
+	// Setting the buttons sets the modes, as if they had been clicked.
+	let buttons = ::get_check_buttons();
+	buttons.interval_btn.set_active(ws.eval_mode == EvalMode::Interval);
+	match ws.trig_mode {
+		TrigMode::Radians  => buttons.radians_btn .set_active(true),
+		TrigMode::Degrees  => buttons.degrees_btn .set_active(true),
+		TrigMode::Gradians => buttons.gradians_btn.set_active(true),
+	}
+	set_workspace_path(Some(path));
+	refresh_history();
+	dirty_expression();
+}
+
+/// Saves the workspace to the file it was opened from, or asks for a file if it hasn't been saved yet or `save_as`
+/// is true.
+pub fn save_workspace(save_as: bool) {
+	let path = match WORKSPACE_PATH.with(|p| p.borrow().clone()) {
+		Some(ref path) if !save_as => path.clone(),
+		_ => match choose_workspace_file(FileChooserAction::Save) {
+			Some(path) => path,
+			None => return,
+		},
+	};
+	let ws = save::Workspace::current(::get_sheet(), ::get_vm());
+	match save::save_workspace(&path, &ws) {
+		Ok(_) => set_workspace_path(Some(path)),
+		Err(e) => {
+			println!("error: could not save '{}': {}", path.display(), e);
+			show_error(&format!("Could not save '{}':\n{}", path.display(), e));
+		},
+	}
+}
+
+fn to_rgba(c: (f64, f64, f64)) -> gdk::RGBA {
+	gdk::RGBA{ red: c.0, green: c.1, blue: c.2, alpha: 1.0 }
+}
+
+fn from_rgba(c: gdk::RGBA) -> (f64, f64, f64) {
+	(c.red, c.green, c.blue)
+}
+
+/// Asks for a file and how to draw the expression being edited, then exports it to the file as an image. The format
+/// is chosen by the file's extension, and is PNG if it doesn't have one.
+pub fn export_image() {
+	let (options, with_result) = EXPORT_SETTINGS.with(|s| *s.borrow());
+	let win: &Window = ::get_window();
+	let dialog = FileChooserDialog::new(Some("Export Image"), Some(win), FileChooserAction::Save);
+	dialog.add_buttons(&[("Cancel", ResponseType::Cancel.into()), ("Export", ResponseType::Accept.into())]);
+	dialog.set_do_overwrite_confirmation(true);
+	for &(name, pattern) in [("PNG images (*.png)", "*.png"), ("SVG images (*.svg)", "*.svg"), ("PDF documents (*.pdf)", "*.pdf")].iter() {
+		let filter = FileFilter::new();
+		filter.set_name(name);
+		filter.add_pattern(pattern);
+		dialog.add_filter(&filter);
+	}
+	dialog.set_current_name("equation.png");
+	
+	// The options go under the list of files
+	let scale = SpinButton::new_with_range(0.25, 16.0, 0.25);
+	scale.set_value(options.scale);
+	let padding = SpinButton::new_with_range(0.0, 200.0, 1.0);
+	padding.set_value(options.padding);
+	let foreground = ColorButton::new_with_rgba(&to_rgba(options.foreground));
+	let background = ColorButton::new_with_rgba(&to_rgba(options.background.unwrap_or((1.0, 1.0, 1.0))));
+	background.set_sensitive(options.background.is_some());
+	let transparent = CheckButton::new_with_label("Transparent");
+	transparent.set_active(options.background.is_none());
+	{
+		let background = background.clone();
+		transparent.connect_toggled(move |but| background.set_sensitive(!but.get_active()));
 	}
+	let result = CheckButton::new_with_label("Include the result");
+	result.set_active(with_result);
 	
-	let res = match expr_to_commands(::get_editor().root_ex.clone()) {
-		Ok(commands) => ::get_vm().get_result(&commands),
-		Err(e) => { println!("parse error: {}", e); return; },
+	let grid = Grid::new();
+	grid.set_row_spacing(3);
+	grid.set_column_spacing(12);
+	for (i, name) in ["Scale", "Padding", "Colour", "Background"].iter().enumerate() {
+		let label = Label::new(Some(*name));
+		label.set_xalign(0.0);
+		grid.attach(&label, 0, i as i32, 1, 1);
+	}
+	grid.attach(&scale      , 1, 0, 1, 1);
+	grid.attach(&padding    , 1, 1, 1, 1);
+	grid.attach(&foreground , 1, 2, 1, 1);
+	grid.attach(&background , 1, 3, 1, 1);
+	grid.attach(&transparent, 2, 3, 1, 1);
+	grid.attach(&result     , 0, 4, 3, 1);
+	grid.show_all();
+	dialog.set_extra_widget(&grid);
+	
+	let accept: i32 = ResponseType::Accept.into();
+	let path = if dialog.run() == accept { dialog.get_filename() } else { None };
+	let options = ExportOptions{
+		scale: scale.get_value(),
+		padding: padding.get_value(),
+		foreground: from_rgba(foreground.get_rgba()),
+		background: if transparent.get_active() { None } else { Some(from_rgba(background.get_rgba())) },
 	};
-	match res {
-		Ok(v)  => println!("result : {}", v),
-		Err(e) => println!("result : error: {}", e),
+	let with_result = result.get_active();
+	dialog.destroy();
+	let mut path = match path {
+		Some(path) => path,
+		None => return,
+	};
+	EXPORT_SETTINGS.with(|s| *s.borrow_mut() = (options, with_result));
+	
+	let format = match ImageFormat::from_path(&path) {
+		Some(format) => format,
+		None if path.extension().is_none() => {
+			path.set_extension("png");
+			ImageFormat::Png
+		},
+		None => {
+			show_error(&format!("Could not export '{}':\nImages can be exported as .png, .svg or .pdf files.", path.display()));
+			return;
+		},
+	};
+	let res = {
+		let sheet = ::get_sheet();
+		sheet.rows[sheet.current].result.clone()
+	};
+	let res = if with_result { Some(&res) } else { None };
+	if let Err(e) = render::export_expression(::get_editor(), res, format, &options, &path) {
+		println!("error: could not export '{}': {}", path.display(), e);
+		show_error(&format!("Could not export '{}':\n{}", path.display(), e));
+	}
+}
+
+/// The gui, as the front end of the editor.
+pub struct GtkFrontend;
+
+impl Frontend for GtkFrontend {
+	fn dirty_expression(&mut self) {
+		dirty_expression();
+	}
+	fn dirty_view(&mut self) {
+		dirty_gui();
+	}
+	fn clipboard_text(&mut self) -> Option<String> {
+		get_clipboard_text()
+	}
+	fn set_clipboard_text(&mut self, text: &str) {
+		set_clipboard_text(text);
+	}
+	fn storing(&self) -> bool {
+		get_gui_state() == GuiState::Store
+	}
+	fn stop_storing(&mut self) {
+		set_gui_state(GuiState::Normal);
+	}
+}
+
+/// Types the character of a key that isn't bound to an action. Keys pressed with Ctrl or Alt are shortcuts, so they
+/// never type anything.
+fn type_key(e: &gdk::EventKey) -> bool {
+	if e.get_state().intersects(gdk::CONTROL_MASK | gdk::MOD1_MASK) {
+		return false;
+	}
+	match gdk::keyval_to_unicode(e.get_keyval()) {
+		Some(c) => ::get_editor().handle_char(::get_context(), c),
+		None => false,
 	}
 }
 
+pub fn set_clipboard_text(text: &str) {
+	Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(text);
+}
+
+pub fn get_clipboard_text() -> Option<String> {
+	Clipboard::get(&gdk::SELECTION_CLIPBOARD).wait_for_text()
+}
+
 // The GUI has changed - queue a redraw buttons
 pub fn dirty_gui() {
 	println!("=== DIRTY GUI ===");
@@ -109,10 +595,24 @@ pub fn init_gui() {
 	let da_frame = Frame::new(None);
 	{
 		let eb = EventBox::new();
+		eb.add_events(gdk::BUTTON1_MOTION_MASK.bits() as i32);
 		eb.connect_button_press_event(|_, e| {
 			let (x, y) = e.get_position();
 			println!("mouse click: ({}, {})", x, y);
-			if ::get_editor().handle_click(x, y) {
+			let row = ::get_sheet().current;
+			if ::get_sheet().handle_click(::get_vm(), x, y, e.get_state().contains(gdk::SHIFT_MASK)) {
+				dirty_gui();
+			}
+			// Leaving a row may have calculated it again
+			if ::get_sheet().current != row {
+				refresh_problems();
+			}
+			
+			Inhibit(false)
+		});
+		eb.connect_motion_notify_event(|_, e| {
+			let (x, y) = e.get_position();
+			if ::get_editor().handle_drag(x, y) {
 				dirty_gui();
 			}
 			
@@ -125,13 +625,29 @@ pub fn init_gui() {
 		da.connect_draw(|w: &DrawingArea, c: &Context| {
 			let (alloc_w, alloc_h) = (w.get_allocated_width(), w.get_allocated_height());
 			
-			let mut ren = Render::new(&c, ::get_editor());
-			ren.render(alloc_w as f64, alloc_h as f64);
-			
-			::get_editor().update_hitboxes(ren.exts.hitboxes.into_boxed_slice());
+			if ::get_sheet().rows.len() == 1 {
+				let mut ren = Render::new(&c, ::get_editor());
+				ren.render(alloc_w as f64, alloc_h as f64);
+				
+				::get_editor().update_hitboxes(ren.exts.hitboxes.into_boxed_slice(), ren.exts.errors.into_boxed_slice());
+			} else {
+				render_sheet(&c, ::get_sheet(), alloc_w as f64);
+			}
 			
 			Inhibit(false)
 		});
+		// Explain the problem under the mouse
+		da.set_has_tooltip(true);
+		da.connect_query_tooltip(|_, x, y, _, tooltip| {
+			let sheet = ::get_sheet();
+			match sheet.rows.iter().filter_map(|row| row.editor.diagnostic_at(x as f64, y as f64)).next() {
+				Some(diag) => {
+					tooltip.set_text(Some(diag.message.as_str()));
+					true
+				},
+				None => false,
+			}
+		});
 		da.set_can_focus(true);
 		da.grab_focus();
 		eb.add(&da);
@@ -147,7 +663,16 @@ pub fn init_gui() {
 		da.connect_draw(|w: &DrawingArea, c: &Context| {
 			let (alloc_w, alloc_h) = (w.get_allocated_width(), w.get_allocated_height());
 			
-			render_result(&c, ::get_vm().get_last_result(), alloc_w as f64, alloc_h as f64);
+			// While the expression is being edited, show the live result, faded if it is out of date.
+			let live = LIVE.with(|live| {
+				let live = live.borrow();
+				live.result.clone().map(|v| (v, live.stale))
+			});
+			match (::get_vm().get_last_result(), live) {
+				(Err(ParseError::NoLastResult), Some((v, stale))) if ::get_check_buttons().live_btn.get_active() =>
+					render_result(&c, Ok(v), stale, alloc_w as f64, alloc_h as f64),
+				(res, _) => render_result(&c, res, false, alloc_w as f64, alloc_h as f64),
+			}
 			
 			Inhibit(false)
 		});
@@ -176,19 +701,64 @@ pub fn init_gui() {
 	});
 	var_frame.add(var_vbox);*/
 	
+	let history_frame = Frame::new(Some("History"));
+	{
+		let scroll = ScrolledWindow::new(None, None);
+		scroll.set_policy(PolicyType::Never, PolicyType::Automatic);
+		scroll.set_min_content_height(100);
+		scroll.set_size_request(200, -1);
+		scroll.set_vexpand(true);
+		let list = ListBox::new();
+		list.set_can_focus(false);
+		scroll.add(&list);
+		history_frame.add(&scroll);
+		HISTORY_LIST.with(|l| *l.borrow_mut() = Some(list));
+		refresh_history();
+	}
+	
+	let problems_frame = Frame::new(Some("Problems"));
+	{
+		let scroll = ScrolledWindow::new(None, None);
+		scroll.set_policy(PolicyType::Never, PolicyType::Automatic);
+		scroll.set_min_content_height(60);
+		let list = ListBox::new();
+		list.set_can_focus(false);
+		scroll.add(&list);
+		problems_frame.add(&scroll);
+		PROBLEMS_LIST.with(|l| *l.borrow_mut() = Some(list));
+		refresh_problems();
+	}
+	
+	match keymap::load_keymap() {
+		Ok(keymap) => KEYMAP.with(|k| *k.borrow_mut() = keymap),
+		Err(e) => println!("Warning: Could not load key bindings: {}", e),
+	}
+	
 	let button_grid = get_button_grid();     // This is the 'keypad'
+	let toolbar = get_toolbar();
 	
 	// Connect everything
 	// Need pointer to get around lifetime issue due to the fact that gtk doesn't have a lifetime.
 	// Should be fine since editor exists for all of main(), and so does gtk.
 	win.connect_key_press_event(move |_, event| {
-		let edit: &mut Editor = ::get_editor();
-		let handled = edit.handle_keypress(event);
-		
 		let c = gdk::keyval_to_unicode(event.get_keyval()).unwrap_or(' ');
 		let name = gdk::keyval_name(event.get_keyval()).unwrap_or(" ".to_string());
 		println!("keypress: {0:#08x} : {1} : {2}", event.get_keyval(), c, name);
 		
+		let handled = match Chord::from_event(event) {
+			Some(chord) => {
+				let res = KEYMAP.with(|k| k.borrow_mut().press(chord));
+				// Shows the sequence started, or takes it away once it has been finished or abandoned
+				update_title();
+				match res {
+					KeyResult::Action(action) => do_action(action),
+					KeyResult::Pending => true,
+					KeyResult::Unbound => type_key(event),
+				}
+			},
+			None => false,
+		};
+		
 		match event.get_keyval() {
 			key::Shift_L   | key::Shift_R   => set_gui_state(GuiState::Shift),
 			key::Control_L | key::Control_R => set_gui_state(GuiState::Ctrl),
@@ -208,10 +778,13 @@ pub fn init_gui() {
 	});
 	
 	// Add
-	main_grid.attach(&da_frame   , 0, 0, 1, 1);
-	main_grid.attach(&res_frame  , 0, 1, 1, 1);
-	main_grid.attach(&var_frame  , 1, 0, 1, 2);
-	main_grid.attach(&button_grid, 0, 2, 2, 1);
+	main_grid.attach(&toolbar    , 0, 0, 2, 1);
+	main_grid.attach(&da_frame   , 0, 1, 1, 1);
+	main_grid.attach(&res_frame  , 0, 2, 1, 1);
+	main_grid.attach(&var_frame  , 1, 1, 1, 2);
+	main_grid.attach(&button_grid, 0, 3, 2, 1);
+	main_grid.attach(&history_frame, 2, 1, 1, 3);
+	main_grid.attach(&problems_frame, 0, 4, 3, 1);
 	
 	win.add(&main_grid);
 	//da_frame.grab_focus();
@@ -220,6 +793,37 @@ pub fn init_gui() {
 	win.show_all();
 }
 
+fn get_toolbar() -> ButtonBox {
+	let toolbar = ButtonBox::new(Orientation::Horizontal);
+	toolbar.set_layout(ButtonBoxStyle::Start);
+	toolbar.set_spacing(3);
+	toolbar.add(&make_toolbar_button("Open"   , || open_workspace()      , || true));
+	toolbar.add(&make_toolbar_button("Save"   , || save_workspace(false) , || true));
+	toolbar.add(&make_toolbar_button("Save As", || save_workspace(true)  , || true));
+	toolbar.add(&make_toolbar_button("Export" , || export_image()        , || true));
+	toolbar.add(&make_toolbar_button("Undo"   , || { ::get_editor().handle_button_click(::get_context(), ButtonID::Undo); }, || ::get_editor().can_undo()));
+	toolbar.add(&make_toolbar_button("Redo"   , || { ::get_editor().handle_button_click(::get_context(), ButtonID::Redo); }, || ::get_editor().can_redo()));
+	toolbar.add(&make_toolbar_button("Keys"   , || show_keys()           , || true));
+	toolbar
+}
+
+/// Makes a button that calls `clicked` when it is clicked, and is only enabled when `enabled` returns true.
+fn make_toolbar_button<F, G>(label: &'static str, clicked: F, enabled: G) -> Button
+		where F: Fn() + 'static, G: Fn() -> bool + 'static {
+	let but = Button::new_with_label(label);
+	but.set_focus_on_click(false);
+	but.set_relief(ReliefStyle::None);
+	but.connect_clicked(move |_| clicked());
+	but.connect_draw(move |but, _| {
+		let e = enabled();
+		if but.get_sensitive() != e {
+			but.set_sensitive(e);
+		}
+		Inhibit(false)
+	});
+	but
+}
+
 fn get_button_grid() -> Grid {
 	// Get grid & size it
 	let grid = Grid::new();
@@ -235,17 +839,17 @@ fn get_button_grid() -> Grid {
 	// Insert the radians/degrees selector
 	let frame = Frame::new(None);
 	{
-		let rb_radians = RadioButton::new_with_mnemonic("Radians");
+		let rb_radians = ::get_check_buttons().radians_btn.clone();
 		rb_radians.set_focus_on_click(false);
 		rb_radians.set_relief(ReliefStyle::None);
 		rb_radians.connect_clicked(|but| { if but.get_active() { set_trig_mode(TrigMode::Radians); } });
 		
-		let rb_degrees = RadioButton::new_with_mnemonic_from_widget(&rb_radians, "Degrees");
+		let rb_degrees = ::get_check_buttons().degrees_btn.clone();
 		rb_degrees.set_focus_on_click(false);
 		rb_degrees.set_relief(ReliefStyle::None);
 		rb_degrees.connect_clicked(|but| { if but.get_active() { set_trig_mode(TrigMode::Degrees); } });
 		
-		let rb_gradians = RadioButton::new_with_mnemonic_from_widget(&rb_radians, "Gradians");
+		let rb_gradians = ::get_check_buttons().gradians_btn.clone();
 		rb_gradians.set_focus_on_click(false);
 		rb_gradians.set_relief(ReliefStyle::None);
 		rb_gradians.connect_clicked(|but| { if but.get_active() { set_trig_mode(TrigMode::Gradians); } });
@@ -259,6 +863,23 @@ fn get_button_grid() -> Grid {
 	}
 	grid.attach(&frame, 0, 0, 1, 3);
 	
+	// Insert the interval mode toggle
+	let interval_btn = ::get_check_buttons().interval_btn.clone();
+	interval_btn.set_focus_on_click(false);
+	interval_btn.connect_toggled(|but| {
+		::get_vm().set_mode(if but.get_active() { EvalMode::Interval } else { EvalMode::Normal });
+		dirty_expression();
+	});
+	grid.attach(&interval_btn, 0, 3, 1, 1);
+	
+	// Insert the live evaluation toggle
+	let live_btn = ::get_check_buttons().live_btn.clone();
+	live_btn.set_focus_on_click(false);
+	live_btn.connect_toggled(|_| {
+		dirty_expression();
+	});
+	grid.attach(&live_btn, 0, 4, 1, 1);
+	
 	// Setup the SHIFT + CTRL + STORE buttons.
 	let shift_btn = ::get_check_buttons().shift_btn.clone();
 	let ctrl_btn  = ::get_check_buttons().ctrl_btn .clone();
@@ -271,38 +892,17 @@ fn get_button_grid() -> Grid {
 	store_btn.set_focus_on_click(false);
 	
 	shift_btn.connect_button_press_event(move |_, _| {
-		// If the other is on, turn it off
-		if get_gui_state() == GuiState::Shift {
-			set_gui_state(GuiState::Normal);
-		} else {
-			set_gui_state(GuiState::Shift);
-		}
-		dirty_gui();
-		
+		toggle_gui_state(GuiState::Shift);
 		Inhibit(true)
 	});
 	
 	ctrl_btn.connect_button_press_event(move |_, _| {
-		// If the other is on, turn it off
-		if get_gui_state() == GuiState::Ctrl {
-			set_gui_state(GuiState::Normal);
-		} else {
-			set_gui_state(GuiState::Ctrl);
-		}
-		dirty_gui();
-		
+		toggle_gui_state(GuiState::Ctrl);
 		Inhibit(true)
 	});
 	
 	store_btn.connect_button_press_event(move |_, _| {
-		// If the other is on, turn it off
-		if get_gui_state() == GuiState::Store {
-			set_gui_state(GuiState::Normal);
-		} else {
-			set_gui_state(GuiState::Store);
-		}
-		dirty_gui();
-		
+		toggle_gui_state(GuiState::Store);
 		Inhibit(true)
 	});
 	grid.attach(&shift_btn, 1, 0, 1, 1);
@@ -321,10 +921,28 @@ fn get_button_grid() -> Grid {
 	make_and_attach_button(("tanh", "artanh", "z"), (ButtonID::Tanh, ButtonID::Artanh, ButtonID::Var('z')), &grid, 5, 1);
 	
 	make_and_attach_button(("π"  , "φ" , "e"), (ButtonID::Const('π'), ButtonID::Const('φ'), ButtonID::Const('e')), &grid, 2, 2);
-	make_and_attach_button(("x/y", ""  , "" ), (ButtonID::Frac, ButtonID::Null, ButtonID::Null), &grid, 3, 2);
+	make_and_attach_button(("x/y", "⌊x⌋", "⌈x⌉"), (ButtonID::Frac, ButtonID::Func(FuncType::Floor), ButtonID::Func(FuncType::Ceil)), &grid, 3, 2);
 	make_and_attach_button(("eˣ" , "ln", "" ), (ButtonID::E   , ButtonID::Ln  , ButtonID::Null), &grid, 4, 2);
 	make_and_attach_button(("|x|", "x!", "" ), (ButtonID::Abs , ButtonID::Fact, ButtonID::Null), &grid, 5, 2);
 	
+	make_and_attach_button(("{x}"   , ","     , ""), (ButtonID::List, ButtonID::Comma, ButtonID::Null), &grid, 2, 3);
+	make_and_attach_button(("sum"   , "mean"  , ""), (ButtonID::Func(FuncType::Sum)     , ButtonID::Func(FuncType::Mean)          , ButtonID::Null), &grid, 3, 3);
+	make_and_attach_button(("median", "mode"  , ""), (ButtonID::Func(FuncType::Median)  , ButtonID::Func(FuncType::Mode)          , ButtonID::Null), &grid, 4, 3);
+	make_and_attach_button(("min"   , "max"   , ""), (ButtonID::Func(FuncType::Min)     , ButtonID::Func(FuncType::Max)           , ButtonID::Null), &grid, 5, 3);
+	make_and_attach_button(("σ²"    , "s²"    , ""), (ButtonID::Func(FuncType::Variance), ButtonID::Func(FuncType::SampleVariance), ButtonID::Null), &grid, 2, 4);
+	make_and_attach_button(("σ"     , "s"     , ""), (ButtonID::Func(FuncType::StdDev)  , ButtonID::Func(FuncType::SampleStdDev)  , ButtonID::Null), &grid, 3, 4);
+	make_and_attach_button(("quart" , ""      , ""), (ButtonID::Func(FuncType::Quartiles), ButtonID::Null                         , ButtonID::Null), &grid, 4, 4);
+	make_and_attach_button(("linreg", ""      , ""), (ButtonID::Func(FuncType::LinReg)  , ButtonID::Null                          , ButtonID::Null), &grid, 5, 4);
+	
+	make_and_attach_button(("normpdf" , "normcdf" , "invnorm" ), (ButtonID::Func(FuncType::NormPdf) , ButtonID::Func(FuncType::NormCdf) , ButtonID::Func(FuncType::InvNorm) ), &grid, 2, 5);
+	make_and_attach_button(("binompdf", "binomcdf", "invbinom"), (ButtonID::Func(FuncType::BinomPdf), ButtonID::Func(FuncType::BinomCdf), ButtonID::Func(FuncType::InvBinom)), &grid, 3, 5);
+	make_and_attach_button(("poisspdf", "poisscdf", "invpoiss"), (ButtonID::Func(FuncType::PoissPdf), ButtonID::Func(FuncType::PoissCdf), ButtonID::Func(FuncType::InvPoiss)), &grid, 4, 5);
+	make_and_attach_button(("tpdf"    , "tcdf"    , "invt"    ), (ButtonID::Func(FuncType::TPdf)    , ButtonID::Func(FuncType::TCdf)    , ButtonID::Func(FuncType::InvT)    ), &grid, 5, 5);
+	make_and_attach_button(("chi2pdf" , "chi2cdf" , "invchi2" ), (ButtonID::Func(FuncType::Chi2Pdf) , ButtonID::Func(FuncType::Chi2Cdf) , ButtonID::Func(FuncType::InvChi2) ), &grid, 2, 6);
+	make_and_attach_button(("erf"     , "erfc"    , ""        ), (ButtonID::Func(FuncType::Erf)     , ButtonID::Func(FuncType::Erfc)    , ButtonID::Null                    ), &grid, 3, 6);
+	make_and_attach_button(("Ans"     , "#"       , ""        ), (ButtonID::Ans                     , ButtonID::Hist                    , ButtonID::Null                    ), &grid, 4, 6);
+	make_and_attach_button(("±"       , ""        , ""        ), (ButtonID::PlusMinus               , ButtonID::Null                    , ButtonID::Null                    ), &grid, 5, 6);
+	
 	grid // Return
 }
 
@@ -337,9 +955,9 @@ fn make_and_attach_button(labels: (&'static str, &'static str, &'static str), id
 	let ids_clone = ids.clone();
 	but.connect_clicked(move |_| {
 		match get_gui_state() {
-			GuiState::Normal => ::get_editor().handle_button_click(ids_clone.0.clone()),
-			GuiState::Shift  => ::get_editor().handle_button_click(ids_clone.1.clone()),
-			GuiState::Ctrl | GuiState::Store => ::get_editor().handle_button_click(ids_clone.2.clone()),
+			GuiState::Normal => ::get_editor().handle_button_click(::get_context(), ids_clone.0.clone()),
+			GuiState::Shift  => ::get_editor().handle_button_click(::get_context(), ids_clone.1.clone()),
+			GuiState::Ctrl | GuiState::Store => ::get_editor().handle_button_click(::get_context(), ids_clone.2.clone()),
 		};
 	});update_button_attrib(&but, labels.2, &ids.2);
 	
@@ -403,6 +1021,17 @@ pub enum GuiState {
 }
 static mut gui_state: GuiState = GuiState::Normal;
 
+/// Switches to `state`, or back to normal if already in it.
+fn toggle_gui_state(state: GuiState) {
+	// If the other is on, turn it off
+	if get_gui_state() == state {
+		set_gui_state(GuiState::Normal);
+	} else {
+		set_gui_state(state);
+	}
+	dirty_gui();
+}
+
 pub fn get_gui_state() -> GuiState {
 	unsafe { gui_state.clone() }
 }
@@ -422,20 +1051,7 @@ pub fn set_gui_state(state: GuiState) {
 	}
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum TrigMode {
-	Radians,
-	Degrees,
-	Gradians
-}
-static mut trig_mode: TrigMode = TrigMode::Radians;
-
-pub fn get_trig_mode() -> TrigMode {
-	unsafe { trig_mode }
-}
 fn set_trig_mode(new_mode: TrigMode) {
-	unsafe {
-		trig_mode = new_mode;
-		dirty_expression();
-	}
+	::get_vm().set_trig_mode(new_mode);
+	dirty_expression();
 }
@@ -0,0 +1,586 @@
+/// Interval arithmetic, used when evaluating in interval mode.
+///
+/// Every operation rounds outwards, so the result always contains the exact result of the operation applied to any
+/// numbers within the operands.
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Add, Sub, Mul, Div, Neg};
+
+use decimal::{self, Status};
+
+use num::*;
+use consts::*;
+use stat;
+
+/// Performs `f`, returning the result and whether it had to be rounded
+fn rounded<F>(f: F) -> (d128, bool) where F: FnOnce() -> d128 {
+	d128::set_status(Status::empty());
+	let r = f();
+	(r, d128::get_status().contains(decimal::INEXACT))
+}
+
+/// Performs `f`, rounding the result towards -∞
+fn down<F>(f: F) -> d128 where F: FnOnce() -> d128 {
+	match rounded(f) {
+		(r, true) => r.previous(),
+		(r, false) => r,
+	}
+}
+
+/// Performs `f`, rounding the result towards +∞
+fn up<F>(f: F) -> d128 where F: FnOnce() -> d128 {
+	match rounded(f) {
+		(r, true) => r.next(),
+		(r, false) => r,
+	}
+}
+
+/// Bound on the relative error of the functions in `num`. Unlike the arithmetic operations these aren't correctly
+/// rounded, as most of them are calculated using series.
+fn func_error() -> d128 {
+	d128!(1e-28)
+}
+
+/// Widens `x` by `err` in each direction
+fn widen(x: d128, err: d128) -> Interval {
+	if x.is_nan() {
+		Interval::nan()
+	} else if x.is_infinite() {
+		// An overflow could have come from any number bigger than the largest finite one
+		if x.is_negative() {
+			Interval::new(x, x.next())
+		} else {
+			Interval::new(x.previous(), x)
+		}
+	} else {
+		Interval::new(down(|| x - err), up(|| x + err))
+	}
+}
+
+/// An interval containing the true value of a function that was calculated as `x` by one of the functions in `num`
+fn loose(x: d128) -> Interval {
+	widen(x, up(|| x.abs() * func_error()))
+}
+
+/// Returns true if `x` may contain any point `offset + k·period` for an integer k.
+///
+/// Points just outside of `x` are counted as well, as the offset and period are only approximations.
+fn contains_periodic(x: Interval, offset: d128, period: d128) -> bool {
+	let k = floor((x.lo - offset) / period);
+	for i in 0..3 {
+		let p = offset + (k + d128::from(i)) * period;
+		let tol = (p.abs() + *D1) * func_error();
+		if p >= x.lo - tol && p <= x.hi + tol {
+			return true;
+		}
+	}
+	false
+}
+
+/// A closed interval [lo, hi] of the extended real line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+	pub lo: d128,
+	pub hi: d128,
+}
+impl Display for Interval {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "[{}, {}]", self.lo, self.hi)
+	}
+}
+impl Interval {
+	pub fn new(lo: d128, hi: d128) -> Interval {
+		Interval{ lo: lo, hi: hi }
+	}
+
+	/// The interval containing only `x`
+	pub fn point(x: d128) -> Interval {
+		Interval::new(x, x)
+	}
+
+	/// The smallest interval that is guaranteed to contain a number that has been rounded to `x`
+	pub fn around(x: d128) -> Interval {
+		Interval::new(x.previous(), x.next())
+	}
+
+	/// The interval for a literal typed by the user. If it has as many digits as a `d128` can hold, it may have been
+	/// rounded when it was parsed.
+	pub fn from_literal(x: d128) -> Interval {
+		if x.digits() >= 34 {
+			Interval::around(x)
+		} else {
+			Interval::point(x)
+		}
+	}
+
+	/// The interval for the constant that the variable `id` holds by default, if it is one
+	pub fn constant(id: char) -> Option<Interval> {
+		match id {
+			'π' => Some(Interval::around(*DPI)),
+			'e' => Some(Interval::around(*DE)),
+			'φ' => Some(Interval::around(*DGOLDEN_RATIO)),
+			_ => None,
+		}
+	}
+
+	/// The whole real line
+	pub fn entire() -> Interval {
+		Interval::new(*DNEG_INFINITY, *DINFINITY)
+	}
+
+	/// The result of an operation that is undefined for every number in its operands
+	pub fn nan() -> Interval {
+		Interval::point(d128!(NaN))
+	}
+
+	pub fn is_nan(&self) -> bool {
+		self.lo.is_nan() || self.hi.is_nan()
+	}
+
+	pub fn is_point(&self) -> bool {
+		self.lo == self.hi
+	}
+
+	pub fn contains(&self, x: d128) -> bool {
+		self.lo <= x && x <= self.hi
+	}
+
+	/// The smallest interval containing both intervals
+	pub fn hull(&self, other: Interval) -> Interval {
+		Interval::new(self.lo.min(other.lo), self.hi.max(other.hi))
+	}
+
+	/// The part of the interval that lies within [lo, hi], or `None` if there is no such part
+	pub fn clip(&self, lo: d128, hi: d128) -> Option<Interval> {
+		let ret = Interval::new(self.lo.max(lo), self.hi.min(hi));
+		if self.is_nan() || ret.lo > ret.hi {
+			None
+		} else {
+			Some(ret)
+		}
+	}
+
+	/// Rounds the endpoints outwards to `dp` decimal places
+	pub fn round_out(&self, dp: i32) -> Interval {
+		let step = D1.scaleb(d128::from(-dp));
+		let mut lo = round_dp(self.lo, dp);
+		if lo > self.lo {
+			lo = lo - step;
+		}
+		let mut hi = round_dp(self.hi, dp);
+		if hi < self.hi {
+			hi = hi + step;
+		}
+		Interval::new(lo, hi)
+	}
+
+	/// Applies the function `f` from `num`, which must be non-decreasing over the interval
+	pub fn increasing<F>(&self, f: F) -> Interval where F: Fn(d128) -> d128 {
+		if self.is_nan() {
+			return Interval::nan();
+		}
+		Interval::new(loose(f(self.lo)).lo, loose(f(self.hi)).hi)
+	}
+
+	/// Applies the function `f` from `num`, which must be non-increasing over the interval
+	pub fn decreasing<F>(&self, f: F) -> Interval where F: Fn(d128) -> d128 {
+		if self.is_nan() {
+			return Interval::nan();
+		}
+		Interval::new(loose(f(self.hi)).lo, loose(f(self.lo)).hi)
+	}
+
+	/// Applies `f` restricted to its domain [lo, hi]. The result is NaN if the interval lies completely outside of it.
+	fn increasing_on<F>(&self, lo: d128, hi: d128, f: F) -> Interval where F: Fn(d128) -> d128 {
+		match self.clip(lo, hi) {
+			Some(x) => x.increasing(f),
+			None => Interval::nan(),
+		}
+	}
+
+	pub fn abs(&self) -> Interval {
+		if self.lo >= *D0 {
+			*self
+		} else if self.hi <= *D0 {
+			-*self
+		} else {
+			Interval::new(*D0, self.hi.max(-self.lo))
+		}
+	}
+
+	pub fn pow(&self, b: Interval) -> Interval {
+		if self.is_nan() || b.is_nan() {
+			return Interval::nan();
+		}
+		if b.is_point() && b.lo.is_integer() {
+			return self.powi(b.lo);
+		}
+		// Non-integer powers are only defined for non-negative bases. Over those, x^y is monotonic in both x and y, so
+		// the bounds are at the corners.
+		let a = match self.clip(*D0, *DINFINITY) {
+			Some(a) => a,
+			None => return Interval::nan(),
+		};
+		let corners = [(a.lo, b.lo), (a.lo, b.hi), (a.hi, b.lo), (a.hi, b.hi)];
+		let lo = corners.iter().map(|&(x, y)| down(|| x.pow(y))).fold(*DINFINITY, |acc, v| acc.min(v));
+		let hi = corners.iter().map(|&(x, y)| up(|| x.pow(y))).fold(*DNEG_INFINITY, |acc, v| acc.max(v));
+		Interval::new(lo, hi)
+	}
+
+	/// Raises the interval to the integer power `n`
+	fn powi(&self, n: d128) -> Interval {
+		if n.is_zero() {
+			Interval::point(*D1)
+		} else if n.is_negative() {
+			Interval::point(*D1) / self.powi(-n)
+		} else if (n % *D2).is_zero() {
+			let a = self.abs();
+			Interval::new(down(|| a.lo.pow(n)), up(|| a.hi.pow(n)))
+		} else {
+			Interval::new(down(|| self.lo.pow(n)), up(|| self.hi.pow(n)))
+		}
+	}
+
+	pub fn sqrt(&self) -> Interval {
+		self.increasing_on(*D0, *DINFINITY, sqrt)
+	}
+
+	pub fn ln(&self) -> Interval {
+		self.increasing_on(*D0, *DINFINITY, ln)
+	}
+
+	pub fn sin(&self) -> Interval {
+		if self.is_nan() {
+			return Interval::nan();
+		}
+		let tau = *DPI * *D2;
+		if !self.lo.is_finite() || !self.hi.is_finite() || self.hi - self.lo >= tau {
+			return Interval::new(-*D1, *D1);
+		}
+		// The error of sin grows with its argument, as the argument is reduced using an approximation of π.
+		let sin_err = |x: d128| widen(sin(x), up(|| (x.abs() + *D1) * func_error()));
+		let mut ret = sin_err(self.lo).hull(sin_err(self.hi));
+		if contains_periodic(*self, *DPI2, tau) {
+			ret.hi = *D1;
+		}
+		if contains_periodic(*self, -*DPI2, tau) {
+			ret.lo = -*D1;
+		}
+		Interval::new(ret.lo.max(-*D1), ret.hi.min(*D1))
+	}
+
+	pub fn cos(&self) -> Interval {
+		(*self + Interval::around(*DPI2)).sin()
+	}
+
+	pub fn tan(&self) -> Interval {
+		if self.is_nan() {
+			return Interval::nan();
+		}
+		if !self.lo.is_finite() || !self.hi.is_finite() || contains_periodic(*self, *DPI2, *DPI) {
+			return Interval::entire();
+		}
+		// tan is increasing between its poles
+		let tan_point = |x: d128| {
+			let x = Interval::point(x);
+			x.sin() / x.cos()
+		};
+		Interval::new(tan_point(self.lo).lo, tan_point(self.hi).hi)
+	}
+
+	pub fn asin(&self) -> Interval {
+		self.increasing_on(-*D1, *D1, asin)
+	}
+
+	pub fn acos(&self) -> Interval {
+		match self.clip(-*D1, *D1) {
+			Some(x) => x.decreasing(acos),
+			None => Interval::nan(),
+		}
+	}
+
+	pub fn atan(&self) -> Interval {
+		self.increasing(atan)
+	}
+
+	pub fn sinh(&self) -> Interval {
+		self.increasing(sinh)
+	}
+
+	pub fn cosh(&self) -> Interval {
+		self.abs().increasing(cosh)
+	}
+
+	pub fn tanh(&self) -> Interval {
+		self.increasing(tanh)
+	}
+
+	pub fn asinh(&self) -> Interval {
+		self.increasing(asinh)
+	}
+
+	pub fn acosh(&self) -> Interval {
+		self.increasing_on(*D1, *DINFINITY, acosh)
+	}
+
+	pub fn atanh(&self) -> Interval {
+		self.increasing_on(-*D1, *D1, atanh)
+	}
+
+	pub fn erf(&self) -> Interval {
+		self.increasing(erf)
+	}
+
+	pub fn erfc(&self) -> Interval {
+		self.decreasing(erfc)
+	}
+
+	/// x!, or Γ(x + 1).
+	///
+	/// Γ is only monotonic on either side of its minimum for positive arguments, so unless the interval is a single
+	/// point, any part of it at or below -1 gives the whole real line.
+	pub fn fact(&self) -> Interval {
+		// The position of the minimum of Γ(x + 1)
+		let xmin = d128!(0.4616321449683623412626595423257213284681962040064463512959884085987864);
+		if self.is_nan() {
+			Interval::nan()
+		} else if self.is_point() {
+			loose(factorial(self.lo))
+		} else if self.lo <= -*D1 {
+			Interval::entire()
+		} else if self.hi <= xmin {
+			self.decreasing(factorial)
+		} else if self.lo >= xmin {
+			self.increasing(factorial)
+		} else {
+			let lo = loose(factorial(xmin)).lo;
+			let hi = loose(factorial(self.lo)).hi.max(loose(factorial(self.hi)).hi);
+			Interval::new(lo, hi)
+		}
+	}
+}
+
+/// Evaluates `f` over the box of arguments given, where `f` is monotonic in each argument. `increasing[i]` is true if
+/// `f` is non-decreasing in argument `i`, and false if it is non-increasing.
+///
+/// Returns `None` if `f` returns `None` at either of the corners it is evaluated at, i.e. if it isn't defined there.
+pub fn monotonic<F>(args: &[Interval], increasing: &[bool], f: F) -> Option<Interval> where F: Fn(&[d128]) -> Option<d128> {
+	let lo_args: Vec<d128> = args.iter().zip(increasing.iter()).map(|(x, &inc)| if inc { x.lo } else { x.hi }).collect();
+	let hi_args: Vec<d128> = args.iter().zip(increasing.iter()).map(|(x, &inc)| if inc { x.hi } else { x.lo }).collect();
+	match (f(&lo_args), f(&hi_args)) {
+		(Some(lo), Some(hi)) => Some(Interval::new(loose(lo).lo, loose(hi).hi)),
+		_ => None,
+	}
+}
+
+impl Add for Interval {
+	type Output = Interval;
+	fn add(self, b: Interval) -> Interval {
+		Interval::new(down(|| self.lo + b.lo), up(|| self.hi + b.hi))
+	}
+}
+impl Sub for Interval {
+	type Output = Interval;
+	fn sub(self, b: Interval) -> Interval {
+		Interval::new(down(|| self.lo - b.hi), up(|| self.hi - b.lo))
+	}
+}
+impl Neg for Interval {
+	type Output = Interval;
+	fn neg(self) -> Interval {
+		Interval::new(-self.hi, -self.lo)
+	}
+}
+impl Mul for Interval {
+	type Output = Interval;
+	fn mul(self, b: Interval) -> Interval {
+		if self.is_nan() || b.is_nan() {
+			return Interval::nan();
+		}
+		// 0 × ∞ is taken to be 0, as the infinite endpoint only stands for arbitrarily large numbers.
+		let zero_nan = |x: d128| if x.is_nan() { *D0 } else { x };
+		let corners = [(self.lo, b.lo), (self.lo, b.hi), (self.hi, b.lo), (self.hi, b.hi)];
+		let lo = corners.iter().map(|&(x, y)| zero_nan(down(|| x * y))).fold(*DINFINITY, |acc, v| acc.min(v));
+		let hi = corners.iter().map(|&(x, y)| zero_nan(up(|| x * y))).fold(*DNEG_INFINITY, |acc, v| acc.max(v));
+		Interval::new(lo, hi)
+	}
+}
+impl Div for Interval {
+	type Output = Interval;
+	fn div(self, b: Interval) -> Interval {
+		if self.is_nan() || b.is_nan() || (b.lo.is_zero() && b.hi.is_zero()) {
+			return Interval::nan();
+		} else if b.contains(*D0) {
+			return Interval::entire();
+		}
+		let corners = [(self.lo, b.lo), (self.lo, b.hi), (self.hi, b.lo), (self.hi, b.hi)];
+		let lo = corners.iter().map(|&(x, y)| down(|| x / y)).fold(*DINFINITY, |acc, v| acc.min(v));
+		let hi = corners.iter().map(|&(x, y)| up(|| x / y)).fold(*DNEG_INFINITY, |acc, v| acc.max(v));
+		Interval::new(lo, hi)
+	}
+}
+
+// Statistics over lists of intervals, mirroring `stat`.
+//
+// Order statistics are non-decreasing in every element, so they are calculated separately on the lower and upper
+// bounds. The others are calculated with interval arithmetic directly.
+
+fn sorted(xs: &[d128]) -> Vec<d128> {
+	let mut v = xs.to_vec();
+	v.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+	v
+}
+
+fn median_sorted(xs: &[d128]) -> Option<Interval> {
+	let n = xs.len();
+	if n == 0 {
+		None
+	} else if n % 2 == 1 {
+		Some(Interval::point(xs[n / 2]))
+	} else {
+		Some((Interval::point(xs[n / 2 - 1]) + Interval::point(xs[n / 2])) / Interval::point(*D2))
+	}
+}
+
+fn quartiles_sorted(s: &[d128]) -> Option<(Interval, Interval, Interval)> {
+	let n = s.len();
+	if n == 0 {
+		return None;
+	} else if n == 1 {
+		let p = Interval::point(s[0]);
+		return Some((p, p, p));
+	}
+	match (median_sorted(&s[..n / 2]), median_sorted(s), median_sorted(&s[(n + 1) / 2..])) {
+		(Some(q1), Some(q2), Some(q3)) => Some((q1, q2, q3)),
+		_ => None,
+	}
+}
+
+fn lows(xs: &[Interval]) -> Vec<d128> {
+	sorted(&xs.iter().map(|x| x.lo).collect::<Vec<_>>())
+}
+
+fn highs(xs: &[Interval]) -> Vec<d128> {
+	sorted(&xs.iter().map(|x| x.hi).collect::<Vec<_>>())
+}
+
+pub fn sum(xs: &[Interval]) -> Option<Interval> {
+	Some(xs.iter().fold(Interval::point(*D0), |acc, x| acc + *x))
+}
+
+pub fn mean(xs: &[Interval]) -> Option<Interval> {
+	if xs.len() == 0 {
+		return None;
+	}
+	sum(xs).map(|s| s / Interval::point(d128::from(xs.len() as u64)))
+}
+
+pub fn median(xs: &[Interval]) -> Option<Interval> {
+	match (median_sorted(&lows(xs)), median_sorted(&highs(xs))) {
+		(Some(lo), Some(hi)) => Some(Interval::new(lo.lo, hi.hi)),
+		_ => None,
+	}
+}
+
+/// The mode is always one of the elements, but which one can change within the intervals, so unless they are all
+/// points this is the hull of all of them.
+pub fn mode(xs: &[Interval]) -> Option<Interval> {
+	if xs.iter().all(|x| x.is_point()) {
+		return stat::mode(&xs.iter().map(|x| x.lo).collect::<Vec<_>>()).map(Interval::point);
+	}
+	xs.iter().fold(None, |acc, x| match acc {
+		Some(m) => Some(x.hull(m)),
+		None => Some(*x),
+	})
+}
+
+pub fn variance(xs: &[Interval], sample: bool) -> Option<Interval> {
+	let n = xs.len();
+	if n == 0 || (sample && n < 2) {
+		return None;
+	}
+	let m = match mean(xs) { Some(m) => m, None => return None };
+	let ss = xs.iter().fold(Interval::point(*D0), |acc, x| acc + (*x - m).powi(*D2));
+	let div = if sample { d128::from((n - 1) as u64) } else { d128::from(n as u64) };
+	Some(ss / Interval::point(div))
+}
+
+pub fn std_dev(xs: &[Interval], sample: bool) -> Option<Interval> {
+	variance(xs, sample).map(|v| v.sqrt())
+}
+
+pub fn quartiles(xs: &[Interval]) -> Option<(Interval, Interval, Interval)> {
+	match (quartiles_sorted(&lows(xs)), quartiles_sorted(&highs(xs))) {
+		(Some((l1, l2, l3)), Some((h1, h2, h3))) => Some((
+			Interval::new(l1.lo, h1.hi),
+			Interval::new(l2.lo, h2.hi),
+			Interval::new(l3.lo, h3.hi),
+		)),
+		_ => None,
+	}
+}
+
+pub fn min(xs: &[Interval]) -> Option<Interval> {
+	xs.iter().fold(None, |acc, x| match acc {
+		Some(m) => Some(Interval::new(x.lo.min(m.lo), x.hi.min(m.hi))),
+		None => Some(*x),
+	})
+}
+
+pub fn max(xs: &[Interval]) -> Option<Interval> {
+	xs.iter().fold(None, |acc, x| match acc {
+		Some(m) => Some(Interval::new(x.lo.max(m.lo), x.hi.max(m.hi))),
+		None => Some(*x),
+	})
+}
+
+/// Returns (a, b, r) for y = ax + b, as in `stat::lin_reg`.
+pub fn lin_reg(xs: &[Interval], ys: &[Interval]) -> Option<(Interval, Interval, Interval)> {
+	if xs.len() != ys.len() || xs.len() < 2 {
+		return None;
+	}
+	let (mx, my) = match (mean(xs), mean(ys)) {
+		(Some(mx), Some(my)) => (mx, my),
+		_ => return None,
+	};
+	let zero = Interval::point(*D0);
+	let mut sxx = zero;
+	let mut syy = zero;
+	let mut sxy = zero;
+	for (x, y) in xs.iter().zip(ys.iter()) {
+		sxx = sxx + (*x - mx).powi(*D2);
+		syy = syy + (*y - my).powi(*D2);
+		sxy = sxy + (*x - mx) * (*y - my);
+	}
+	if sxx == zero {
+		return None;
+	}
+	let a = sxy / sxx;
+	let b = my - a * mx;
+	let r = if syy == zero { zero } else { sxy / (sxx * syy).sqrt() };
+	Some((a, b, r))
+}
+
+#[test]
+fn rounding_test() {
+	// Exact results stay points
+	assert_eq!(Interval::point(d128!(0.1)) + Interval::point(d128!(0.2)), Interval::point(d128!(0.3)));
+	assert_eq!(Interval::point(d128!(6)) / Interval::point(d128!(3)), Interval::point(d128!(2)));
+
+	// Inexact results are widened by one ulp either side of the rounded result, as the direction it was rounded in
+	// isn't known
+	let third = Interval::point(*D1) / Interval::point(d128!(3));
+	assert_eq!(third.lo.next().next(), third.hi);
+	assert!((third * Interval::point(d128!(3))).contains(*D1));
+	let x = Interval::point(*D1) + Interval::point(d128!(1e-40));
+	assert_eq!(x, Interval::new(D1.previous(), D1.next()));
+	let x = Interval::point(*D1) - Interval::point(d128!(1e-40));
+	assert_eq!(x, Interval::new(D1.previous(), D1.next()));
+	let x = Interval::point(-third.hi) * Interval::point(d128!(3));
+	assert!(x.lo < -*D1 && x.hi > -*D1);
+
+	// The functions in `num` contain the true value
+	let x = Interval::point(*D2).sqrt();
+	assert!((x * x).contains(*D2));
+	assert!(Interval::point(*D0).sin().contains(*D0));
+}
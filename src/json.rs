@@ -0,0 +1,469 @@
+/// A minimal JSON document model, with a pretty printer and a parser that reports where in the text errors are.
+use std::fmt::{self, Display, Formatter, Write};
+
+// The largest integer up to which every integer can be held exactly by an f64, 2^53
+const MAX_EXACT_INT: f64 = 9007199254740992.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Num(f64),
+	Str(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>), // Kept in order, so that files are written the same way every time
+}
+
+impl Json {
+	/// Gets the member `key` of an object.
+	pub fn get(&self, key: &str) -> Option<&Json> {
+		match self {
+			&Json::Object(ref members) => members.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| v),
+			_ => None,
+		}
+	}
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			&Json::Str(ref s) => Some(s),
+			_ => None,
+		}
+	}
+	pub fn as_array(&self) -> Option<&[Json]> {
+		match self {
+			&Json::Array(ref vs) => Some(vs),
+			_ => None,
+		}
+	}
+	/// Returns the number held if it is a non-negative integer. Numbers above 2^53 aren't, as they may have been rounded
+	/// when they were parsed, and nor are those too large for a `usize`.
+	pub fn as_usize(&self) -> Option<usize> {
+		match self {
+			&Json::Num(n) if n >= 0.0 && n.fract() == 0.0 && n <= MAX_EXACT_INT && n <= usize::MAX as f64 => Some(n as usize),
+			_ => None,
+		}
+	}
+	pub fn as_bool(&self) -> Option<bool> {
+		match self {
+			&Json::Bool(b) => Some(b),
+			_ => None,
+		}
+	}
+	/// A description of the kind of value, for errors.
+	pub fn kind(&self) -> &'static str {
+		match self {
+			&Json::Null => "null",
+			&Json::Bool(_) => "a boolean",
+			&Json::Num(_) => "a number",
+			&Json::Str(_) => "a string",
+			&Json::Array(_) => "an array",
+			&Json::Object(_) => "an object",
+		}
+	}
+
+	/// Writes the value on a single line without any spaces, e.g. for protocols that send a document per line.
+	pub fn to_compact_string(&self) -> String {
+		let mut s = String::new();
+		let _ = self.write_compact(&mut s);
+		s
+	}
+
+	fn write_compact<W: Write>(&self, f: &mut W) -> fmt::Result {
+		match self {
+			&Json::Array(ref vs) => {
+				try!(write!(f, "["));
+				for (i, v) in vs.iter().enumerate() {
+					if i != 0 {
+						try!(write!(f, ","));
+					}
+					try!(v.write_compact(f));
+				}
+				write!(f, "]")
+			},
+			&Json::Object(ref members) => {
+				try!(write!(f, "{{"));
+				for (i, &(ref k, ref v)) in members.iter().enumerate() {
+					if i != 0 {
+						try!(write!(f, ","));
+					}
+					try!(write_str(f, k));
+					try!(write!(f, ":"));
+					try!(v.write_compact(f));
+				}
+				write!(f, "}}")
+			},
+			_ => self.write_indented(f, 0),
+		}
+	}
+
+	fn is_scalar(&self) -> bool {
+		match self {
+			&Json::Array(_) | &Json::Object(_) => false,
+			_ => true,
+		}
+	}
+
+	fn write_indented<W: Write>(&self, f: &mut W, indent: usize) -> fmt::Result {
+		match self {
+			&Json::Null => write!(f, "null"),
+			&Json::Bool(b) => write!(f, "{}", b),
+			&Json::Num(n) => write!(f, "{}", n),
+			&Json::Str(ref s) => write_str(f, s),
+			&Json::Array(ref vs) => {
+				// Arrays of simple values are written on one line
+				if vs.iter().all(Json::is_scalar) {
+					try!(write!(f, "["));
+					for (i, v) in vs.iter().enumerate() {
+						if i != 0 {
+							try!(write!(f, ", "));
+						}
+						try!(v.write_indented(f, indent));
+					}
+					return write!(f, "]");
+				}
+				try!(write!(f, "[\n"));
+				for (i, v) in vs.iter().enumerate() {
+					try!(write_indent(f, indent + 1));
+					try!(v.write_indented(f, indent + 1));
+					try!(write!(f, "{}\n", if i + 1 == vs.len() { "" } else { "," }));
+				}
+				try!(write_indent(f, indent));
+				write!(f, "]")
+			},
+			&Json::Object(ref members) => {
+				if members.is_empty() {
+					return write!(f, "{{}}");
+				}
+				// Objects of simple values are too, such as `{"op": "+"}`
+				if members.iter().all(|&(_, ref v)| v.is_scalar()) {
+					try!(write!(f, "{{"));
+					for (i, &(ref k, ref v)) in members.iter().enumerate() {
+						if i != 0 {
+							try!(write!(f, ", "));
+						}
+						try!(write_str(f, k));
+						try!(write!(f, ": "));
+						try!(v.write_indented(f, indent));
+					}
+					return write!(f, "}}");
+				}
+				try!(write!(f, "{{\n"));
+				for (i, &(ref k, ref v)) in members.iter().enumerate() {
+					try!(write_indent(f, indent + 1));
+					try!(write_str(f, k));
+					try!(write!(f, ": "));
+					try!(v.write_indented(f, indent + 1));
+					try!(write!(f, "{}\n", if i + 1 == members.len() { "" } else { "," }));
+				}
+				try!(write_indent(f, indent));
+				write!(f, "}}")
+			},
+		}
+	}
+}
+
+impl Display for Json {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		self.write_indented(f, 0)
+	}
+}
+
+fn write_indent<W: Write>(f: &mut W, indent: usize) -> fmt::Result {
+	for _ in 0..indent {
+		try!(write!(f, "\t"));
+	}
+	Ok(())
+}
+
+fn write_str<W: Write>(f: &mut W, s: &str) -> fmt::Result {
+	try!(write!(f, "\""));
+	for c in s.chars() {
+		match c {
+			'"'  => try!(write!(f, "\\\"")),
+			'\\' => try!(write!(f, "\\\\")),
+			'\n' => try!(write!(f, "\\n")),
+			'\t' => try!(write!(f, "\\t")),
+			'\r' => try!(write!(f, "\\r")),
+			_ if (c as u32) < 0x20 => try!(write!(f, "\\u{:04x}", c as u32)),
+			_ => try!(write!(f, "{}", c)),
+		}
+	}
+	write!(f, "\"")
+}
+
+/// An error in the syntax of a JSON document. Lines and columns count from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonError {
+	pub line: usize,
+	pub col: usize,
+	pub msg: String,
+}
+impl Display for JsonError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "line {}, column {}: {}", self.line, self.col, self.msg)
+	}
+}
+
+/// How deeply arrays and objects can be nested in a document. The parser recurses into each, so without a limit a
+/// document such as `[[[[...` could overflow the stack.
+pub const MAX_DEPTH: usize = 512;
+
+/// Parses a whole JSON document.
+pub fn parse(s: &str) -> Result<Json, JsonError> {
+	let mut p = Parser{ chars: s.chars().collect(), pos: 0, depth: 0 };
+	let v = try!(p.parse_value());
+	p.skip_whitespace();
+	if p.pos < p.chars.len() {
+		return Err(p.error("expected the end of the file"));
+	}
+	Ok(v)
+}
+
+struct Parser {
+	chars: Vec<char>,
+	pos: usize,
+	depth: usize, // The number of arrays and objects the parser is in
+}
+
+impl Parser {
+	fn error(&self, msg: &str) -> JsonError {
+		let before = &self.chars[..self.pos];
+		let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+		let col = before.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+		JsonError{ line: line, col: col, msg: msg.to_string() }
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).cloned()
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.peek().map_or(false, |c| c.is_whitespace()) {
+			self.pos += 1;
+		}
+	}
+
+	fn expect(&mut self, c: char) -> Result<(), JsonError> {
+		self.skip_whitespace();
+		if self.peek() == Some(c) {
+			self.pos += 1;
+			Ok(())
+		} else {
+			Err(self.error(&format!("expected '{}'", c)))
+		}
+	}
+
+	fn parse_value(&mut self) -> Result<Json, JsonError> {
+		self.skip_whitespace();
+		match self.peek() {
+			Some(c) if c == '{' || c == '[' => {
+				if self.depth == MAX_DEPTH {
+					return Err(self.error(&format!("nested more than {} deep", MAX_DEPTH)));
+				}
+				self.depth += 1;
+				let v = if c == '{' { self.parse_object() } else { self.parse_array() };
+				self.depth -= 1;
+				v
+			},
+			Some('"') => self.parse_str().map(Json::Str),
+			Some(c) if c == '-' || c.is_digit(10) => self.parse_num(),
+			Some(c) if c.is_alphabetic() => {
+				let start = self.pos;
+				let word: String = self.chars[self.pos..].iter().take_while(|c| c.is_alphabetic()).cloned().collect();
+				self.pos += word.len();
+				match word.as_str() {
+					"null" => Ok(Json::Null),
+					"true" => Ok(Json::Bool(true)),
+					"false" => Ok(Json::Bool(false)),
+					_ => {
+						self.pos = start;
+						Err(self.error(&format!("unexpected '{}'", word)))
+					},
+				}
+			},
+			Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+			None => Err(self.error("unexpected end of file")),
+		}
+	}
+
+	fn parse_object(&mut self) -> Result<Json, JsonError> {
+		try!(self.expect('{'));
+		let mut members = Vec::new();
+		self.skip_whitespace();
+		if self.peek() == Some('}') {
+			self.pos += 1;
+			return Ok(Json::Object(members));
+		}
+		loop {
+			self.skip_whitespace();
+			if self.peek() != Some('"') {
+				return Err(self.error("expected a key in quotes"));
+			}
+			let key = try!(self.parse_str());
+			try!(self.expect(':'));
+			let v = try!(self.parse_value());
+			members.push((key, v));
+			self.skip_whitespace();
+			match self.peek() {
+				Some(',') => self.pos += 1,
+				Some('}') => { self.pos += 1; return Ok(Json::Object(members)); },
+				_ => return Err(self.error("expected ',' or '}'")),
+			}
+		}
+	}
+
+	fn parse_array(&mut self) -> Result<Json, JsonError> {
+		try!(self.expect('['));
+		let mut vs = Vec::new();
+		self.skip_whitespace();
+		if self.peek() == Some(']') {
+			self.pos += 1;
+			return Ok(Json::Array(vs));
+		}
+		loop {
+			vs.push(try!(self.parse_value()));
+			self.skip_whitespace();
+			match self.peek() {
+				Some(',') => self.pos += 1,
+				Some(']') => { self.pos += 1; return Ok(Json::Array(vs)); },
+				_ => return Err(self.error("expected ',' or ']'")),
+			}
+		}
+	}
+
+	fn parse_str(&mut self) -> Result<String, JsonError> {
+		try!(self.expect('"'));
+		let mut s = String::new();
+		loop {
+			let c = match self.peek() {
+				Some(c) => c,
+				None => return Err(self.error("unterminated string")),
+			};
+			self.pos += 1;
+			match c {
+				'"' => return Ok(s),
+				'\\' => {
+					let e = match self.peek() {
+						Some(e) => e,
+						None => return Err(self.error("unterminated string")),
+					};
+					self.pos += 1;
+					match e {
+						'"' | '\\' | '/' => s.push(e),
+						'n' => s.push('\n'),
+						't' => s.push('\t'),
+						'r' => s.push('\r'),
+						'b' => s.push('\u{8}'),
+						'f' => s.push('\u{c}'),
+						'u' => {
+							let start = self.pos - 1;
+							let c = match self.hex4() {
+								// Characters outside the Basic Multilingual Plane are escaped as a pair of UTF-16 surrogates
+								Some(hi) if hi >= 0xD800 && hi < 0xDC00 => {
+									let lo = if self.chars[self.pos..].starts_with(&['\\', 'u']) {
+										self.pos += 2;
+										self.hex4()
+									} else {
+										None
+									};
+									match lo {
+										Some(lo) if lo >= 0xDC00 && lo < 0xE000 =>
+											::std::char::from_u32(0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00)),
+										_ => None,
+									}
+								},
+								// Unpaired low surrogates aren't characters, so aren't converted
+								Some(n) => ::std::char::from_u32(n),
+								None => None,
+							};
+							match c {
+								Some(c) => s.push(c),
+								None => {
+									self.pos = start;
+									return Err(self.error("invalid unicode escape"));
+								},
+							}
+						},
+						_ => {
+							self.pos -= 1;
+							return Err(self.error(&format!("invalid escape '\\{}'", e)));
+						},
+					}
+				},
+				_ => s.push(c),
+			}
+		}
+	}
+
+	/// Reads the four hex digits of a `\\u` escape.
+	fn hex4(&mut self) -> Option<u32> {
+		let digits = &self.chars[self.pos..];
+		if digits.len() < 4 || !digits[..4].iter().all(|c| c.is_digit(16)) {
+			return None;
+		}
+		self.pos += 4;
+		Some(digits[..4].iter().fold(0, |n, c| n * 16 + c.to_digit(16).unwrap()))
+	}
+
+	fn parse_num(&mut self) -> Result<Json, JsonError> {
+		let start = self.pos;
+		while self.peek().map_or(false, |c| c.is_digit(10) || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E') {
+			self.pos += 1;
+		}
+		let s: String = self.chars[start..self.pos].iter().cloned().collect();
+		match s.parse() {
+			Ok(n) => Ok(Json::Num(n)),
+			Err(_) => {
+				self.pos = start;
+				Err(self.error(&format!("invalid number '{}'", s)))
+			},
+		}
+	}
+}
+
+#[test]
+fn parse_test() {
+	let parsed = |s: &str| parse(s).unwrap();
+	assert_eq!(parsed(r#""a\"b\\c\/d\n\t\r\b\f""#), Json::Str("a\"b\\c/d\n\t\r\u{8}\u{c}".to_string()));
+	assert_eq!(parsed(r#""\u00e9\u00E9 \ud835\udc65""#), Json::Str("éé 𝑥".to_string()));
+	assert_eq!(parsed(" [0, -12.5e3, 1E2, true, null] "),
+		Json::Array(vec![Json::Num(0.0), Json::Num(-12500.0), Json::Num(100.0), Json::Bool(true), Json::Null]));
+	assert_eq!(parsed(r#"{"a": {}, "b": []}"#),
+		Json::Object(vec![("a".to_string(), Json::Object(vec![])), ("b".to_string(), Json::Array(vec![]))]));
+
+	// Writing a document and reading it again gives the same document
+	let j = Json::Object(vec![("x\u{1}\n\"".to_string(), Json::Array(vec![Json::Str("𝑥 ± é\\".to_string()), Json::Num(0.25)]))]);
+	assert_eq!(parsed(&j.to_string()), j);
+	assert_eq!(parsed(&j.to_compact_string()), j);
+
+	// Errors are at the line and column the problem starts at
+	let error = |s: &str| parse(s).map(|j| panic!("'{}' was parsed as {:?}", s, j)).unwrap_err();
+	let errors = [
+		("{\n  \"a\": tru\n}", 2, 8, "unexpected 'tru'"),
+		("[1, 2", 1, 6, "expected ',' or ']'"),
+		("[1] x", 1, 5, "expected the end of the file"),
+		("{1: 2}", 1, 2, "expected a key in quotes"),
+		("[1.2.3]", 1, 2, "invalid number '1.2.3'"),
+		(r#""ab"#, 1, 4, "unterminated string"),
+		(r#""\x""#, 1, 3, "invalid escape '\\x'"),
+		(r#""\u+123""#, 1, 3, "invalid unicode escape"),
+		(r#""\u12""#, 1, 3, "invalid unicode escape"),
+		(r#""\ud835""#, 1, 3, "invalid unicode escape"),
+		(r#""\ud835\u0041""#, 1, 3, "invalid unicode escape"),
+		(r#""\udc65\ud835""#, 1, 3, "invalid unicode escape"),
+	];
+	for &(s, line, col, msg) in errors.iter() {
+		assert_eq!(error(s), JsonError{ line: line, col: col, msg: msg.to_string() }, "{}", s);
+	}
+}
+
+#[test]
+fn depth_test() {
+	use std::iter;
+	let nested = |n: usize| iter::repeat("[").take(n).chain(iter::repeat("]").take(n)).collect::<String>();
+	assert!(parse(&nested(MAX_DEPTH)).is_ok());
+	let e = parse(&nested(MAX_DEPTH + 1)).unwrap_err();
+	assert_eq!((e.line, e.col), (1, MAX_DEPTH + 1));
+	// Far deeper than the stack could recurse
+	assert!(parse(&nested(100000)).is_err());
+	assert!(parse(&iter::repeat(r#"{"a":"#).take(100000).collect::<String>()).is_err());
+}
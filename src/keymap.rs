@@ -0,0 +1,325 @@
+/// Key bindings, which map key chords (e.g. `Ctrl+Z`), or sequences of them (e.g. `Ctrl+K F`), to editor actions.
+///
+/// The bindings are the defaults below, changed by those in `keys.json` in Equator's data directory, e.g.
+/// `{"version": 3, "bindings": {"Ctrl+K F": "insert frac", "F1": null}}`, where `null` removes a binding.
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+use gdk::{self, EventKey};
+use gdk::enums::key;
+
+use action::Action;
+use json::Json;
+use save::{LoadError, data_dir, read_file, invalid, member};
+
+/// The bindings used when there is no `keys.json`, as (keys, action).
+const DEFAULT_BINDINGS: &'static [(&'static str, &'static str)] = &[
+	("Left", "move left"), ("Right", "move right"), ("Up", "move up"), ("Down", "move down"),
+	("Shift+Left", "select left"), ("Shift+Right", "select right"), ("Shift+Up", "select up"), ("Shift+Down", "select down"),
+	("Ctrl+Left", "move word-left"), ("Ctrl+Right", "move word-right"),
+	("Ctrl+Shift+Left", "select word-left"), ("Ctrl+Shift+Right", "select word-right"),
+	("Home", "move start"), ("End", "move end"), ("Shift+Home", "select start"), ("Shift+End", "select end"),
+	("Ctrl+Home", "move root-start"), ("Ctrl+End", "move root-end"),
+	("Ctrl+Shift+Home", "select root-start"), ("Ctrl+Shift+End", "select root-end"),
+	("Escape", "move out"),
+	("Tab", "next-slot"),
+	("Shift+Tab", "prev-slot"),
+	("Delete", "delete"),
+	("BackSpace", "backspace"),
+	("Return", "evaluate"),
+	("KP_Enter", "evaluate"),
+	("Shift+Return", "new-row"),
+	("Ctrl+Z", "undo"),
+	("Ctrl+Shift+Z", "redo"),
+	("Ctrl+Y", "redo"),
+	("Ctrl+X", "cut"),
+	("Ctrl+C", "copy"),
+	("Ctrl+Shift+C", "copy-latex"),
+	("Ctrl+V", "paste"),
+	("Ctrl+A", "insert ans"),
+	("Ctrl++", "insert plus-minus"),
+	("Ctrl+O", "open"),
+	("Ctrl+S", "save"),
+	("Ctrl+Shift+S", "save-as"),
+	("Ctrl+E", "export"),
+	("Ctrl+?", "show-keys"),
+	("Ctrl+.", "quick-fix"),
+	("F1", "toggle debug-1"),
+	("F2", "toggle debug-2"),
+	("F3", "toggle debug-3"),
+	("F4", "toggle debug-view"),
+];
+
+/// The names of keys that aren't written as the character they type.
+const KEY_NAMES: &'static [(&'static str, key::Key)] = &[
+	("Left", key::Left), ("Right", key::Right), ("Up", key::Up), ("Down", key::Down),
+	("Home", key::Home), ("End", key::End), ("Page_Up", key::Page_Up), ("Page_Down", key::Page_Down),
+	("Return", key::Return), ("KP_Enter", key::KP_Enter), ("Tab", key::Tab), ("Escape", key::Escape),
+	("BackSpace", key::BackSpace), ("Delete", key::Delete), ("Insert", key::Insert),
+	("F1", key::F1), ("F2", key::F2), ("F3", key::F3), ("F4", key::F4), ("F5", key::F5), ("F6", key::F6),
+	("F7", key::F7), ("F8", key::F8), ("F9", key::F9), ("F10", key::F10), ("F11", key::F11), ("F12", key::F12),
+];
+
+/// A key on the keyboard.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Key {
+	Char(char), // A key that types a character, which is kept in lower case
+	Named(key::Key),
+}
+
+/// A key pressed together with modifiers.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Chord {
+	pub key: Key,
+	pub ctrl: bool,
+	pub shift: bool,
+	pub alt: bool,
+}
+
+impl Chord {
+	/// Gets the chord pressed in a key event, or `None` if only a modifier was pressed.
+	pub fn from_event(e: &EventKey) -> Option<Chord> {
+		let keyval = e.get_keyval();
+		let state = e.get_state();
+		let mut shift = state.contains(gdk::SHIFT_MASK);
+		let key = match gdk::keyval_to_unicode(keyval) {
+			Some(c) if !c.is_control() => {
+				let lower = c.to_lowercase().next().unwrap_or(c);
+				// Shift is already part of characters such as `+`, which aren't letters
+				if lower == c && c.to_uppercase().next() == Some(c) {
+					shift = false;
+				}
+				Key::Char(lower)
+			},
+			_ => match keyval {
+				key::Shift_L | key::Shift_R | key::Control_L | key::Control_R | key::Alt_L | key::Alt_R
+					| key::Meta_L | key::Meta_R | key::Super_L | key::Super_R | key::Caps_Lock | key::ISO_Level3_Shift => return None,
+				// Shift+Tab
+				key::ISO_Left_Tab => Key::Named(key::Tab),
+				_ => Key::Named(keyval),
+			},
+		};
+		Some(Chord{ key: key, ctrl: state.contains(gdk::CONTROL_MASK), shift: shift, alt: state.contains(gdk::MOD1_MASK) })
+	}
+
+	/// Parses a chord such as `Ctrl+Shift+Z`, `Ctrl++` or `F1`. Modifiers and key names are not case sensitive.
+	pub fn parse(s: &str) -> Option<Chord> {
+		// The key is after the last `+`, unless the key is `+` itself
+		let last = match s.char_indices().last() {
+			Some((i, _)) => i,
+			None => return None,
+		};
+		let (mods, name) = match s[..last].rfind('+') {
+			Some(i) => (&s[..i], &s[i + 1..]),
+			None => ("", s),
+		};
+		let mut chord = Chord{ key: Key::Char(' '), ctrl: false, shift: false, alt: false };
+		for m in mods.split('+').filter(|m| !m.is_empty()) {
+			match m.to_lowercase().as_str() {
+				"ctrl" | "control" => chord.ctrl = true,
+				"shift" => chord.shift = true,
+				"alt" => chord.alt = true,
+				_ => return None,
+			}
+		}
+		chord.key = if name.chars().count() == 1 {
+			let c = name.chars().next().unwrap();
+			Key::Char(c.to_lowercase().next().unwrap_or(c))
+		} else if name.to_lowercase() == "space" {
+			Key::Char(' ')
+		} else {
+			match KEY_NAMES.iter().find(|&&(n, _)| n.to_lowercase() == name.to_lowercase()) {
+				Some(&(_, keyval)) => Key::Named(keyval),
+				None => return None,
+			}
+		};
+		Some(chord)
+	}
+}
+
+impl Display for Chord {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		if self.ctrl  { try!(write!(f, "Ctrl+")); }
+		if self.alt   { try!(write!(f, "Alt+")); }
+		if self.shift { try!(write!(f, "Shift+")); }
+		match self.key {
+			Key::Char(' ') => write!(f, "Space"),
+			Key::Char(c) => write!(f, "{}", c.to_uppercase().next().unwrap_or(c)),
+			Key::Named(keyval) => match KEY_NAMES.iter().find(|&&(_, k)| k == keyval) {
+				Some(&(name, _)) => write!(f, "{}", name),
+				None => write!(f, "{}", gdk::keyval_name(keyval).unwrap_or(format!("{:#x}", keyval))),
+			},
+		}
+	}
+}
+
+/// Parses a sequence of chords separated by spaces, e.g. `Ctrl+K F`.
+pub fn parse_keys(s: &str) -> Option<Vec<Chord>> {
+	let mut keys = Vec::new();
+	for chord in s.split_whitespace() {
+		keys.push(match Chord::parse(chord) {
+			Some(chord) => chord,
+			None => return None,
+		});
+	}
+	if keys.is_empty() { None } else { Some(keys) }
+}
+
+/// Writes a sequence of chords as `parse_keys` reads them.
+pub fn keys_to_string(keys: &[Chord]) -> String {
+	keys.iter().map(|chord| chord.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// What a key press did.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum KeyResult {
+	Action(Action),
+	Pending, // The key started, or continued, a sequence of keys
+	Unbound,
+}
+
+pub struct Keymap {
+	bindings: Vec<(Vec<Chord>, Action)>,
+	pending: Vec<Chord>, // The keys pressed so far of a sequence
+}
+
+impl Keymap {
+	/// Makes a keymap with the default bindings.
+	pub fn new() -> Keymap {
+		let mut keymap = Keymap{ bindings: Vec::new(), pending: Vec::new() };
+		for &(keys, action) in DEFAULT_BINDINGS.iter() {
+			keymap.bind(parse_keys(keys).unwrap(), Action::from_name(action));
+		}
+		keymap
+	}
+
+	/// Binds `keys` to `action`, replacing any binding it had. If `action` is `None`, the binding is removed.
+	pub fn bind(&mut self, keys: Vec<Chord>, action: Option<Action>) {
+		self.bindings.retain(|&(ref k, _)| *k != keys);
+		if let Some(action) = action {
+			self.bindings.push((keys, action));
+		}
+	}
+
+	/// The bindings, in the order they were made.
+	pub fn bindings(&self) -> &[(Vec<Chord>, Action)] {
+		&self.bindings
+	}
+
+	/// The keys pressed so far of an unfinished sequence.
+	pub fn pending(&self) -> &[Chord] {
+		&self.pending
+	}
+
+	/// Handles a chord being pressed. If it doesn't continue the sequence pressed so far, the sequence is dropped and
+	/// the chord is looked up on its own.
+	pub fn press(&mut self, chord: Chord) -> KeyResult {
+		self.pending.push(chord);
+		let (found, prefix) = {
+			let pending = &self.pending;
+			(self.bindings.iter().find(|&&(ref keys, _)| keys == pending).map(|&(_, action)| action),
+				self.bindings.iter().any(|&(ref keys, _)| keys.starts_with(pending)))
+		};
+		if let Some(action) = found {
+			self.pending.clear();
+			return KeyResult::Action(action);
+		}
+		if prefix {
+			return KeyResult::Pending;
+		}
+		let restart = self.pending.len() > 1;
+		self.pending.clear();
+		if restart { self.press(chord) } else { KeyResult::Unbound }
+	}
+}
+
+/// Changes the bindings of `keymap` to those in `j`, an object of keys and the actions they are bound to, which is at
+/// `at`. An action of `null` removes the binding.
+pub fn bindings_from_json(keymap: &mut Keymap, j: &Json, at: &str) -> Result<(), LoadError> {
+	let members = match j {
+		&Json::Object(ref members) => members,
+		_ => return invalid(at, &format!("expected an object, found {}", j.kind())),
+	};
+	for &(ref keys, ref action) in members.iter() {
+		let at = format!("{}.\"{}\"", at, keys);
+		let keys = match parse_keys(keys) {
+			Some(keys) => keys,
+			None => return invalid(&at, "unknown key"),
+		};
+		let action = match action {
+			&Json::Null => None,
+			&Json::Str(ref name) => match Action::from_name(name) {
+				Some(action) => Some(action),
+				None => return invalid(&at, &format!("unknown action '{}'", name)),
+			},
+			_ => return invalid(&at, &format!("expected an action or null, found {}", action.kind())),
+		};
+		keymap.bind(keys, action);
+	}
+	Ok(())
+}
+
+fn keymap_path() -> Option<PathBuf> {
+	data_dir().map(|d| d.join("keys.json"))
+}
+
+/// Loads the key bindings: the defaults, changed by those in `keys.json` if there is one.
+pub fn load_keymap() -> Result<Keymap, LoadError> {
+	let mut keymap = Keymap::new();
+	let path = match keymap_path() {
+		Some(ref p) if p.exists() => p.clone(),
+		_ => return Ok(keymap),
+	};
+	let j = try!(read_file(&path));
+	try!(bindings_from_json(&mut keymap, try!(member(&j, "bindings", "file")), "bindings"));
+	Ok(keymap)
+}
+
+#[test]
+fn parse_test() {
+	let chord = Chord::parse("Ctrl+Shift+Z").unwrap();
+	assert_eq!(chord, Chord{ key: Key::Char('z'), ctrl: true, shift: true, alt: false });
+	assert_eq!(Chord::parse("ctrl++"), Some(Chord{ key: Key::Char('+'), ctrl: true, shift: false, alt: false }));
+	assert_eq!(Chord::parse("f1").map(|c| c.key), Some(Key::Named(key::F1)));
+	assert_eq!(Chord::parse("Hyper+A"), None);
+	assert_eq!(Chord::parse("Ctrl+Nothing"), None);
+	assert_eq!(Chord::parse(""), None);
+	assert_eq!(parse_keys("  "), None);
+	for s in ["Ctrl+K F", "Ctrl++", "Ctrl+Alt+Shift+Space", "Shift+Page_Up"].iter() {
+		assert_eq!(keys_to_string(&parse_keys(s).unwrap()), *s);
+	}
+}
+
+#[test]
+fn press_test() {
+	use action::Motion;
+	use json;
+
+	let chord = |s| Chord::parse(s).unwrap();
+	let mut keymap = Keymap::new();
+	assert_eq!(keymap.press(chord("Left")), KeyResult::Action(Action::Move(Motion::Left)));
+	assert_eq!(keymap.press(chord("Ctrl+Q")), KeyResult::Unbound);
+
+	keymap.bind(parse_keys("Ctrl+K F").unwrap(), Action::from_name("insert frac"));
+	assert_eq!(keymap.press(chord("Ctrl+K")), KeyResult::Pending);
+	assert_eq!(keymap.pending(), &[chord("Ctrl+K")]);
+	assert_eq!(keymap.press(chord("F")), Action::from_name("insert frac").map(KeyResult::Action).unwrap());
+	assert!(keymap.pending().is_empty());
+
+	// A key that doesn't continue the sequence is looked up on its own
+	assert_eq!(keymap.press(chord("Ctrl+K")), KeyResult::Pending);
+	assert_eq!(keymap.press(chord("Right")), KeyResult::Action(Action::Move(Motion::Right)));
+	assert_eq!(keymap.press(chord("Ctrl+K")), KeyResult::Pending);
+	assert_eq!(keymap.press(chord("Ctrl+Q")), KeyResult::Unbound);
+	assert!(keymap.pending().is_empty());
+
+	// Bindings are replaced and removed
+	let n = keymap.bindings().len();
+	let j = json::parse(r#"{"Left": "move right", "F1": null}"#).unwrap();
+	bindings_from_json(&mut keymap, &j, "bindings").unwrap();
+	assert_eq!(keymap.bindings().len(), n - 1);
+	assert_eq!(keymap.press(chord("Left")), KeyResult::Action(Action::Move(Motion::Right)));
+	assert_eq!(keymap.press(chord("F1")), KeyResult::Unbound);
+	let j = json::parse(r#"{"Left": "fly"}"#).unwrap();
+	assert!(bindings_from_json(&mut keymap, &j, "bindings").is_err());
+}
@@ -0,0 +1,364 @@
+/// Layout of expressions as text, for terminals.
+///
+/// Each part of an expression becomes a block of characters, and the blocks are put together the way `render::Render`
+/// puts together the extents it draws: scripts are raised or lowered against their base, fractions are stacked over a
+/// line, and brackets and roots grow to fit what is inside them. Scripts of digits use the Unicode superscript and
+/// subscript digits instead, so that e.g. `x²` stays on one line.
+use std::cmp;
+use std::fmt::{self, Display, Formatter};
+
+use consts::*;
+use func::FuncType;
+use vis::*;
+use edit::is_token_term_left;
+
+/// A rectangle of characters. `baseline` is the row that lines up with the baselines of the blocks beside it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+	rows: Vec<Vec<char>>, // Every row is `width` characters long
+	width: usize,
+	pub baseline: usize,
+}
+
+impl Block {
+	/// An empty block, one row high.
+	pub fn new() -> Block {
+		Block{ rows: vec![Vec::new()], width: 0, baseline: 0 }
+	}
+
+	/// A block of a single row of text.
+	pub fn text(s: &str) -> Block {
+		let row: Vec<char> = s.chars().collect();
+		Block{ width: row.len(), rows: vec![row], baseline: 0 }
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.rows.len()
+	}
+
+	/// The number of rows below the baseline.
+	fn descent(&self) -> usize {
+		self.rows.len() - 1 - self.baseline
+	}
+
+	/// Draws `other` with its left edge at column `x`, and its baseline `dy` rows below the baseline of this block
+	/// (above it if `dy` is negative), growing this block to fit it. Spaces in `other` leave what is under them.
+	pub fn draw(&mut self, x: usize, dy: isize, other: &Block) {
+		let top = dy - other.baseline as isize; // Relative to the baseline of this block
+		let above = -top - self.baseline as isize;
+		for _ in 0..cmp::max(above, 0) {
+			self.rows.insert(0, vec![' '; self.width]);
+			self.baseline += 1;
+		}
+		let below = dy + other.descent() as isize - self.descent() as isize;
+		for _ in 0..cmp::max(below, 0) {
+			self.rows.push(vec![' '; self.width]);
+		}
+		if x + other.width > self.width {
+			self.width = x + other.width;
+			for row in self.rows.iter_mut() {
+				row.resize(x + other.width, ' ');
+			}
+		}
+		let y0 = (self.baseline as isize + top) as usize;
+		for (i, row) in other.rows.iter().enumerate() {
+			for (j, &c) in row.iter().enumerate() {
+				if c != ' ' {
+					self.rows[y0 + i][x + j] = c;
+				}
+			}
+		}
+	}
+
+	/// Puts `other` on the right of this block, lining up their baselines.
+	pub fn append(&mut self, other: &Block) {
+		let x = self.width;
+		self.draw(x, 0, other);
+	}
+}
+
+impl Display for Block {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		for (i, row) in self.rows.iter().enumerate() {
+			if i != 0 {
+				try!(write!(f, "\n"));
+			}
+			// Leave off the spaces at the end of the row
+			let len = row.iter().rposition(|&c| c != ' ').map_or(0, |n| n + 1);
+			let s: String = row[..len].iter().cloned().collect();
+			try!(write!(f, "{}", s));
+		}
+		Ok(())
+	}
+}
+
+/// Where a block was drawn, relative to the baseline of the block it was drawn on.
+#[derive(Copy, Clone, Debug)]
+struct Area {
+	x1: usize,
+	top: isize, // Negative, as rows above the baseline are
+	bottom: isize,
+}
+
+impl Area {
+	/// The row half way down, which scripts are raised or lowered from.
+	fn mid(&self) -> isize {
+		self.top + (self.bottom - self.top) / 2
+	}
+}
+
+/// Draws `b` on the right of `block`, returning where it went.
+fn append(block: &mut Block, b: &Block) -> Area {
+	block.append(b);
+	Area{ x1: block.width(), top: -(b.baseline as isize), bottom: b.descent() as isize }
+}
+
+/// Lays out the expression `ex`. An empty expression is an empty box, as it is in the editor.
+pub fn layout_vexpr(ex: &VExprRef) -> Block {
+	let tokens = ex.borrow().tokens.clone();
+	layout_tokens(&tokens)
+}
+
+/// Lays out a sequence of tokens.
+pub fn layout_tokens(tokens: &[VToken]) -> Block {
+	let mut block = Block::new();
+	if tokens.is_empty() {
+		block.append(&Block::text(&CHAR_BOX.to_string()));
+		return block;
+	}
+	let mut prev: Option<Area> = None; // Where the last token was drawn
+	let mut script_base: Option<(Area, usize)> = None; // The base of the last subscript, and the column it ended at
+	for (i, tok) in tokens.iter().enumerate() {
+		// Operators between two terms are spaced out, but signs, such as in `-2` or `3×-2`, aren't
+		let binary = i != 0 && match tokens[i - 1] {
+			VToken::Op(_) | VToken::Char('=') | VToken::Char(',') => false,
+			_ => true,
+		};
+		let after_sub = i != 0 && match tokens[i - 1] { VToken::Sub(_) => true, _ => false };
+		let area = match tok {
+			&VToken::Space => append(&mut block, &Block::text(&CHAR_BOX.to_string())),
+			&VToken::Char('=') => append(&mut block, &Block::text(" = ")),
+			&VToken::Char(',') => append(&mut block, &Block::text(", ")),
+			&VToken::Digit(c) | &VToken::Char(c) => append(&mut block, &Block::text(&c.to_string())),
+			&VToken::Op(op) if binary => append(&mut block, &Block::text(&format!(" {} ", op))),
+			&VToken::Op(op) => append(&mut block, &Block::text(&op.to_string())),
+			&VToken::Ans(n) => append(&mut block, &Block::text(&format!("Ans{}", subscript(n)))),
+			&VToken::Hist(0) => append(&mut block, &Block::text("#")),
+			&VToken::Hist(n) => append(&mut block, &Block::text(&format!("#{}", n))),
+			&VToken::Pow(ref inner) | &VToken::Sub(ref inner) => {
+				let raised = match tok { &VToken::Pow(_) => true, _ => false };
+				// A script with nothing before it to go on has an empty box put there, as the editor does
+				let needs_base = i == 0 || match &tokens[i - 1] {
+					&VToken::Char(_) | &VToken::Digit(_) => false,
+					prev_tok => !raised || !is_token_term_left(prev_tok),
+				};
+				let base = match prev {
+					Some(base) if !needs_base => base,
+					_ => append(&mut block, &Block::text(&CHAR_BOX.to_string())),
+				};
+				let script = layout_script(inner, raised);
+				if script.height() == 1 && script_chars(inner, raised).is_some() {
+					let area = append(&mut block, &script);
+					if !raised {
+						script_base = None;
+					}
+					Area{ x1: area.x1, top: base.top, bottom: base.bottom }
+				} else {
+					let mut dy = if raised {
+						base.mid() - 1 - script.descent() as isize
+					} else {
+						base.mid() + 1 + script.baseline as isize
+					};
+					match script_base {
+						// Stack the power above the subscript, both against the same base
+						Some((sub_base, sub_end)) if raised && after_sub => {
+							dy = sub_base.mid() - 1 - script.descent() as isize;
+							block.draw(sub_base.x1, dy, &script);
+							let end = cmp::max(sub_end, sub_base.x1 + script.width());
+							if end > block.width() {
+								let pad = end - block.width();
+								block.append(&Block::text(&" ".repeat(pad)));
+							}
+						},
+						_ => {
+							let x = block.width();
+							block.draw(x, dy, &script);
+						},
+					}
+					let area = Area{ x1: block.width(), top: cmp::min(base.top, dy - script.baseline as isize),
+						bottom: cmp::max(base.bottom, dy + script.descent() as isize) };
+					if !raised {
+						script_base = Some((base, block.width()));
+					}
+					area
+				}
+			},
+			&VToken::Frac(ref num, ref den) => append(&mut block, &layout_frac(num, den)),
+			&VToken::Func(FuncType::Sqrt, ref inner) => append(&mut block, &layout_root(inner, None)),
+			&VToken::Root(ref degree, ref inner) => append(&mut block, &layout_root(inner, Some(degree))),
+			&VToken::Func(func, ref inner) => {
+				let mut b = Block::text(&func.to_string());
+				b.append(&layout_delimited(inner, '(', ')'));
+				append(&mut block, &b)
+			},
+			&VToken::List(ref inner) => append(&mut block, &layout_delimited(inner, '{', '}')),
+			&VToken::Delim(delim, ref inner) => append(&mut block, &layout_delimited(inner, delim.open(), delim.close())),
+		};
+		prev = Some(area);
+	}
+	block
+}
+
+/// Returns the script of only digits and signs in `inner` as Unicode superscript characters if `raised` is true, or
+/// subscript characters otherwise. Returns `None` if it has anything else in it.
+fn script_chars(inner: &VExprRef, raised: bool) -> Option<String> {
+	const SUPERSCRIPTS: &'static [char] = &['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+	const SUBSCRIPTS: &'static [char] = &['₀', '₁', '₂', '₃', '₄', '₅', '₆', '₇', '₈', '₉'];
+	let tokens = &inner.borrow().tokens;
+	if tokens.is_empty() {
+		return None;
+	}
+	let mut s = String::new();
+	for tok in tokens.iter() {
+		let c = match tok {
+			&VToken::Digit(d) if d.is_digit(10) => {
+				let n = d.to_digit(10).unwrap() as usize;
+				if raised { SUPERSCRIPTS[n] } else { SUBSCRIPTS[n] }
+			},
+			&VToken::Op(OpType::Sub) => if raised { '⁻' } else { '₋' },
+			&VToken::Op(OpType::Add) => if raised { '⁺' } else { '₊' },
+			_ => return None,
+		};
+		s.push(c);
+	}
+	Some(s)
+}
+
+/// Lays out a power if `raised` is true, or a subscript otherwise, before it is put against its base.
+fn layout_script(inner: &VExprRef, raised: bool) -> Block {
+	match script_chars(inner, raised) {
+		Some(s) => Block::text(&s),
+		None => layout_vexpr(inner),
+	}
+}
+
+/// Lays out a fraction, with its line on the baseline.
+fn layout_frac(num: &VExprRef, den: &VExprRef) -> Block {
+	let (num, den) = (layout_vexpr(num), layout_vexpr(den));
+	// Leave a space either side of the line
+	let w = cmp::max(num.width(), den.width()) + 2;
+	let mut block = Block::text(&"─".repeat(w));
+	block.draw((w - num.width() + 1) / 2, -1 - num.descent() as isize, &num);
+	block.draw((w - den.width() + 1) / 2, 1 + den.baseline as isize, &den);
+	block
+}
+
+/// Lays out a root, with its degree in front of it if it isn't a square root.
+fn layout_root(inner: &VExprRef, degree: Option<&VExprRef>) -> Block {
+	let inner = layout_vexpr(inner);
+	let bottom = inner.descent() as isize;
+	let mut block = Block::new();
+	let mut x = 0;
+	if let Some(degree) = degree {
+		match script_chars(degree, true) {
+			Some(s) => block.draw(0, bottom, &Block::text(&s)),
+			None => {
+				let degree = layout_vexpr(degree);
+				block.draw(0, bottom - 1 - degree.descent() as isize, &degree);
+			},
+		}
+		x = block.width();
+	}
+	// The sign runs up the left of the inner expression, and its line along the top
+	let top = -(inner.baseline as isize) - 1;
+	for dy in top..bottom + 1 {
+		let c = if dy == bottom { '√' } else if dy == top { if inner.height() == 1 { ' ' } else { '┌' } } else { '│' };
+		block.draw(x, dy, &Block::text(&c.to_string()));
+	}
+	block.draw(x + 1, top, &Block::text(&"─".repeat(inner.width())));
+	block.draw(x + 1, 0, &inner);
+	block
+}
+
+/// Lays out `inner` between `open` and `close`, which are stretched to be as tall as it.
+fn layout_delimited(inner: &VExprRef, open: char, close: char) -> Block {
+	let inner = layout_vexpr(inner);
+	let mut block = stretched(open, &inner);
+	block.append(&inner);
+	block.append(&stretched(close, &inner));
+	block
+}
+
+/// Makes a block of the bracket `c`, as tall as `inner` and lined up with it.
+fn stretched(c: char, inner: &Block) -> Block {
+	let h = inner.height();
+	if h == 1 {
+		return Block::text(&c.to_string());
+	}
+	// The pieces of the bracket: (top, middle, bottom), then the piece at the baseline of a brace
+	let (top, mid, bot, centre) = match c {
+		'(' => ('⎛', '⎜', '⎝', '⎜'),
+		')' => ('⎞', '⎟', '⎠', '⎟'),
+		'[' => ('⎡', '⎢', '⎣', '⎢'),
+		']' => ('⎤', '⎥', '⎦', '⎥'),
+		'⌊' => ('⎢', '⎢', '⎣', '⎢'),
+		'⌋' => ('⎥', '⎥', '⎦', '⎥'),
+		'⌈' => ('⎡', '⎢', '⎢', '⎢'),
+		'⌉' => ('⎤', '⎥', '⎥', '⎥'),
+		'{' => ('⎧', '⎪', '⎩', '⎨'),
+		'}' => ('⎫', '⎪', '⎭', '⎬'),
+		_ => ('│', '│', '│', '│'),
+	};
+	let mut block = Block::new();
+	for y in 0..h {
+		let piece = if y == 0 { top } else if y + 1 == h { bot } else if y == inner.baseline { centre } else { mid };
+		block.draw(0, y as isize - inner.baseline as isize, &Block::text(&piece.to_string()));
+	}
+	block
+}
+
+
+#[test]
+fn layout_test() {
+	fn layout_text(text: &str) -> String {
+		use parse::parse_text;
+		layout_vexpr(&parse_text(text).unwrap()).to_string()
+	}
+
+	// The numerator and denominator are centred over and under the line, which is on the baseline
+	assert_eq!(layout_text("1/2+x"), " 1\n─── + x\n 2");
+	assert_eq!(layout_text("(a+1)/(b-2)"), " a + 1\n───────\n b − 2");
+	assert_eq!(layout_text("(1/2)"), "⎛ 1 ⎞\n⎜───⎟\n⎝ 2 ⎠");
+
+	// Scripts of digits stay on the line, and others are raised or lowered from the middle of their base
+	assert_eq!(layout_text("x^2"), "x²");
+	assert_eq!(layout_text("x_1^2"), "x₁²");
+	assert_eq!(layout_text("x^(a+1)"), " a + 1\nx");
+	assert_eq!(layout_text("e^(x/2)"), "  x\n ───\n  2\ne");
+	// A power after a subscript is stacked above it
+	assert_eq!(layout_text("x_(n+1)^(a+b)"), " a + b\nx\n n + 1");
+
+	// The sign runs up the left of what is inside, and its line along the top
+	assert_eq!(layout_text("sqrt(x)"), " ─\n√x");
+	assert_eq!(layout_text("sqrt(1/x)"), "┌───\n│ 1\n│───\n√ x");
+	assert_eq!(layout_text("root(3, 8)"), "  ─\n³√8");
+	assert_eq!(layout_text("root(n+1, x/2)"), "     ┌───\n     │ x\nn + 1│───\n     √ 2");
+}
+
+#[test]
+fn baseline_test() {
+	use parse::parse_text;
+	// The result is put after the expression as `equator-repl` does, on the same row as the baseline
+	let results = [("1/2+x", 1, " 1\n─── + x = 5\n 2"), ("e^(x/2)", 3, "  x\n ───\n  2\ne    = 5"),
+		("sqrt(1/x)", 2, "┌───\n│ 1\n│─── = 5\n√ x"), ("x^2", 0, "x² = 5")];
+	for &(text, baseline, expected) in results.iter() {
+		let mut block = layout_vexpr(&parse_text(text).unwrap());
+		block.append(&Block::text(" = 5"));
+		assert_eq!(block.baseline, baseline, "the baseline of '{}'", text);
+		assert_eq!(block.to_string(), expected);
+	}
+}
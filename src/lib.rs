@@ -0,0 +1,35 @@
+//! Equator's evaluator and expression editor, without the window. A `session::Session` holds a worksheet and the VM it
+//! is evaluated with, e.g. `Session::new().evaluate_text("2sin(x)")`.
+#![feature(box_syntax, const_fn)]
+#![allow(non_upper_case_globals)]
+#[macro_use]
+extern crate decimal;
+#[macro_use]
+extern crate lazy_static;
+#[cfg(feature = "render")]
+extern crate cairo;
+#[cfg(feature = "render")]
+extern crate cairo_sys;
+
+pub mod num;
+pub mod val;
+pub mod interval;
+pub mod uncertain;
+pub mod stat;
+pub mod vis;
+pub mod parse;
+pub mod edit;
+pub mod sheet;
+pub mod func;
+pub mod com;
+pub mod err;
+pub mod consts;
+pub mod json;
+pub mod save;
+pub mod diag;
+pub mod extent;
+pub mod action;
+pub mod session;
+pub mod layout;
+#[cfg(feature = "render")]
+pub mod render;
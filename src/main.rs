@@ -1,13 +1,10 @@
 //#![windows_subsystem = "windows"]
-#![feature(box_syntax, const_fn)]
+#![feature(box_syntax)]
 #![allow(non_upper_case_globals)]
 extern crate gtk;
 extern crate gdk;
 extern crate cairo;
-#[macro_use]
-extern crate decimal;
-#[macro_use]
-extern crate lazy_static;
+extern crate equator;
 #[cfg(windows)]
 extern crate kernel32;
 
@@ -16,19 +13,14 @@ use gtk::{Window, WindowType, WindowPosition};
 
 use std::ptr;
 
-pub mod num;
-pub mod vis;
-pub mod edit;
-pub mod func;
+// The engine is in the library, so that other programs can use it too
+pub use equator::{num, val, interval, uncertain, stat, vis, parse, edit, sheet, func, com, err, consts, json, save, diag};
+pub use equator::{extent, action, session, render};
 pub mod gui;
-pub mod render;
-pub mod com;
-pub mod err;
-pub mod consts;
+pub mod keymap;
 
 static mut g_window: *mut Window = ptr::null_mut();
-static mut g_editor: *mut edit::Editor = ptr::null_mut();
-static mut g_vm    : *mut com::VM = ptr::null_mut();
+static mut g_session: *mut session::Session = ptr::null_mut();
 static mut g_check_buttons: *mut gui::CheckButtons = ptr::null_mut();
 
 pub fn get_window() -> &'static mut Window {
@@ -39,21 +31,27 @@ pub fn get_window() -> &'static mut Window {
 		&mut *g_window
 	}
 }
-pub fn get_editor() -> &'static mut edit::Editor {
+pub fn get_session() -> &'static mut session::Session {
 	unsafe {
-		if g_editor.is_null() {
-			panic!("editor not initialized");
+		if g_session.is_null() {
+			panic!("session not initialized");
 		}
-		&mut *g_editor
+		&mut *g_session
 	}
 }
+pub fn get_sheet() -> &'static mut sheet::Worksheet {
+	&mut get_session().sheet
+}
+/// Gets the editor of the row of the worksheet being edited.
+pub fn get_editor() -> &'static mut edit::Editor {
+	get_sheet().editor()
+}
 pub fn get_vm() -> &'static mut com::VM {
-	unsafe {
-		if g_vm.is_null() {
-			panic!("vm not initialized");
-		}
-		&mut *g_vm
-	}
+	&mut get_session().ctx.vm
+}
+/// Gets what the editor needs to evaluate and change the expression: the VM, and the gui as its front end.
+pub fn get_context() -> &'static mut session::Context {
+	&mut get_session().ctx
 }
 pub fn get_check_buttons() -> &'static mut gui::CheckButtons {
 	unsafe {
@@ -142,9 +140,9 @@ fn main() {
 	
 	cfg_settings();
 	
-	let mut temp_edit = edit::Editor::new();
+	let mut temp_session = session::Session::with_frontend(box gui::GtkFrontend);
 	unsafe {
-		g_editor = &mut temp_edit;
+		g_session = &mut temp_session;
 	}
 	
 	let mut temp_win = Window::new(WindowType::Toplevel);
@@ -167,9 +165,8 @@ fn main() {
 			.map_err(|e| println!("Warning: Could not load icon: {}", e)).ok();
 	}
 	
-	let mut temp_vm = com::VM::new();
-	unsafe {
-		g_vm = &mut temp_vm;
+	if let Err(e) = save::load_history(::get_vm()) {
+		println!("Warning: Could not load history: {}", e);
 	}
 	
 	let mut temp_check_buttons = gui::CheckButtons::new();
@@ -38,6 +38,20 @@ pub fn round_dp(x: d128, dp: i32) -> d128 {
 	rounded / d128!(1.000000000000000000000000000000000)
 }
 
+/// Returns the largest integer that is not greater than `x`.
+pub fn floor(x: d128) -> d128 {
+	if x.is_infinite() || x.is_nan() || x.is_integer() {
+		return x;
+	}
+	let t = (x - x % *D1).quantize(*D1);
+	if t > x { t - *D1 } else { t }
+}
+
+/// Returns the smallest integer that is not less than `x`.
+pub fn ceil(x: d128) -> d128 {
+	-floor(-x)
+}
+
 /// Performs `v!`
 pub fn factorial(x: d128) -> d128 {
 	// Calculate the factorial using an approximation to the gamma function if `x` is not an integer, or is less than 0.
@@ -54,28 +68,478 @@ pub fn factorial(x: d128) -> d128 {
 	}
 }
 
-pub fn gamma(mut x: d128) -> d128 {
-	// Using the Lanczos approximation - https://en.wikipedia.org/wiki/Lanczos_approximation
-	let p = &[  d128!(676.5203681218851),   d128!(-1259.1392167224028),  d128!(771.32342877765313),
-			   d128!(-176.61502916214059),     d128!(12.507343278686905), d128!(-0.13857109526572012),
-				  d128!(9.9843695780195716e-6), d128!(1.5056327351493116e-7)];
-	
+pub fn gamma(x: d128) -> d128 {
 	if x < *DP5 {
+		// Reflection formula
 		*DPI / (sin(*DPI*x) * gamma(*D1-x))
 	} else {
-		x = x - *D1;
-		let mut y = d128!(0.99999999999980993);
-		
-		for (i, pval) in p.iter().enumerate() {
-			y = y + *pval / (x + d128::from(i as u32) + *D1);
+		exp(ln_gamma(x))
+	}
+}
+
+/// ln(Γ(x)), for x > 0.
+pub fn ln_gamma(x: d128) -> d128 {
+	// Stirling's series, with the coefficients B_2k / (2k(2k - 1)). This is accurate to full precision for x >= 40,
+	// so smaller values are shifted up using Γ(x + 1) = xΓ(x).
+	let coeffs: [(i64, i64); 12] = [(1, 12), (-1, 360), (1, 1260), (-1, 1680), (1, 1188), (-691, 360360), (1, 156),
+		(-3617, 122400), (43867, 244188), (-174611, 125400), (77683, 5796), (-236364091, 1506960)];
+	let threshold = d128::from(40);
+	
+	let mut x = x;
+	let mut shift = *D1;
+	while x < threshold {
+		shift = shift * x;
+		x = x + *D1;
+	}
+	
+	let mut sum = (x - *DP5) * x.ln() - x + *DLN_2PI / *D2;
+	let x2 = x * x;
+	let mut xpow = x;
+	for &(num, den) in coeffs.iter() {
+		sum = sum + d128::from(num) / (d128::from(den) * xpow);
+		xpow = xpow * x2;
+	}
+	sum - shift.ln()
+}
+
+/// The digamma function ψ(x) = Γ'(x)/Γ(x)
+pub fn digamma(x: d128) -> d128 {
+	if x < *DP5 {
+		// Reflection formula
+		return digamma(*D1 - x) - *DPI * cos(*DPI * x) / sin(*DPI * x);
+	}
+	// Asymptotic series, with the coefficients B_2k / 2k. This is accurate to full precision for x >= 100, so smaller
+	// values are shifted up using ψ(x + 1) = ψ(x) + 1/x.
+	let coeffs: [(i64, i64); 9] = [(1, 12), (-1, 120), (1, 252), (-1, 240), (1, 132), (-691, 32760), (1, 12),
+		(-3617, 8160), (43867, 14364)];
+	let threshold = d128::from(100);
+	
+	let mut x = x;
+	let mut shift = *D0;
+	while x < threshold {
+		shift = shift + *D1 / x;
+		x = x + *D1;
+	}
+	
+	let mut sum = x.ln() - *D1 / (*D2 * x);
+	let x2 = x * x;
+	let mut xpow = x2;
+	for &(num, den) in coeffs.iter() {
+		sum = sum - d128::from(num) / (d128::from(den) * xpow);
+		xpow = xpow * x2;
+	}
+	sum - shift
+}
+
+/// e^x
+pub fn exp(x: d128) -> d128 {
+	DE.pow(x)
+}
+
+/// Used in place of zero in continued fractions, to avoid dividing by zero.
+fn tiny() -> d128 {
+	d128!(1e-1000)
+}
+
+fn is_converged(delta: d128, sum: d128) -> bool {
+	delta.abs() <= sum.abs() * *DEPSILON
+}
+
+const MAX_ITER: u32 = 10000;
+
+/// The error function, erf(x) = 2/√π ∫₀ˣ e^(-t²) dt
+pub fn erf(x: d128) -> d128 {
+	if x.is_nan() {
+		x
+	} else if x.is_negative() {
+		-erf(-x)
+	} else if x >= d128::from(3) {
+		*D1 - erfc(x)
+	} else {
+		// erf(x) = 2/√π e^(-x²) Σ 2ⁿx^(2n+1) / (1·3·5···(2n+1)). All of the terms are positive, so there's no cancellation.
+		let x2 = x * x;
+		let mut term = x;
+		let mut sum = x;
+		let mut n = *D0;
+		for _ in 0..MAX_ITER {
+			n = n + *D1;
+			term = term * *D2 * x2 / (*D2 * n + *D1);
+			sum = sum + term;
+			if is_converged(term, sum) {
+				break;
+			}
 		}
-		
-		let t = x + d128::from(p.len() as u32) - *DP5;
-		((*D2)*(*DPI)).pow(*DP5) * t.pow(x+*DP5) * DE.pow(-t) * y
+		*D2 / *DSQRT_PI * exp(-x2) * sum
+	}
+}
+
+/// The complementary error function, erfc(x) = 1 - erf(x)
+pub fn erfc(x: d128) -> d128 {
+	if x.is_nan() || x < d128::from(3) {
+		return *D1 - erf(x);
+	}
+	// Continued fraction erfc(x) = e^(-x²)/√π · 1/(x + (1/2)/(x + 1/(x + (3/2)/(x + ...)))), evaluated using Lentz's method.
+	let mut f = x;
+	let mut c = x;
+	let mut d = *D0;
+	let mut n = *D0;
+	for _ in 0..MAX_ITER {
+		n = n + *D1;
+		let a = n / *D2;
+		d = x + a * d;
+		if d.is_zero() { d = tiny(); }
+		c = x + a / c;
+		if c.is_zero() { c = tiny(); }
+		d = *D1 / d;
+		let delta = c * d;
+		f = f * delta;
+		if is_converged(delta - *D1, *D1) {
+			break;
+		}
+	}
+	exp(-x * x) / (*DSQRT_PI * f)
+}
+
+/// ln(n choose k)
+fn ln_choose(n: d128, k: d128) -> d128 {
+	ln_gamma(n + *D1) - ln_gamma(k + *D1) - ln_gamma(n - k + *D1)
+}
+
+/// The regularized lower incomplete gamma function P(a, x), for a > 0 and x >= 0.
+pub fn gamma_p(a: d128, x: d128) -> d128 {
+	if x <= *D0 {
+		*D0
+	} else if x < a + *D1 {
+		gamma_p_series(a, x)
+	} else {
+		*D1 - gamma_q_frac(a, x)
 	}
 }
 
-const NUM_ITER: u32 = 10;
+/// The regularized upper incomplete gamma function Q(a, x) = 1 - P(a, x), for a > 0 and x >= 0.
+pub fn gamma_q(a: d128, x: d128) -> d128 {
+	if x <= *D0 {
+		*D1
+	} else if x < a + *D1 {
+		*D1 - gamma_p_series(a, x)
+	} else {
+		gamma_q_frac(a, x)
+	}
+}
+
+fn gamma_p_series(a: d128, x: d128) -> d128 {
+	let mut ap = a;
+	let mut del = *D1 / a;
+	let mut sum = del;
+	for _ in 0..MAX_ITER {
+		ap = ap + *D1;
+		del = del * x / ap;
+		sum = sum + del;
+		if is_converged(del, sum) {
+			break;
+		}
+	}
+	sum * exp(a * x.ln() - x - ln_gamma(a))
+}
+
+fn gamma_q_frac(a: d128, x: d128) -> d128 {
+	// Continued fraction, evaluated using Lentz's method.
+	let mut b = x + *D1 - a;
+	let mut c = *D1 / tiny();
+	let mut d = *D1 / b;
+	let mut h = d;
+	let mut i = *D0;
+	for _ in 0..MAX_ITER {
+		i = i + *D1;
+		let an = -i * (i - a);
+		b = b + *D2;
+		d = an * d + b;
+		if d.is_zero() { d = tiny(); }
+		c = b + an / c;
+		if c.is_zero() { c = tiny(); }
+		d = *D1 / d;
+		let delta = d * c;
+		h = h * delta;
+		if is_converged(delta - *D1, *D1) {
+			break;
+		}
+	}
+	exp(a * x.ln() - x - ln_gamma(a)) * h
+}
+
+/// The regularized incomplete beta function I_x(a, b), for a, b > 0 and 0 <= x <= 1.
+pub fn beta_i(a: d128, b: d128, x: d128) -> d128 {
+	if x <= *D0 {
+		return *D0;
+	} else if x >= *D1 {
+		return *D1;
+	}
+	let bt = exp(ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (*D1 - x).ln());
+	// The continued fraction converges quickly for x < (a + 1)/(a + b + 2), otherwise use the symmetry relation.
+	if x < (a + *D1) / (a + b + *D2) {
+		bt * beta_frac(a, b, x) / a
+	} else {
+		*D1 - bt * beta_frac(b, a, *D1 - x) / b
+	}
+}
+
+fn beta_frac(a: d128, b: d128, x: d128) -> d128 {
+	let qab = a + b;
+	let qap = a + *D1;
+	let qam = a - *D1;
+	let mut c = *D1;
+	let mut d = *D1 - qab * x / qap;
+	if d.is_zero() { d = tiny(); }
+	d = *D1 / d;
+	let mut h = d;
+	let mut m = *D0;
+	for _ in 0..MAX_ITER {
+		m = m + *D1;
+		let m2 = *D2 * m;
+		// Even step
+		let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+		d = *D1 + aa * d;
+		if d.is_zero() { d = tiny(); }
+		c = *D1 + aa / c;
+		if c.is_zero() { c = tiny(); }
+		d = *D1 / d;
+		h = h * d * c;
+		// Odd step
+		let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+		d = *D1 + aa * d;
+		if d.is_zero() { d = tiny(); }
+		c = *D1 + aa / c;
+		if c.is_zero() { c = tiny(); }
+		d = *D1 / d;
+		let delta = d * c;
+		h = h * delta;
+		if is_converged(delta - *D1, *D1) {
+			break;
+		}
+	}
+	h
+}
+
+/// Finds x in [lo, hi] such that f(x) = p, where f is an increasing function. If f(hi) < p, hi is doubled until it isn't.
+fn invert<F>(f: F, p: d128, mut lo: d128, mut hi: d128) -> d128 where F: Fn(d128) -> d128 {
+	for _ in 0..MAX_ITER {
+		if f(hi) >= p {
+			break;
+		}
+		lo = hi;
+		hi = hi * *D2;
+	}
+	for _ in 0..MAX_ITER {
+		let mid = (lo + hi) / *D2;
+		if mid == lo || mid == hi || is_converged(hi - lo, mid) {
+			return mid;
+		}
+		if f(mid) < p {
+			lo = mid;
+		} else {
+			hi = mid;
+		}
+	}
+	(lo + hi) / *D2
+}
+
+/// Finds the smallest integer k in [lo, hi] such that f(k) >= p, where f is an increasing function.
+/// If `hi` is `None`, the upper bound is found by doubling.
+fn invert_discrete<F>(f: F, p: d128, lo: d128, hi: Option<d128>) -> d128 where F: Fn(d128) -> d128 {
+	let mut lo = lo;
+	let mut hi = match hi {
+		Some(hi) => hi,
+		None => {
+			let mut hi = (lo * *D2).max(*D1);
+			while f(hi) < p && !hi.is_infinite() {
+				lo = hi;
+				hi = hi * *D2;
+			}
+			hi
+		}
+	};
+	if f(lo) >= p {
+		return lo;
+	}
+	// Invariant: f(lo) < p <= f(hi)
+	while hi - lo > *D1 {
+		let mid = ((lo + hi) / *D2).quantize(*D1);
+		let mid = if mid <= lo { lo + *D1 } else if mid >= hi { hi - *D1 } else { mid };
+		if f(mid) < p {
+			lo = mid;
+		} else {
+			hi = mid;
+		}
+	}
+	hi
+}
+
+fn is_probability(p: d128) -> bool {
+	p >= *D0 && p <= *D1
+}
+
+fn is_whole(n: d128) -> bool {
+	n.is_integer() && !n.is_negative()
+}
+
+/// Probability density function of the normal distribution N(μ, σ²)
+pub fn norm_pdf(x: d128, mu: d128, sigma: d128) -> Option<d128> {
+	if !(sigma > *D0) {
+		return None;
+	}
+	let z = (x - mu) / sigma;
+	Some(exp(-z * z / *D2) / (sigma * *DSQRT_2 * *DSQRT_PI))
+}
+
+/// Cumulative distribution function of the normal distribution N(μ, σ²)
+pub fn norm_cdf(x: d128, mu: d128, sigma: d128) -> Option<d128> {
+	if !(sigma > *D0) {
+		return None;
+	}
+	Some(erfc(-(x - mu) / (sigma * *DSQRT_2)) / *D2)
+}
+
+/// Inverse of `norm_cdf`
+pub fn inv_norm(p: d128, mu: d128, sigma: d128) -> Option<d128> {
+	if !(sigma > *D0) || !(p > *D0 && p < *D1) {
+		return None;
+	}
+	let z = invert(|z| erfc(-z / *DSQRT_2) / *D2, p, d128::from(-50), d128::from(50));
+	Some(mu + sigma * z)
+}
+
+/// Probability of exactly k successes in n trials, with a probability of success p
+pub fn binom_pdf(n: d128, p: d128, k: d128) -> Option<d128> {
+	if !is_whole(n) || !is_probability(p) || !k.is_integer() {
+		return None;
+	}
+	if k.is_negative() || k > n {
+		Some(*D0)
+	} else if p.is_zero() {
+		Some(if k.is_zero() { *D1 } else { *D0 })
+	} else if p == *D1 {
+		Some(if k == n { *D1 } else { *D0 })
+	} else {
+		Some(exp(ln_choose(n, k) + k * p.ln() + (n - k) * (*D1 - p).ln()))
+	}
+}
+
+/// Probability of at most k successes in n trials, with a probability of success p
+pub fn binom_cdf(n: d128, p: d128, k: d128) -> Option<d128> {
+	if !is_whole(n) || !is_probability(p) || !k.is_integer() {
+		return None;
+	}
+	if k.is_negative() {
+		Some(*D0)
+	} else if k >= n {
+		Some(*D1)
+	} else {
+		Some(beta_i(n - k, k + *D1, *D1 - p))
+	}
+}
+
+/// Smallest k such that `binom_cdf(n, p, k) >= prob`
+pub fn inv_binom(prob: d128, n: d128, p: d128) -> Option<d128> {
+	if !is_whole(n) || !is_probability(p) || !is_probability(prob) {
+		return None;
+	}
+	Some(invert_discrete(|k| binom_cdf(n, p, k).unwrap_or(*D1), prob, *D0, Some(n)))
+}
+
+/// Probability of exactly k events, where λ events are expected
+pub fn poiss_pdf(lambda: d128, k: d128) -> Option<d128> {
+	if !(lambda > *D0) || !k.is_integer() {
+		return None;
+	}
+	if k.is_negative() {
+		Some(*D0)
+	} else {
+		Some(exp(k * lambda.ln() - lambda - ln_gamma(k + *D1)))
+	}
+}
+
+/// Probability of at most k events, where λ events are expected
+pub fn poiss_cdf(lambda: d128, k: d128) -> Option<d128> {
+	if !(lambda > *D0) || !k.is_integer() {
+		return None;
+	}
+	if k.is_negative() {
+		Some(*D0)
+	} else {
+		Some(gamma_q(k + *D1, lambda))
+	}
+}
+
+/// Smallest k such that `poiss_cdf(λ, k) >= prob`
+pub fn inv_poiss(prob: d128, lambda: d128) -> Option<d128> {
+	if !(lambda > *D0) || !(prob >= *D0 && prob < *D1) {
+		return None;
+	}
+	Some(invert_discrete(|k| poiss_cdf(lambda, k).unwrap_or(*D1), prob, *D0, None))
+}
+
+/// Probability density function of Student's t-distribution with ν degrees of freedom
+pub fn t_pdf(x: d128, nu: d128) -> Option<d128> {
+	if !(nu > *D0) {
+		return None;
+	}
+	let half = (nu + *D1) / *D2;
+	Some(exp(ln_gamma(half) - ln_gamma(nu / *D2) - (nu * *DPI).ln() / *D2 - half * (*D1 + x * x / nu).ln()))
+}
+
+/// Cumulative distribution function of Student's t-distribution with ν degrees of freedom
+pub fn t_cdf(x: d128, nu: d128) -> Option<d128> {
+	if !(nu > *D0) {
+		return None;
+	}
+	let tail = beta_i(nu / *D2, *DP5, nu / (nu + x * x)) / *D2;
+	Some(if x.is_negative() { tail } else { *D1 - tail })
+}
+
+/// Inverse of `t_cdf`
+pub fn inv_t(p: d128, nu: d128) -> Option<d128> {
+	if !(nu > *D0) || !(p > *D0 && p < *D1) {
+		return None;
+	}
+	if p < *DP5 {
+		// The distribution is symmetric
+		return inv_t(*D1 - p, nu).map(|t| -t);
+	}
+	Some(invert(|t| t_cdf(t, nu).unwrap_or(*D1), p, *D0, *D1))
+}
+
+/// Probability density function of the chi-squared distribution with k degrees of freedom
+pub fn chi2_pdf(x: d128, k: d128) -> Option<d128> {
+	if !(k > *D0) {
+		return None;
+	}
+	if x.is_negative() {
+		Some(*D0)
+	} else if x.is_zero() {
+		Some(if k < *D2 { *DINFINITY } else if k == *D2 { *DP5 } else { *D0 })
+	} else {
+		let half = k / *D2;
+		Some(exp((half - *D1) * x.ln() - x / *D2 - half * D2.ln() - ln_gamma(half)))
+	}
+}
+
+/// Cumulative distribution function of the chi-squared distribution with k degrees of freedom
+pub fn chi2_cdf(x: d128, k: d128) -> Option<d128> {
+	if !(k > *D0) {
+		return None;
+	}
+	Some(gamma_p(k / *D2, x / *D2))
+}
+
+/// Inverse of `chi2_cdf`
+pub fn inv_chi2(p: d128, k: d128) -> Option<d128> {
+	if !(k > *D0) || !(p > *D0 && p < *D1) {
+		return None;
+	}
+	Some(invert(|x| chi2_cdf(x, k).unwrap_or(*D1), p, *D0, k.max(*D1)))
+}
+
+const NUM_ITER: u32 = 40;
 
 pub fn sin(x: d128) -> d128 {
 	sin_precision(x, NUM_ITER)
@@ -84,7 +548,14 @@ pub fn sin_precision(mut x: d128, iters: u32) -> d128 {
 	// Calculate based on taylor series. https://en.wikipedia.org/wiki/Sine#Series_definition
 	// Sum fromm 0 to ∞ of ((-1)^n / (2n + 1)!) * x^(2n + 1)
 	
-	x = x % *DPI;   // equals x
+	// Reduce x to [-π, π]
+	let tau = *DPI * *D2;
+	x = x % tau;
+	if x > *DPI {
+		x = x - tau;
+	} else if x < -*DPI {
+		x = x + tau;
+	}
 	if x == *DPI || x == -(*DPI) {
 		return *D0;
 	}
@@ -128,3 +599,169 @@ pub fn tan_precision(x: d128, iters: u32) -> d128 {
 	// tan(x) = sin(x) / cos(x)
 	sin_precision(x, iters) / cos_precision(x, iters)
 }
+
+pub fn sqrt(x: d128) -> d128 {
+	x.pow(*DP5)
+}
+
+pub fn ln(x: d128) -> d128 {
+	x.ln()
+}
+
+pub fn atan(x: d128) -> d128 {
+	if x.is_nan() {
+		return x;
+	} else if x.is_negative() {
+		return -atan(-x);
+	} else if x > *D1 {
+		// atan(x) = π/2 - atan(1/x)
+		return *DPI2 - atan(*D1 / x);
+	}
+	// Halve the argument twice using atan(x) = 2atan(x / (1 + √(1 + x²))), so that the series converges quickly.
+	let mut x = x;
+	for _ in 0..2 {
+		x = x / (*D1 + sqrt(*D1 + x * x));
+	}
+	// atan(x) = Σ (-1)ⁿ x^(2n+1) / (2n + 1)
+	let x2 = x * x;
+	let mut xpow = x;
+	let mut sum = x;
+	let mut n = *D1;
+	for _ in 0..MAX_ITER {
+		xpow = -xpow * x2;
+		n = n + *D2;
+		let term = xpow / n;
+		sum = sum + term;
+		if is_converged(term, sum) {
+			break;
+		}
+	}
+	sum * d128::from(4)
+}
+
+pub fn asin(x: d128) -> d128 {
+	if x.abs() > *D1 {
+		d128!(NaN)
+	} else if x.abs() == *D1 {
+		*DPI2 * x
+	} else {
+		atan(x / sqrt(*D1 - x * x))
+	}
+}
+
+pub fn acos(x: d128) -> d128 {
+	*DPI2 - asin(x)
+}
+
+pub fn sinh(x: d128) -> d128 {
+	if x.abs() < *D1 {
+		// sinh(x) = Σ x^(2n+1) / (2n + 1)!, which avoids the cancellation in (eˣ - e⁻ˣ) / 2 for small x.
+		let x2 = x * x;
+		let mut term = x;
+		let mut sum = x;
+		let mut n = *D1;
+		for _ in 0..MAX_ITER {
+			term = term * x2 / ((n + *D1) * (n + *D2));
+			n = n + *D2;
+			sum = sum + term;
+			if is_converged(term, sum) {
+				break;
+			}
+		}
+		sum
+	} else {
+		(exp(x) - exp(-x)) / *D2
+	}
+}
+
+pub fn cosh(x: d128) -> d128 {
+	(exp(x) + exp(-x)) / *D2
+}
+
+pub fn tanh(x: d128) -> d128 {
+	if x.abs() > d128::from(50) {
+		// tanh(x) is within 10⁻⁴³ of ±1
+		if x.is_negative() { -*D1 } else { *D1 }
+	} else {
+		sinh(x) / cosh(x)
+	}
+}
+
+pub fn atanh(x: d128) -> d128 {
+	if x.abs() > *D1 {
+		d128!(NaN)
+	} else if x.abs() < *DP5 {
+		// atanh(x) = Σ x^(2n+1) / (2n + 1)
+		let x2 = x * x;
+		let mut xpow = x;
+		let mut sum = x;
+		let mut n = *D1;
+		for _ in 0..MAX_ITER {
+			xpow = xpow * x2;
+			n = n + *D2;
+			let term = xpow / n;
+			sum = sum + term;
+			if is_converged(term, sum) {
+				break;
+			}
+		}
+		sum
+	} else {
+		((*D1 + x) / (*D1 - x)).ln() / *D2
+	}
+}
+
+pub fn asinh(x: d128) -> d128 {
+	if x.is_negative() {
+		-asinh(-x)
+	} else if x < *DP5 {
+		atanh(x / sqrt(*D1 + x * x))
+	} else {
+		(x + sqrt(x * x + *D1)).ln()
+	}
+}
+
+pub fn acosh(x: d128) -> d128 {
+	if x < *D1 {
+		d128!(NaN)
+	} else {
+		(x + sqrt(x * x - *D1)).ln()
+	}
+}
+
+#[test]
+fn distributions_test() {
+	// Compares with values from elsewhere, which are given to 16 significant figures
+	fn close(x: Option<d128>, expected: d128) {
+		let x = x.unwrap();
+		assert!((x - expected).abs() <= d128!(1e-15) * expected.abs(), "{} isn't {}", x, expected);
+	}
+	close(Some(erf(*D1)), d128!(0.8427007929497149));
+	close(Some(erf(d128!(-0.5))), d128!(-0.5204998778130465));
+	close(Some(erfc(d128!(2))), d128!(0.004677734981047266));
+	close(Some(erfc(d128!(10))), d128!(2.088487583762545e-45));
+	close(Some(gamma(d128!(0.5))), d128!(1.772453850905516));
+	close(Some(gamma(d128!(5))), d128!(24));
+
+	close(norm_pdf(*D0, *D0, *D1), d128!(0.3989422804014327));
+	close(norm_cdf(d128!(1.96), *D0, *D1), d128!(0.9750021048517795));
+	close(norm_cdf(d128!(90), d128!(100), d128!(15)), d128!(0.2524925375469229));
+	close(inv_norm(d128!(0.975), *D0, *D1), d128!(1.959963984540054));
+	close(binom_pdf(d128!(10), d128!(0.5), d128!(3)), d128!(0.1171875));
+	close(binom_cdf(d128!(10), d128!(0.5), d128!(3)), d128!(0.171875));
+	close(inv_binom(d128!(0.5), d128!(10), d128!(0.5)), d128!(5));
+	close(poiss_pdf(d128!(2), d128!(3)), d128!(0.1804470443154836));
+	close(poiss_cdf(d128!(2), d128!(3)), d128!(0.8571234604985470));
+	close(inv_poiss(d128!(0.9), d128!(2)), d128!(4));
+	close(t_pdf(*D0, *D1), d128!(0.3183098861837907));
+	close(t_cdf(d128!(2), d128!(5)), d128!(0.9490302605850709));
+	close(inv_t(d128!(0.975), d128!(10)), d128!(2.228138851986274));
+	close(chi2_pdf(d128!(2), d128!(2)), d128!(0.1839397205857212));
+	close(chi2_cdf(d128!(3.841458820694124), *D1), d128!(0.95));
+	close(inv_chi2(d128!(0.95), d128!(2)), d128!(5.991464547107979));
+
+	// Parameters outside of the distributions' domains
+	assert_eq!(norm_pdf(*D0, *D0, *D0), None);
+	assert_eq!(binom_pdf(d128!(10), d128!(1.5), d128!(3)), None);
+	assert_eq!(inv_norm(d128!(1.5), *D0, *D1), None);
+}
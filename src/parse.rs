@@ -0,0 +1,398 @@
+/// Parsing of linear text, such as `(a+1)/(b-2)` or `sqrt(x^2+1)`, into a structured expression.
+///
+/// Fractions, powers, roots and functions become the matching tokens, so the result is the same as if the expression
+/// had been entered with the editor. The positions in errors are character positions in the text.
+use consts::*;
+use err::*;
+use func::FuncType;
+use vis::*;
+
+/// Parses the text given into a new expression.
+pub fn parse_text(s: &str) -> Result<VExprRef, ParseError> {
+	let mut p = Parser{ chars: s.chars().collect(), pos: 0 };
+	let ex = VExpr::new_ref();
+	try!(p.parse_seq(&ex, &[]));
+	Ok(ex)
+}
+
+/// Adds the closing brackets missing from the end of `s`, such as in `sqrt(2*(x+1`. Returns `None` if there are none
+/// missing, or if the brackets are mismatched some other way. `|` opens and closes, so it is left alone.
+pub fn close_brackets(s: &str) -> Option<String> {
+	let mut open = Vec::new();
+	for c in s.chars() {
+		let closes = match c {
+			'|' => continue,
+			'{' => { open.push('}'); continue; },
+			'}' => true,
+			_ => match DelimType::from_open(c) {
+				Some(delim) => { open.push(delim.close()); continue; },
+				None => DelimType::from_close(c).is_some(),
+			},
+		};
+		if closes && open.pop() != Some(c) {
+			return None;
+		}
+	}
+	if open.is_empty() {
+		return None;
+	}
+	let mut closed = s.to_string();
+	closed.extend(open.into_iter().rev());
+	Some(closed)
+}
+
+struct Parser {
+	chars: Vec<char>,
+	pos: usize,
+}
+
+impl Parser {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).cloned()
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.peek().map_or(false, |c| c.is_whitespace()) {
+			self.pos += 1;
+		}
+	}
+
+	/// Returns the run of letters and digits starting at the current position.
+	fn word(&self) -> String {
+		self.chars[self.pos..].iter().take_while(|c| c.is_alphanumeric()).cloned().collect()
+	}
+
+	/// Reads an index into the history written with the digits that `digit` gives the values of, such as `12` in
+	/// `#12`. Returns `None` if it is too large for any result to have it.
+	fn index(&mut self, digit: fn(char) -> Option<usize>) -> Option<usize> {
+		let mut n: usize = 0;
+		while let Some(d) = self.peek().and_then(digit) {
+			n = match n.checked_mul(10).and_then(|n| n.checked_add(d)) {
+				Some(n) => n,
+				None => return None,
+			};
+			self.pos += 1;
+		}
+		Some(n)
+	}
+
+	/// Returns true if the next character that isn't whitespace after `i` characters from the current position is `c`.
+	fn followed_by(&self, i: usize, c: char) -> bool {
+		self.chars[self.pos + i..].iter().skip_while(|c| c.is_whitespace()).next() == Some(&c)
+	}
+
+	/// Skips whitespace, then consumes `c`. Returns an error at the current position if it isn't there.
+	fn expect(&mut self, c: char) -> Result<(), ParseError> {
+		self.skip_whitespace();
+		if self.peek() == Some(c) {
+			self.pos += 1;
+			Ok(())
+		} else {
+			Err(UnmatchedParen(self.pos))
+		}
+	}
+
+	/// Parses tokens into `ex` until one of `closers` is consumed, which is returned. If `closers` is empty, parses
+	/// until the end of the text.
+	fn parse_seq(&mut self, ex: &VExprRef, closers: &[char]) -> Result<Option<char>, ParseError> {
+		let mut last: Option<usize> = None; // The position in `ex` that the last term starts at
+		loop {
+			let c = match self.peek() {
+				Some(c) => c,
+				None if closers.is_empty() => return Ok(None),
+				None => return Err(UnmatchedParen(self.pos)),
+			};
+			// `|` both opens and closes, so it only closes if there is a term before it
+			if closers.contains(&c) && !(c == '|' && last.is_none()) {
+				self.pos += 1;
+				return Ok(Some(c));
+			}
+			match c {
+				_ if c.is_whitespace() => self.pos += 1,
+				')' | '}' | ']' | '⌋' | '⌉' => return Err(UnmatchedParen(self.pos)),
+				'^' => {
+					self.pos += 1;
+					let inner = VExpr::with_parent(ex.clone()).to_ref();
+					try!(self.parse_operand(&inner));
+					ex.borrow_mut().tokens.push(VToken::Pow(inner));
+				},
+				'_' => {
+					self.pos += 1;
+					let inner = VExpr::with_parent(ex.clone()).to_ref();
+					try!(self.parse_subscript(&inner));
+					ex.borrow_mut().tokens.push(VToken::Sub(inner));
+				},
+				'₀' ... '₉' => {
+					let inner = VExpr::with_parent(ex.clone()).to_ref();
+					while let Some(d) = self.peek().and_then(from_subscript) {
+						self.pos += 1;
+						inner.borrow_mut().tokens.push(VToken::Digit(::std::char::from_digit(d as u32, 10).unwrap()));
+					}
+					ex.borrow_mut().tokens.push(VToken::Sub(inner));
+				},
+				'/' | CHAR_DIV => {
+					self.pos += 1;
+					// The term before becomes the numerator
+					let num = VExpr::with_parent(ex.clone()).to_ref();
+					let mut tokens = {
+						let mut ex = ex.borrow_mut();
+						let start = last.unwrap_or(ex.tokens.len());
+						ex.tokens.split_off(start)
+					};
+					strip_parens(&mut tokens);
+					let tokens = copy_tokens(&tokens, &num);
+					num.borrow_mut().tokens = tokens;
+
+					let den = VExpr::with_parent(ex.clone()).to_ref();
+					try!(self.parse_operand(&den));
+					last = Some(ex.borrow().tokens.len());
+					ex.borrow_mut().tokens.push(VToken::Frac(num, den));
+				},
+				_ => {
+					let start = ex.borrow().tokens.len();
+					last = if try!(self.parse_item(ex)) { Some(start) } else { None };
+				},
+			}
+		}
+	}
+
+	/// Parses the operand of `^`, `/` or `√` into `ex`. Brackets around it are dropped, as `ex` groups it anyway.
+	fn parse_operand(&mut self, ex: &VExprRef) -> Result<(), ParseError> {
+		self.skip_whitespace();
+		match self.peek() {
+			Some('(') => {
+				self.pos += 1;
+				try!(self.parse_seq(ex, &[')']));
+			},
+			Some('-') | Some(CHAR_SUB) => {
+				self.pos += 1;
+				ex.borrow_mut().tokens.push(VToken::Op(OpType::Sub));
+				return self.parse_operand(ex);
+			},
+			Some(_) => {
+				try!(self.parse_item(ex));
+			},
+			None => {},
+		}
+		// Powers are right associative, so a following power belongs to the operand.
+		self.skip_whitespace();
+		if self.peek() == Some('^') {
+			self.pos += 1;
+			let inner = VExpr::with_parent(ex.clone()).to_ref();
+			try!(self.parse_operand(&inner));
+			ex.borrow_mut().tokens.push(VToken::Pow(inner));
+		}
+		Ok(())
+	}
+
+	/// Parses the operand of `_` into `ex`. Unlike other operands, a following power belongs to the base, as in `x_1^2`.
+	fn parse_subscript(&mut self, ex: &VExprRef) -> Result<(), ParseError> {
+		self.skip_whitespace();
+		match self.peek() {
+			Some('(') => {
+				self.pos += 1;
+				try!(self.parse_seq(ex, &[')']));
+			},
+			Some(_) => {
+				try!(self.parse_item(ex));
+			},
+			None => {},
+		}
+		Ok(())
+	}
+
+	/// Parses a single number, variable, operator or bracketed item into `ex`. Returns true if it was a term, and false
+	/// if it was an operator, a comma or `=`.
+	fn parse_item(&mut self, ex: &VExprRef) -> Result<bool, ParseError> {
+		let start = self.pos;
+		let c = match self.peek() {
+			Some(c) => c,
+			None => return Ok(false),
+		};
+		self.pos += 1;
+		let tok = match c {
+			_ if c.is_digit(10) || c == '.' => {
+				// The decimal point is a `Char`, as it is when typed
+				let mut ex = ex.borrow_mut();
+				ex.tokens.push(VToken::from_char(c));
+				while let Some(d) = self.peek() {
+					if d.is_digit(10) || d == '.' {
+						ex.tokens.push(VToken::from_char(d));
+						self.pos += 1;
+					} else {
+						break;
+					}
+				}
+				return Ok(true);
+			},
+			'+' | '-' | '*' | CHAR_SUB | CHAR_MUL | CHAR_PLUS_MINUS => {
+				ex.borrow_mut().tokens.push(VToken::from_char(c));
+				return Ok(false);
+			},
+			',' | '=' => {
+				ex.borrow_mut().tokens.push(VToken::Char(c));
+				return Ok(false);
+			},
+			_ if DelimType::from_open(c).is_some() => {
+				let delim = DelimType::from_open(c).unwrap();
+				let inner = VExpr::with_parent(ex.clone()).to_ref();
+				try!(self.parse_seq(&inner, &[delim.close()]));
+				VToken::Delim(delim, inner)
+			},
+			'{' => {
+				let inner = VExpr::with_parent(ex.clone()).to_ref();
+				try!(self.parse_seq(&inner, &['}']));
+				VToken::List(inner)
+			},
+			'#' => {
+				let n = try!(self.index(|d| d.to_digit(10).map(|d| d as usize)).ok_or(NoHistoryEntry(start)));
+				VToken::Hist(n)
+			},
+			'√' => {
+				let inner = VExpr::with_parent(ex.clone()).to_ref();
+				try!(self.parse_operand(&inner));
+				VToken::Func(FuncType::Sqrt, inner)
+			},
+			_ if c.is_alphabetic() => {
+				self.pos = start;
+				return self.parse_word(ex).map(|_| true);
+			},
+			_ => return Err(IllegalChar(c, start)),
+		};
+		ex.borrow_mut().tokens.push(tok);
+		Ok(true)
+	}
+
+	/// Parses a function call, `Ans`, a constant written out such as `pi`, or a single letter into `ex`.
+	fn parse_word(&mut self, ex: &VExprRef) -> Result<(), ParseError> {
+		let word = self.word();
+		let len = word.chars().count();
+		if word == "root" && self.followed_by(len, '(') {
+			self.pos += len;
+			try!(self.expect('('));
+			let degree = VExpr::with_parent(ex.clone()).to_ref();
+			let inner = VExpr::with_parent(ex.clone()).to_ref();
+			try!(self.parse_seq(&degree, &[',']));
+			try!(self.parse_seq(&inner, &[')']));
+			ex.borrow_mut().tokens.push(VToken::Root(degree, inner));
+			return Ok(());
+		}
+		if let Some(func) = FuncType::from_name(&word) {
+			if self.followed_by(len, '(') {
+				self.pos += len;
+				try!(self.expect('('));
+				let inner = VExpr::with_parent(ex.clone()).to_ref();
+				try!(self.parse_seq(&inner, &[')']));
+				let tok = match DelimType::from_func(func) {
+					Some(delim) => VToken::Delim(delim, inner),
+					None => VToken::Func(func, inner),
+				};
+				ex.borrow_mut().tokens.push(tok);
+				return Ok(());
+			}
+		}
+		if word.starts_with("Ans") {
+			// Older results are given with subscript digits, e.g. `Ans₂`
+			let start = self.pos;
+			self.pos += 3;
+			let n = try!(self.index(from_subscript).ok_or(NoHistoryEntry(start)));
+			ex.borrow_mut().tokens.push(VToken::Ans(n));
+			return Ok(());
+		}
+		// The constants can be written out, for keyboards without `π` and `φ`
+		let constant = match word.as_str() {
+			"pi" => Some('π'),
+			"phi" => Some('φ'),
+			_ => None,
+		};
+		if let Some(c) = constant {
+			ex.borrow_mut().tokens.push(VToken::Char(c));
+			self.pos += len;
+			return Ok(());
+		}
+		// Anything else is a variable, and letters next to each other are multiplied.
+		ex.borrow_mut().tokens.push(VToken::Char(self.chars[self.pos]));
+		self.pos += 1;
+		Ok(())
+	}
+}
+
+/// Returns the value of a subscript digit.
+fn from_subscript(c: char) -> Option<usize> {
+	"₀₁₂₃₄₅₆₇₈₉".chars().position(|d| d == c)
+}
+
+/// Removes the brackets around `tokens`, if it is a single bracketed expression.
+fn strip_parens(tokens: &mut Vec<VToken>) {
+	let inner = match tokens.first() {
+		Some(&VToken::Delim(DelimType::Paren, ref inner)) if tokens.len() == 1 => inner.borrow().tokens.clone(),
+		_ => return,
+	};
+	*tokens = inner;
+}
+
+
+#[test]
+fn decimal_point_test() {
+	// Numbers are parsed into the same tokens as typing them makes
+	let ex = parse_text("3.5x^0.25").unwrap();
+	let tokens = ex.borrow().tokens.clone();
+	match (&tokens[0], &tokens[1], &tokens[2]) {
+		(&VToken::Digit('3'), &VToken::Char('.'), &VToken::Digit('5')) => {},
+		_ => panic!("'3.5' was parsed as {:?}", &tokens[..3]),
+	}
+	assert_eq!(tokens_to_text(&tokens), "3.5x^0.25");
+}
+
+#[test]
+fn history_index_test() {
+	fn hist(text: &str) -> Option<usize> {
+		match parse_text(text) {
+			Ok(ref ex) => match ex.borrow().tokens.first() {
+				Some(&VToken::Hist(n)) | Some(&VToken::Ans(n)) => Some(n),
+				_ => panic!("'{}' wasn't parsed as a history reference", text),
+			},
+			Err(NoHistoryEntry(0)) => None,
+			Err(e) => panic!("parsing '{}' gave {}", text, e),
+		}
+	}
+	assert_eq!(hist("#12"), Some(12));
+	assert_eq!(hist("Ans₁₂"), Some(12));
+	assert_eq!(hist("#99999999999999999999999999"), None);
+	assert_eq!(hist("Ans₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉₉"), None);
+}
+
+#[test]
+fn text_test() {
+	// Each is written back as it was parsed, or as the text after it
+	let texts = [
+		("(a+1)/(b-2)", None), ("sqrt(x^2+1)", None), ("√2", Some("sqrt(2)")), ("root(3, 8)", None),
+		("2sin(x)^2", None), ("{1, 2, 3}", Some("{1,2,3}")), ("mean({1,2})", None), ("|x-1|", None),
+		("floor(2.5)", Some("⌊2.5⌋")), ("⌈x⌉", None), ("Ans", None), ("Ans₂", None), ("#3", None),
+		("x₁+y_2", Some("x_1+y_2")), ("1.5±0.1", None), ("e^(2x)", None), ("x^-1", Some("x^(-1)")),
+		("2×3÷4", Some("2*3/4")), ("-2^2", None), ("pi+φ", Some("π+φ")), ("a=3.5", None), ("1/2/3", Some("(1/2)/3")),
+		("x^2^3", Some("x^(2^3)")),
+	];
+	for &(text, written) in texts.iter() {
+		let ex = parse_text(text).unwrap();
+		let back = tokens_to_text(&ex.borrow().tokens);
+		assert_eq!(back, written.unwrap_or(text));
+		// Reading the text written gives the same expression
+		assert!(vexpr_eq(&parse_text(&back).unwrap(), &ex), "'{}' was read back differently", back);
+	}
+}
+
+#[test]
+fn error_test() {
+	// The positions are of the character at fault, or where a closing bracket is missing
+	let errors = [("2+$", IllegalChar('$', 2)), ("(1+2", UnmatchedParen(4)), ("1+2)", UnmatchedParen(3)), ("sqrt(2", UnmatchedParen(6))];
+	for &(ref text, ref expected) in errors.iter() {
+		match parse_text(text) {
+			Err(ref e) => assert_eq!(format!("{:?}", e), format!("{:?}", expected)),
+			Ok(_) => panic!("'{}' was parsed", text),
+		}
+	}
+	assert_eq!(close_brackets("sqrt(2*(x+1"), Some("sqrt(2*(x+1))".to_string()));
+	assert_eq!(close_brackets("(1+2)"), None);
+	assert_eq!(close_brackets("(1+2]"), None);
+}
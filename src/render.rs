@@ -1,17 +1,28 @@
-use std::mem;
-
 use cairo::{Antialias, Context, FontOptions};
 use cairo::enums::FontSlant;
 use cairo::enums::FontWeight;
 use cairo::enums::HintStyle;
 use cairo::LineCap;
+use cairo::prelude::SurfaceExt;
+use cairo::{Format, ImageSurface, Matrix, Status, Surface};
+use cairo_sys::cairo_surface_t;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::raw::{c_double, c_uchar, c_uint, c_void};
+use std::path::Path;
+use std::slice;
 
 use num::*;
 use edit::*;
 use err::ParseError;
+use val::Value;
 use vis::*;
 use self::Align::*;
 use func::FuncType;
+use sheet::Worksheet;
+use diag::{Diagnostic, Severity};
+pub use extent::Extent;
 
 static mut debug_view_extents: bool = false;
 pub fn toggle_debug_view() {
@@ -20,93 +31,41 @@ pub fn toggle_debug_view() {
 	}
 }
 
-#[derive(Debug, Copy, Clone)]
-pub struct Extent {
-	x0: f64,
-	y0: f64,
-	x1: f64,
-	y1: f64
-}
-impl Extent {
-	pub fn new(ex: (f64, f64, f64, f64)) -> Extent {
-		if mem::size_of::<Extent>() == mem::size_of::<(f64, f64, f64, f64)>() {
-			unsafe { mem::transmute(ex) }
-		} else {
-			Extent { x0:ex.0, y0:ex.1, x1:ex.2, y1:ex.3 }
-		}
-	}
-	pub const fn w(self) -> f64 {
-		self.x1 - self.x0
-	}
-	pub const fn h(self) -> f64 {
-		self.y1 - self.y0
-	}
-	pub fn enclosing(self, other: &Extent) -> Extent {
-		Extent{
-			x0: self.x0.min(other.x0), // min x
-			y0: self.y0.min(other.y0), // min y
-			x1: self.x1.max(other.x1), // max x
-			y1: self.y1.max(other.y1)  // max y
-		}
-	}
-	pub const fn translate(self, x: f64, y: f64) -> Extent {
-		Extent {
-			x0:self.x0 + x,
-			y0:self.y0 + y,
-			x1:self.x1 + x,
-			y1:self.y1 + y,
-		}
-	}
-	/// Returns if the rectangle contains the point (x, y)
-	pub const fn contains(self, x: f64, y: f64) -> bool {
-		(x >= self.x0 && x </*=*/ self.x1) && (y >= self.y0 && y </*=*/ self.y1)
-	}
-	
-	/// Splits the extent into two equal sized extents, one on the left, one on the right.
-	/// Returns (left, right)
-	pub fn split_lr(self) -> (Extent, Extent) {
-		let w  = self.w();
-		let l = Extent{x0:self.x0, y0:self.y0, x1:self.x0 + w / 2.0, y1:self.y1};
-		let r = Extent{x0:l.x0   , y0:self.y0, x1:self.x0 + w      , y1:self.y1};
-		(l, r)
-	}
-	
-	/// Splits the extent into two equal sized extents, one on the top, one on the bottom.
-	/// Returns (top, bottom)
-	pub fn split_tb(self) -> (Extent, Extent) {
-		let h  = self.h();
-		let t = Extent{x0:self.x0, y0:self.y0, x1:self.x1, y1:self.y0 + h / 2.0};
-		let b = Extent{x0:self.x0, y0:t.y0   , x1:self.x1, y1:self.y1};
-		(t, b)
-	}
-}
-
 const INIT_FONT_SIZE: f64 = 24.0;
+// The space around the worksheet, and between its rows
+const SHEET_MARGIN: f64 = 10.0;
+const SHEET_ROW_SPACING: f64 = 12.0;
+// The space around exported images of expressions, and between the expression and its result
+const EXPORT_PADDING: f64 = 8.0;
+const EXPORT_RESULT_SPACING: f64 = 12.0;
 
 #[derive(Copy, Clone)]
 pub struct ExtentState {
 	pub hit_len: usize,
 	pub error_len: usize,
+	pub selected_len: usize,
 	pub cursor_set: bool,
 }
 pub struct Extents {
 	pub states: Vec<ExtentState>, // Keep track of how much to translate the extents by
 	pub hitboxes: Vec<(Extent, Cursor)>,
-	pub errors: Vec<Extent>,
+	pub errors: Vec<(Extent, usize)>, // With the index of the diagnostic in the editor
+	pub selected: Vec<Extent>,
 	pub cursor_extent: Option<Extent>
 }
 impl Extents {
 	pub fn new() -> Extents {
-		Extents { states: Vec::new(), hitboxes: Vec::new(), errors: Vec::new(), cursor_extent: None }
+		Extents { states: Vec::new(), hitboxes: Vec::new(), errors: Vec::new(), selected: Vec::new(), cursor_extent: None }
 	}
 	pub fn reset(&mut self) {
 		self.states.clear();
 		self.hitboxes.clear();
 		self.errors.clear();
+		self.selected.clear();
 		self.cursor_extent = None;
 	}
 	pub fn get_state(&self) -> ExtentState {
-		ExtentState{ hit_len: self.hitboxes.len(), error_len: self.errors.len(), cursor_set: self.cursor_extent.is_some() }
+		ExtentState{ hit_len: self.hitboxes.len(), error_len: self.errors.len(), selected_len: self.selected.len(), cursor_set: self.cursor_extent.is_some() }
 	}
 	pub fn push_state(&mut self) {
 		let state = self.get_state();
@@ -117,13 +76,18 @@ impl Extents {
 		self.hitboxes.push((extent, cursor));
 	}
 	#[inline]
-	pub fn push_error(&mut self, extent: Extent) {
-		self.errors.push(extent);
+	pub fn push_error(&mut self, extent: Extent, diag: usize) {
+		self.errors.push((extent, diag));
+	}
+	#[inline]
+	pub fn push_selected(&mut self, extent: Extent) {
+		self.selected.push(extent);
 	}
 	pub fn pop_state(&mut self) {
 		let prev_state = match self.states.pop() { Some(v) => v, _ => { println!("error: mismatching states"); return; } };
 		self.hitboxes.truncate(prev_state.hit_len);
 		self.errors.truncate(prev_state.error_len);
+		self.selected.truncate(prev_state.selected_len);
 		
 		if !prev_state.cursor_set {
 			self.cursor_extent = None;
@@ -139,7 +103,10 @@ impl Extents {
 			self.hitboxes[i].0 = self.hitboxes[i].0.translate(x, y);
 		}
 		for i in from.error_len..to.error_len {
-			self.errors[i] = self.errors[i].translate(x, y);
+			self.errors[i].0 = self.errors[i].0.translate(x, y);
+		}
+		for i in from.selected_len..to.selected_len {
+			self.selected[i] = self.selected[i].translate(x, y);
 		}
 	}
 	pub fn translate(&mut self, x:f64, y:f64) {
@@ -151,6 +118,7 @@ impl Extents {
 	/// u32 is 0 when it is transforming the cursor
 	//         1 when it is transforming a hitbox
 	//         2 when it is transforming an error box
+	//         3 when it is transforming a selected box
 	pub fn transform_from_to<F>(&mut self, from: ExtentState, to: ExtentState, f: F) where F: Fn(Extent, u32) -> Extent {
 		if !from.cursor_set && to.cursor_set {
 			self.cursor_extent = self.cursor_extent.map(|ex| f(ex, 0));
@@ -160,7 +128,10 @@ impl Extents {
 			self.hitboxes[i].0 = f(self.hitboxes[i].0, 1);
 		}
 		for i in from.error_len..to.error_len {
-			self.errors[i] = f(self.errors[i], 2);
+			self.errors[i].0 = f(self.errors[i].0, 2);
+		}
+		for i in from.selected_len..to.selected_len {
+			self.selected[i] = f(self.selected[i], 3);
 		}
 	}
 	pub fn transform<F>(&mut self, f: F) where F: Fn(Extent, u32) -> Extent {
@@ -178,16 +149,32 @@ pub struct Render<'a> {
 	pub prev_extent: Option<Extent>,
 	pub root_ex: VExprRef,
 	pub cursor: Cursor,
-	pub errors: Vec<Span>,
+	pub diagnostics: Vec<Diagnostic>,
+	pub selection: Option<Span>,
+	pub show_cursor: bool,
+	pub foreground: (f64, f64, f64),
+	// The transformation of the context when the renderer was made, which everything is drawn with, so that the
+	// context can be scaled beforehand
+	matrix: Matrix,
 }
 
 impl<'a> Render<'a> {
 	pub fn new(c: &'a Context, ed: &Editor) -> Render<'a> {
-		Render {exts: Extents::new(), c: c, prev_extent: None, root_ex: ed.root_ex.clone(), cursor: ed.cursor.clone(), errors: ed.errors.clone() }
+		Render {exts: Extents::new(), c: c, prev_extent: None, root_ex: ed.root_ex.clone(), cursor: ed.cursor.clone(), diagnostics: ed.diagnostics.clone(),
+			selection: ed.selection(), show_cursor: true, foreground: (0.0, 0.0, 0.0), matrix: c.get_matrix() }
 	}
 	
-	#[allow(unused_variables)]
+	/// Renders the expression in the middle of the area given.
 	pub fn render(&mut self, alloc_w: f64, alloc_h: f64) -> Extent {
+		self.render_aligned(alloc_w/2.0, alloc_h/2.0, Mid)
+	}
+	
+	/// Renders the expression with its top left corner at (x, y).
+	pub fn render_at(&mut self, x: f64, y: f64) -> Extent {
+		self.render_aligned(x, y, BotRight)
+	}
+	
+	fn render_aligned(&mut self, anchor_x: f64, anchor_y: f64, alignment: Align) -> Extent {
 		// TODO: Load font face from 'resources/Computer Modern.ttf'
 		self.c.select_font_face("CMU Serif", FontSlant::Normal, FontWeight::Normal);
 		self.c.set_font_size(INIT_FONT_SIZE);
@@ -196,7 +183,7 @@ impl<'a> Render<'a> {
 		opt.set_antialias(Antialias::Best);
 		opt.set_hint_style(HintStyle::Medium);
 		self.c.set_font_options(&opt);
-		self.c.identity_matrix();
+		self.c.set_matrix(self.matrix);
 		
 		self.exts = Extents::new();
 		
@@ -207,8 +194,7 @@ impl<'a> Render<'a> {
 		let path = self.c.copy_path();
 		
 		// === ALIGN ===
-		let (mut x, mut y) = align(&full_extent, alloc_w/2.0, alloc_h/2.0, Mid); // Central
-		//let (mut x, mut y) = align(&full_extent, alloc_w - 10.0 alloc_h/2.0, MidRight); // Right
+		let (mut x, mut y) = align(&full_extent, anchor_x, anchor_y, alignment);
 		
 		/*let (mut x, mut y) = match get_final_alignment() {
 			FinalAlignment::Central => align(&full_extent, alloc_w/2.0, alloc_h/2.0, Mid),
@@ -220,7 +206,7 @@ impl<'a> Render<'a> {
 		y = y.floor();
 		self.exts.translate(x, y);
 		
-		self.c.identity_matrix();
+		self.c.set_matrix(self.matrix);
 		self.c.new_path();
 		if unsafe { debug_view_extents } {
 			for &(ex, _) in self.exts.hitboxes.iter() {
@@ -232,29 +218,42 @@ impl<'a> Render<'a> {
 			self.c.stroke();
 		}
 		
-		// Stroke errors
+		// Highlight the selection
 		self.c.new_path();
-		for &ex in self.exts.errors.iter() {
+		for &ex in self.exts.selected.iter() {
 			self.c.rectangle(ex.x0, ex.y0, ex.w(), ex.h());
 		}
-		self.c.set_source_rgb(1.0, 0.0, 0.0);
-		self.c.set_line_width(1.0);
-		self.c.set_line_cap(LineCap::Round);
-		self.c.stroke();
+		self.c.set_source_rgb(0.7, 0.8, 1.0);
+		self.c.fill();
+		
+		// Stroke errors in red and warnings in orange
+		for &(severity, r, g, b) in [(Severity::Error, 1.0, 0.0, 0.0), (Severity::Warning, 1.0, 0.6, 0.0)].iter() {
+			self.c.new_path();
+			for &(ex, i) in self.exts.errors.iter() {
+				if self.diagnostics[i].severity == severity {
+					self.c.rectangle(ex.x0, ex.y0, ex.w(), ex.h());
+				}
+			}
+			self.c.set_source_rgb(r, g, b);
+			self.c.set_line_width(1.0);
+			self.c.set_line_cap(LineCap::Round);
+			self.c.stroke();
+		}
 		
 		self.c.translate(x, y);
 		self.c.new_path();
 		self.c.append_path(&path);
-		self.c.identity_matrix();
-		self.c.set_source_rgb(0.0, 0.0, 0.0);
+		self.c.set_matrix(self.matrix);
+		let (r, g, b) = self.foreground;
+		self.c.set_source_rgb(r, g, b);
 		self.c.fill();
 		
 		// Draw cursor
-		if self.cursor.is_visible() && self.exts.cursor_extent.is_some() {
+		if self.show_cursor && self.cursor.is_visible() && self.exts.cursor_extent.is_some() {
 			let ex = self.exts.cursor_extent.unwrap();
 			self.c.rectangle(ex.x0, ex.y0, ex.w(), ex.h());
 		}
-		self.c.set_source_rgb(0.0, 0.0, 0.0);
+		self.c.set_source_rgb(r, g, b);
 		self.c.fill();
 		
 		full_extent.translate(x, y)
@@ -286,14 +285,19 @@ impl<'a> Render<'a> {
 		let mut full_extent = Extent{x0:current_x, y0:current_y, x1:current_x, y1:current_y};
 		self.prev_extent.unwrap_or(self.box_extent());
 		
-		{ // Replace `^(x)` with `□^(x)`
+		{ // Replace `^(x)` with `□^(x)`, and `_(x)` with `□_(x)`
 			let mut i = 0;
 			let toks = &mut expr.borrow_mut().tokens;
 			loop {
 				if i >= toks.len() {
 					break;
 				}
-				if match &toks[i] { &VToken::Pow(_) if i == 0 || match &toks[i - 1] { &VToken::Char(_) | &VToken::Digit(_) => false, _ => true } => true, _ => false } {
+				let needs_base = match &toks[i] {
+					&VToken::Pow(_) => i == 0 || match &toks[i - 1] { &VToken::Char(_) | &VToken::Digit(_) | &VToken::Sub(_) => false, _ => true },
+					&VToken::Sub(_) => i == 0 || match &toks[i - 1] { &VToken::Char(_) | &VToken::Digit(_) => false, _ => true },
+					_ => false,
+				};
+				if needs_base {
 					toks.insert(i, VToken::Space);
 					i += 1;
 				}
@@ -303,6 +307,7 @@ impl<'a> Render<'a> {
 		
 		// loop through the tokens in the array
 		let mut cursor_i: isize = 0;
+		let mut script_base: Option<(Extent, f64)> = None; // The base of the last subscript, and where the subscript ended
 		for i in 0..expr.borrow().tokens.len() {
 			if cursor_in_ex && self.cursor.pos == cursor_i as usize {
 				self.exts.cursor_extent = Some(get_cursor_extent(self.c.get_current_point(), self.get_scale()));
@@ -311,6 +316,12 @@ impl<'a> Render<'a> {
 				self.exts.cursor_extent = None;
 			}
 			
+			let is_space = match expr.borrow().tokens[i] { VToken::Space => true, _ => false };
+			let selected = !is_space && match self.selection {
+				Some(ref span) => is_equal_reference(&span.ex, &expr) && cursor_i as usize >= span.start && (cursor_i as usize) < span.end,
+				None => false,
+			};
+			
 			// Main rendering block
 			match &expr.borrow().tokens[i] {
 				&VToken::Space => {
@@ -347,55 +358,44 @@ impl<'a> Render<'a> {
 					self.exts.push(r, Cursor::new_ex(expr.clone(), cursor_i as usize + 1));
 					self.prev_extent = Some(extent);
 				},
+				&VToken::Ans(n) => {
+					let sub = if n == 0 { String::new() } else { n.to_string() };
+					let extent = self.path_history_ref("Ans", &sub);
+					let (l, r)  = extent.split_lr();
+					self.exts.push(l, Cursor::new_ex(expr.clone(), cursor_i as usize));
+					self.exts.push(r, Cursor::new_ex(expr.clone(), cursor_i as usize + 1));
+					self.prev_extent = Some(extent);
+				},
+				&VToken::Hist(n) => {
+					let s = if n == 0 { "#".to_string() } else { format!("#{}", n) };
+					let extent = self.path_history_ref(&s, "");
+					let (l, r)  = extent.split_lr();
+					self.exts.push(l, Cursor::new_ex(expr.clone(), cursor_i as usize));
+					self.exts.push(r, Cursor::new_ex(expr.clone(), cursor_i as usize + 1));
+					self.prev_extent = Some(extent);
+				},
 				&VToken::Pow(ref inner_expr) => {
-					self.c.save();
-					let orig_path = self.c.copy_path();
-					let (orig_x, orig_y) = self.c.get_current_point();
-					let orig_scale = self.get_scale();
-					
-					self.c.new_path();
-					self.exts.push_state();
-					self.set_scale(0.8);
-					let base_prev_extent = self.prev_extent.unwrap();
-					let mut exp_extents = self.path_expr(inner_expr.clone());
-					
-					let exp_path = self.c.copy_path();
-					let anchor_x = base_prev_extent.x1;
-					let anchor_y = base_prev_extent.y0 + base_prev_extent.h() / 2.0;
-					//println!("anchor_y ({}) = prev_extent.y0 ({}) + prev_extent.h() ({}) / 2.0 ({})", anchor_y, prev_extent.y0, prev_extent.h(), prev_extent.h() / 2.0);
-					let (mut x, mut y) = align(&exp_extents, anchor_x, anchor_y, TopRight);
-					//self.exts.push(Extent{x0:anchor_x - 10.0, y0:anchor_y - 100.0, x1:anchor_x + 10.0, y1:anchor_y + 10.0}, Cursor::new());
-					x = x.floor();
-					y = y.floor();
-					exp_extents = exp_extents.translate(x, y);
-					exp_extents.x1 += 2.0;
-					
-					let descent = self.get_descent() / self.get_scale();
-					self.exts.transform(|ex, typ| {
-						let mut new_ex = ex.translate(x, y);
-						if typ == 1 {
-							new_ex.y1 = orig_y + descent;
-						}
-						new_ex
-					});
-					
-					{
-						let mut after_extent = exp_extents;
-						after_extent.x0 = after_extent.x1 - 2.0;
-						after_extent.y1 = orig_y + self.get_descent() / self.get_scale();
-						self.exts.push(after_extent, Cursor::new_ex(inner_expr.clone(), inner_expr.borrow().tokens.len()));
+					let after_sub = i != 0 && match expr.borrow().tokens[i - 1] { VToken::Sub(_) => true, _ => false };
+					match script_base {
+						Some((base, sub_end_x)) if after_sub => {
+							// Stack the power above the subscript, both against the same base
+							let (_, y) = self.c.get_current_point();
+							self.c.move_to(base.x1, y);
+							let extent = self.path_script(inner_expr.clone(), base, true);
+							self.c.move_to(sub_end_x.max(base.x1 + extent.w()), y);
+							self.prev_extent = Some(extent);
+						},
+						_ => {
+							let base = self.prev_extent.unwrap();
+							self.prev_extent = Some(self.path_script(inner_expr.clone(), base, true));
+						},
 					}
-					
-					// All together now!
-					self.c.new_path();
-					self.c.append_path(&orig_path);
-					self.c.translate(x, y);
-					self.c.append_path(&exp_path);
-					self.c.identity_matrix();
-					self.c.restore();
-					self.set_scale(orig_scale);
-					self.c.move_to(orig_x + exp_extents.w(), orig_y); // Moves the current point onwards the width of the exp_path.
-					self.prev_extent = Some(exp_extents);
+				},
+				&VToken::Sub(ref inner_expr) => {
+					let base = self.prev_extent.unwrap();
+					let extent = self.path_script(inner_expr.clone(), base, false);
+					script_base = Some((base, self.c.get_current_point().0));
+					self.prev_extent = Some(extent);
 				},
 				&VToken::Func(FuncType::Sqrt, ref inner_expr) => {
 					self.prev_extent = Some(self.path_root(inner_expr.clone(), None));
@@ -405,50 +405,14 @@ impl<'a> Render<'a> {
 				},
 				&VToken::Func(ref func_type, ref inner_expr) => {
 					// Paths the beginning of the function, the " sin("
-					let (abs_orig_x, abs_orig_y) = self.c.get_current_point();
-					self.c.rel_move_to(5.0, 0.0);
-					self.c.text_path(format!("{}(", func_type).as_str());
-					
-					self.c.save();
-					let orig_path = self.c.copy_path();
-					let (orig_x, orig_y) = self.c.get_current_point();
-					let func_ident_extent = Extent{x0:abs_orig_x, y0:abs_orig_y-self.get_ascent(), x1:orig_x, y1:orig_y+self.get_descent()};
-					{
-						let (l, r) = func_ident_extent.split_lr();
-						self.exts.push(l, Cursor::new_ex(expr.clone(), cursor_i as usize));
-						self.exts.push(r, Cursor::new_ex(inner_expr.clone(), 0));
-					}
-					
-					self.c.new_path();
-					self.exts.push_state();
-					let mut inner_extents = self.path_expr(inner_expr.clone());
-					
-					let func_path = self.c.copy_path();
-					let (mut x, _) = align(&inner_extents, orig_x, orig_y, MidRight);
-					x = x.floor();
-					inner_extents = inner_extents.translate(x, 0.0);
-					
-					self.exts.translate(x, 0.0);
-					
-					self.c.new_path();
-					self.c.append_path(&orig_path);
-					self.c.translate(x, 0.0);
-					self.c.append_path(&func_path);
-					self.c.restore();
-					self.c.move_to(orig_x + inner_extents.w() - 1.0, orig_y); // Moves the current point onwards the width of the func_path.
-					self.c.text_path(")");
-					let end_x = self.c.get_current_point().0 + 1.0;
-					let outer_extent = Extent{x0:orig_x, y0:orig_y-self.get_ascent(), x1:end_x, y1:orig_y+self.get_descent()};
-					let func_extent = outer_extent.enclosing(&inner_extents);
-					
-					{
-						let end_extent = Extent{x0:orig_x + inner_extents.w() - 1.0, y0:orig_y-self.get_ascent(), x1:end_x, y1:orig_y+self.get_descent()};
-						let (l, r) = end_extent.split_lr();
-						self.exts.push(l, Cursor::new_ex(inner_expr.clone(), inner_expr.borrow().tokens.len()));
-						self.exts.push(r, Cursor::new_ex(expr.clone(), cursor_i as usize + 1));
-					}
-					
-					self.prev_extent = Some(func_extent);
+					let open = format!("{}(", func_type);
+					self.prev_extent = Some(self.path_delimited(expr.clone(), cursor_i as usize, inner_expr.clone(), 5.0, &open, ")"));
+				},
+				&VToken::List(ref inner_expr) => {
+					self.prev_extent = Some(self.path_delimited(expr.clone(), cursor_i as usize, inner_expr.clone(), 0.0, "{", "}"));
+				},
+				&VToken::Delim(delim, ref inner_expr) => {
+					self.prev_extent = Some(self.path_delim(expr.clone(), cursor_i as usize, inner_expr.clone(), delim));
 				},
 				&VToken::Frac(ref num_ex, ref den_expr) => {
 					self.prev_extent = Some(self.path_frac(num_ex.clone(), den_expr.clone()));
@@ -456,10 +420,16 @@ impl<'a> Render<'a> {
 			}
 			cursor_i += 1;
 			
-			if is_cursor_in_spans(&self.errors, &Cursor::new_ex(expr.clone(), cursor_i as usize)) {
+			if selected {
+				if let Some(ext) = self.prev_extent {
+					self.exts.push_selected(ext);
+				}
+			}
+			let diag = if is_space { None } else { self.diagnostic_index(&expr, (cursor_i - 1) as usize) };
+			if let Some(diag) = diag {
 				// Push an error extent
 				if let Some(ext) = self.prev_extent {
-					self.exts.push_error(ext);
+					self.exts.push_error(ext, diag);
 				}
 			}
 			
@@ -495,11 +465,217 @@ impl<'a> Render<'a> {
 			full_extent = full_extent.enclosing(&box_extent);
 			self.c.rel_move_to(box_extent.w() - 15.0, 0.0);
 			self.exts.push(box_extent, Cursor::new_ex(expr.clone(), 0));
+			if let Some(diag) = self.diagnostic_index(&expr, 0) {
+				self.exts.push_error(box_extent, diag);
+			}
 		}
 		
 		full_extent
 	}
+	
+	/// Returns the index of the first diagnostic about token `i` of `ex`, or about the box of `ex` if it is empty.
+	fn diagnostic_index(&self, ex: &VExprRef, i: usize) -> Option<usize> {
+		let empty = ex.borrow().tokens.len() == 0;
+		self.diagnostics.iter().position(|d| is_equal_reference(&d.span.ex, ex) && d.span.start <= i && (i < d.span.end || empty))
+	}
+	
+	/// Paths `inner_expr` smaller, raised as a power if `raised` is true or lowered as a subscript otherwise, against the
+	/// extent of its base.
+	fn path_script(&mut self, inner_expr: VExprRef, base: Extent, raised: bool) -> Extent {
+		self.c.save();
+		let orig_path = self.c.copy_path();
+		let (orig_x, orig_y) = self.c.get_current_point();
+		let orig_scale = self.get_scale();
+		let ascent = self.get_ascent();
+		
+		self.c.new_path();
+		self.exts.push_state();
+		self.set_scale(0.8);
+		let mut script_extents = self.path_expr(inner_expr.clone());
+		
+		let script_path = self.c.copy_path();
+		let anchor_x = base.x1;
+		let anchor_y = base.y0 + base.h() / 2.0;
+		let (mut x, mut y) = align(&script_extents, anchor_x, anchor_y, if raised { TopRight } else { BotRight });
+		x = x.floor();
+		y = y.floor();
+		script_extents = script_extents.translate(x, y);
+		script_extents.x1 += 2.0;
+		
+		// Stretch the hitboxes to the baseline, so that the script is easy to click into
+		let descent = self.get_descent() / self.get_scale();
+		self.exts.transform(|ex, typ| {
+			let mut new_ex = ex.translate(x, y);
+			if typ == 1 {
+				if raised {
+					new_ex.y1 = orig_y + descent;
+				} else {
+					new_ex.y0 = orig_y - ascent;
+				}
+			}
+			new_ex
+		});
+		
+		{
+			let mut after_extent = script_extents;
+			after_extent.x0 = after_extent.x1 - 2.0;
+			if raised {
+				after_extent.y1 = orig_y + self.get_descent() / self.get_scale();
+			} else {
+				after_extent.y0 = orig_y - ascent;
+			}
+			self.exts.push(after_extent, Cursor::new_ex(inner_expr.clone(), inner_expr.borrow().tokens.len()));
+		}
+		
+		// All together now!
+		self.c.new_path();
+		self.c.append_path(&orig_path);
+		self.c.translate(x, y);
+		self.c.append_path(&script_path);
+		self.c.set_matrix(self.matrix);
+		self.c.restore();
+		self.set_scale(orig_scale);
+		self.c.move_to(orig_x + script_extents.w(), orig_y); // Moves the current point onwards the width of the script.
+		script_extents
+	}
+	
+	/// Paths `open`, then `inner_expr`, then `close`, e.g. " sin(x)" or "{1, 2}".
+	/// `expr` and `cursor_i` give the position of the token in its parent expression, and `lead` is the space to leave before `open`.
+	fn path_delimited(&mut self, expr: VExprRef, cursor_i: usize, inner_expr: VExprRef, lead: f64, open: &str, close: &str) -> Extent {
+		let (abs_orig_x, abs_orig_y) = self.c.get_current_point();
+		self.c.rel_move_to(lead, 0.0);
+		self.c.text_path(open);
+		
+		self.c.save();
+		let orig_path = self.c.copy_path();
+		let (orig_x, orig_y) = self.c.get_current_point();
+		let func_ident_extent = Extent{x0:abs_orig_x, y0:abs_orig_y-self.get_ascent(), x1:orig_x, y1:orig_y+self.get_descent()};
+		{
+			let (l, r) = func_ident_extent.split_lr();
+			self.exts.push(l, Cursor::new_ex(expr.clone(), cursor_i));
+			self.exts.push(r, Cursor::new_ex(inner_expr.clone(), 0));
+		}
+		
+		self.c.new_path();
+		self.exts.push_state();
+		let mut inner_extents = self.path_expr(inner_expr.clone());
+		
+		let func_path = self.c.copy_path();
+		let (mut x, _) = align(&inner_extents, orig_x, orig_y, MidRight);
+		x = x.floor();
+		inner_extents = inner_extents.translate(x, 0.0);
+		
+		self.exts.translate(x, 0.0);
+		
+		self.c.new_path();
+		self.c.append_path(&orig_path);
+		self.c.translate(x, 0.0);
+		self.c.append_path(&func_path);
+		self.c.restore();
+		self.c.move_to(orig_x + inner_extents.w() - 1.0, orig_y); // Moves the current point onwards the width of the func_path.
+		self.c.text_path(close);
+		let end_x = self.c.get_current_point().0 + 1.0;
+		let outer_extent = Extent{x0:orig_x, y0:orig_y-self.get_ascent(), x1:end_x, y1:orig_y+self.get_descent()};
+		let func_extent = outer_extent.enclosing(&inner_extents);
+		
+		{
+			let end_extent = Extent{x0:orig_x + inner_extents.w() - 1.0, y0:orig_y-self.get_ascent(), x1:end_x, y1:orig_y+self.get_descent()};
+			let (l, r) = end_extent.split_lr();
+			self.exts.push(l, Cursor::new_ex(inner_expr.clone(), inner_expr.borrow().tokens.len()));
+			self.exts.push(r, Cursor::new_ex(expr.clone(), cursor_i + 1));
+		}
+		
+		func_extent
+	}
 
+	/// Paths `inner_expr` between a pair of delimiters, e.g. `(x)` or `⌊x⌋`, which grow to fit around it.
+	/// `expr` and `cursor_i` give the position of the token in its parent expression.
+	fn path_delim(&mut self, expr: VExprRef, cursor_i: usize, inner_expr: VExprRef, delim: DelimType) -> Extent {
+		let (orig_x, orig_y) = self.c.get_current_point();
+		let (ascent, descent) = (self.get_ascent(), self.get_descent());
+		let orig_path = self.c.copy_path();
+		
+		// Path the contents first, to find how tall the delimiters need to be
+		self.c.new_path();
+		self.exts.push_state();
+		let inner_extents = self.path_expr(inner_expr.clone());
+		let inner_path = self.c.copy_path();
+		let (top, bottom) = (inner_extents.y0.min(-ascent), inner_extents.y1.max(descent));
+		let span = if top < -ascent || bottom > descent { Some((orig_y + top, orig_y + bottom)) } else { None };
+		
+		self.c.new_path();
+		self.c.append_path(&orig_path);
+		self.c.move_to(orig_x, orig_y);
+		let open_w = self.path_stretched(delim.open(), span);
+		
+		let inner_x = (orig_x + open_w).floor();
+		self.exts.translate(inner_x, orig_y);
+		self.c.save();
+		self.c.translate(inner_x, orig_y);
+		self.c.append_path(&inner_path);
+		self.c.restore();
+		
+		let close_x = inner_x + inner_extents.x1 + 1.0;
+		self.c.move_to(close_x, orig_y);
+		let close_w = self.path_stretched(delim.close(), span);
+		let end_x = close_x + close_w;
+		self.c.move_to(end_x, orig_y);
+		
+		{
+			let (l, r) = Extent{x0:orig_x, y0:orig_y+top, x1:inner_x, y1:orig_y+bottom}.split_lr();
+			self.exts.push(l, Cursor::new_ex(expr.clone(), cursor_i));
+			self.exts.push(r, Cursor::new_ex(inner_expr.clone(), 0));
+			let (l, r) = Extent{x0:close_x, y0:orig_y+top, x1:end_x, y1:orig_y+bottom}.split_lr();
+			self.exts.push(l, Cursor::new_ex(inner_expr.clone(), inner_expr.borrow().tokens.len()));
+			self.exts.push(r, Cursor::new_ex(expr.clone(), cursor_i + 1));
+		}
+		
+		Extent{x0:orig_x, y0:orig_y+top, x1:end_x, y1:orig_y+bottom}
+	}
+	
+	/// Paths the character `c` at the current point. If `span` is given, it is stretched vertically to reach from the
+	/// first y coordinate to the second. Returns the width taken up.
+	fn path_stretched(&mut self, c: char, span: Option<(f64, f64)>) -> f64 {
+		let s = c.to_string();
+		let (x, y) = self.c.get_current_point();
+		let ext = self.c.text_extents(&s);
+		self.c.save();
+		match span {
+			Some((y0, y1)) if ext.height > 0.0 => {
+				let sy = (y1 - y0) / ext.height;
+				self.c.translate(x, y0 - ext.y_bearing * sy);
+				self.c.scale(1.0, sy);
+			},
+			_ => self.c.translate(x, y),
+		}
+		self.c.move_to(0.0, 0.0);
+		self.c.text_path(&s);
+		self.c.restore();
+		ext.x_advance + 1.0
+	}
+	
+	/// Paths a reference to a previous result, e.g. `Ans₂` or `#3`, in bold so that it stands out from variables.
+	/// `sub` is pathed as a subscript.
+	fn path_history_ref(&mut self, s: &str, sub: &str) -> Extent {
+		let (start_x, start_y) = self.c.get_current_point();
+		let ascent = self.get_ascent();
+		let descent = self.get_descent();
+		self.c.select_font_face("CMU Serif", FontSlant::Normal, FontWeight::Bold);
+		self.c.text_path(s);
+		self.c.select_font_face("CMU Serif", FontSlant::Normal, FontWeight::Normal);
+		if sub.len() != 0 {
+			let scale = self.get_scale();
+			self.set_scale(scale * 0.6);
+			self.c.rel_move_to(1.0, 6.0 * scale);
+			self.c.text_path(sub);
+			self.c.rel_move_to(0.0, -6.0 * scale);
+			self.set_scale(scale);
+		}
+		self.c.rel_move_to(1.0, 0.0);
+		let (end_x, _) = self.c.get_current_point();
+		Extent {x0:start_x, y0:start_y-ascent, x1:end_x, y1:start_y+descent}
+	}
+	
 	fn path_root(&mut self, inner: VExprRef, degree: Option<VExprRef>) -> Extent {
 		// Get the extents of the new expression
 		self.c.save();
@@ -736,13 +912,17 @@ impl<'a> Render<'a> {
 	}
 }
 
-pub fn render_result(c: &Context, res: Result<d128, ParseError>, alloc_w: f64, alloc_h: f64) {
-	let _ = alloc_w;
-	let s = match res {
-		Ok(num) => format!("= {}", round_dp(num, 15)),
-		Err(ParseError::NoLastResult) => "= ".into(),
-		Err(e)  => format!("error: {}", e),
-	};
+fn result_text(res: &Result<Value, ParseError>) -> String {
+	match res {
+		// Intervals are rounded outwards, so that they still contain the result. Uncertain values round themselves to
+		// the precision of their uncertainty.
+		&Ok(ref val) => format!("= {}", val.clone().map(|num| round_dp(num, 15), |i| i.round_out(15), |u| u)),
+		&Err(ParseError::NoLastResult) => "= ".into(),
+		&Err(ref e)  => format!("error: {}", e),
+	}
+}
+
+fn set_result_font(c: &Context) {
 	c.select_font_face("CMU Serif", FontSlant::Normal, FontWeight::Normal);
 	c.set_font_size(INIT_FONT_SIZE);
 	c.set_antialias(Antialias::Best);
@@ -750,8 +930,13 @@ pub fn render_result(c: &Context, res: Result<d128, ParseError>, alloc_w: f64, a
 	opt.set_antialias(Antialias::Best);
 	opt.set_hint_style(HintStyle::Medium);
 	c.set_font_options(&opt);
-	
-	let ext = path_str(c, &s);
+}
+
+/// Draws the result given. If `faded` is true it is drawn in grey, to show that it is out of date.
+pub fn render_result(c: &Context, res: Result<Value, ParseError>, faded: bool, alloc_w: f64, alloc_h: f64) {
+	let _ = alloc_w;
+	set_result_font(c);
+	let ext = path_str(c, &result_text(&res));
 	let (mut x, mut y) = align(&ext, 15.0, alloc_h / 2.0, MidRight);
 	x = x.floor();
 	y = y.floor();
@@ -759,10 +944,235 @@ pub fn render_result(c: &Context, res: Result<d128, ParseError>, alloc_w: f64, a
 	c.new_path();
 	c.translate(x, y);
 	c.append_path(&path);
-	c.set_source_rgb(0.0, 0.0, 0.0);
+	if faded {
+		c.set_source_rgb(0.6, 0.6, 0.6);
+	} else {
+		c.set_source_rgb(0.0, 0.0, 0.0);
+	}
 	c.fill();
 }
 
+/// Renders every row of the worksheet, one under another, with the result of each on the right.
+pub fn render_sheet(c: &Context, sheet: &mut Worksheet, alloc_w: f64) {
+	let mut y = SHEET_MARGIN;
+	let current = sheet.current;
+	for (i, row) in sheet.rows.iter_mut().enumerate() {
+		let ext = {
+			let mut ren = Render::new(c, &row.editor);
+			ren.show_cursor = i == current;
+			let ext = ren.render_at(SHEET_MARGIN, y);
+			row.editor.update_hitboxes(ren.exts.hitboxes.into_boxed_slice(), ren.exts.errors.into_boxed_slice());
+			ext
+		};
+		row.extent = Some(ext);
+		
+		// Mark the row being edited in the margin
+		c.identity_matrix();
+		c.new_path();
+		if i == current {
+			c.rectangle(2.0, ext.y0(), 3.0, ext.h());
+			c.set_source_rgb(0.6, 0.6, 0.6);
+			c.fill();
+		}
+		
+		match row.result {
+			Err(ParseError::NoLastResult) | Err(ParseError::ExpressionEmpty) => {},
+			ref res => {
+				set_result_font(c);
+				c.move_to(0.0, 0.0);
+				let res_ext = path_str(c, &result_text(res));
+				let (x, y) = align(&res_ext, alloc_w - SHEET_MARGIN, (ext.y0() + ext.y1()) / 2.0, MidLeft);
+				let path = c.copy_path();
+				c.new_path();
+				c.translate(x.floor(), y.floor());
+				c.append_path(&path);
+				c.set_source_rgb(0.3, 0.3, 0.3);
+				c.fill();
+				c.identity_matrix();
+			},
+		}
+		y = ext.y1() + SHEET_ROW_SPACING;
+	}
+}
+
+/// The kinds of image that expressions can be exported as.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+	Png,
+	Svg,
+	Pdf,
+}
+
+impl ImageFormat {
+	/// Gets the format from its name, which is also its file extension, e.g. `png`.
+	pub fn from_name(name: &str) -> Option<ImageFormat> {
+		match &*name.to_lowercase() {
+			"png" => Some(ImageFormat::Png),
+			"svg" => Some(ImageFormat::Svg),
+			"pdf" => Some(ImageFormat::Pdf),
+			_ => None,
+		}
+	}
+	/// Gets the format from the extension of a file name.
+	pub fn from_path(path: &Path) -> Option<ImageFormat> {
+		path.extension().and_then(|ext| ext.to_str()).and_then(ImageFormat::from_name)
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			&ImageFormat::Png => "png",
+			&ImageFormat::Svg => "svg",
+			&ImageFormat::Pdf => "pdf",
+		}
+	}
+}
+
+/// How expressions are drawn when they are exported.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ExportOptions {
+	/// How many times larger than on screen it is drawn.
+	pub scale: f64,
+	/// The space left around it, in pixels for PNG and points for SVG and PDF.
+	pub padding: f64,
+	pub foreground: (f64, f64, f64),
+	/// The colour filled in behind it, or `None` for a transparent background.
+	pub background: Option<(f64, f64, f64)>,
+}
+
+impl ExportOptions {
+	/// Black on white, the size it is on screen.
+	pub fn new() -> ExportOptions {
+		ExportOptions{ scale: 1.0, padding: EXPORT_PADDING, foreground: (0.0, 0.0, 0.0), background: Some((1.0, 1.0, 1.0)) }
+	}
+}
+
+/// Reads a colour written as `#rrggbb`, with each component from 0 to 1. The `#` can be left out.
+pub fn parse_colour(s: &str) -> Option<(f64, f64, f64)> {
+	let hex = s.trim_left_matches('#');
+	if hex.len() != 6 || !hex.chars().all(|c| c.is_digit(16)) {
+		return None;
+	}
+	let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap() as f64 / 255.0;
+	Some((component(0), component(2), component(4)))
+}
+
+fn cairo_error<E: Debug>(e: E) -> io::Error {
+	io::Error::new(io::ErrorKind::Other, format!("cairo error: {:?}", e))
+}
+
+// The function cairo calls to write part of a file to `closure`
+type WriteFunc = unsafe extern "C" fn(closure: *mut c_void, data: *mut c_uchar, length: c_uint) -> Status;
+
+extern "C" {
+	fn cairo_svg_surface_create_for_stream(write_func: Option<WriteFunc>, closure: *mut c_void,
+		width_in_points: c_double, height_in_points: c_double) -> *mut cairo_surface_t;
+	fn cairo_pdf_surface_create_for_stream(write_func: Option<WriteFunc>, closure: *mut c_void,
+		width_in_points: c_double, height_in_points: c_double) -> *mut cairo_surface_t;
+}
+
+/// Appends what cairo writes to the `Vec<u8>` that `closure` points to.
+unsafe extern "C" fn write_to_vec(closure: *mut c_void, data: *mut c_uchar, length: c_uint) -> Status {
+	let bytes = &mut *(closure as *mut Vec<u8>);
+	bytes.extend_from_slice(slice::from_raw_parts(data, length as usize));
+	Status::Success
+}
+
+/// Draws a `w` by `h` vector image with `draw`, returning the SVG or PDF file. cairo has surfaces for these, but
+/// cairo-rs doesn't wrap them.
+fn draw_vector<F>(format: ImageFormat, w: f64, h: f64, draw: F) -> io::Result<Vec<u8>> where F: FnOnce(&Context) {
+	let mut bytes: Vec<u8> = Vec::new();
+	{
+		// cairo holds on to a pointer to `bytes` until the surface is finished, so it mustn't be moved before then
+		let closure = &mut bytes as *mut Vec<u8> as *mut c_void;
+		let surface = unsafe {
+			Surface::from_raw_full(match format {
+				ImageFormat::Svg => cairo_svg_surface_create_for_stream(Some(write_to_vec), closure, w, h),
+				ImageFormat::Pdf => cairo_pdf_surface_create_for_stream(Some(write_to_vec), closure, w, h),
+				ImageFormat::Png => panic!("PNG isn't a vector format"),
+			})
+		};
+		if surface.status() != Status::Success {
+			return Err(cairo_error(surface.status()));
+		}
+		draw(&Context::new(&surface));
+		surface.finish();
+		if surface.status() != Status::Success {
+			return Err(cairo_error(surface.status()));
+		}
+	}
+	Ok(bytes)
+}
+
+/// Draws the expression being edited in `ed`, without the cursor, the selection or any errors marked, and returns it as
+/// a file in `format`. If `result` is given it is drawn after the expression. Also returns the width and height of the
+/// image. The file is made in memory, so nothing is written to the file system.
+pub fn export_expression_bytes(ed: &Editor, result: Option<&Result<Value, ParseError>>, format: ImageFormat,
+		options: &ExportOptions) -> io::Result<((f64, f64), Vec<u8>)> {
+	// Draw it once where it isn't seen to find its size
+	let ext = {
+		let surface = try!(ImageSurface::create(Format::ARgb32, 1, 1).map_err(cairo_error));
+		draw_export(&Context::new(&surface), ed, result, options)
+	};
+	let w = (ext.w() * options.scale + 2.0 * options.padding).ceil();
+	let h = (ext.h() * options.scale + 2.0 * options.padding).ceil();
+	let bytes = match format {
+		ImageFormat::Png => {
+			let surface = try!(ImageSurface::create(Format::ARgb32, w as i32, h as i32).map_err(cairo_error));
+			draw_export(&Context::new(&surface), ed, result, options);
+			let mut bytes = Vec::new();
+			try!(surface.write_to_png(&mut bytes).map_err(cairo_error));
+			bytes
+		},
+		ImageFormat::Svg | ImageFormat::Pdf => try!(draw_vector(format, w, h, |c| { draw_export(c, ed, result, options); })),
+	};
+	Ok(((w, h), bytes))
+}
+
+/// Writes the image made by `export_expression_bytes` to `path`, returning its width and height.
+pub fn export_expression(ed: &Editor, result: Option<&Result<Value, ParseError>>, format: ImageFormat, options: &ExportOptions,
+		path: &Path) -> io::Result<(f64, f64)> {
+	let (size, bytes) = try!(export_expression_bytes(ed, result, format, options));
+	try!(try!(File::create(path)).write_all(&bytes));
+	Ok(size)
+}
+
+/// Draws an exported expression, returning the extent of it and its result in the scaled coordinates.
+fn draw_export(c: &Context, ed: &Editor, result: Option<&Result<Value, ParseError>>, options: &ExportOptions) -> Extent {
+	if let Some((r, g, b)) = options.background {
+		c.set_source_rgb(r, g, b);
+		c.paint();
+	}
+	c.scale(options.scale, options.scale);
+	let padding = options.padding / options.scale;
+	let ext = {
+		let mut ren = Render::new(c, ed);
+		ren.show_cursor = false;
+		ren.selection = None;
+		ren.diagnostics = Vec::new();
+		ren.foreground = options.foreground;
+		ren.render_at(padding, padding)
+	};
+	match result {
+		None | Some(&Err(ParseError::NoLastResult)) | Some(&Err(ParseError::ExpressionEmpty)) => ext,
+		Some(res) => {
+			set_result_font(c);
+			c.new_path();
+			c.move_to(0.0, 0.0);
+			let res_ext = path_str(c, &result_text(res));
+			// Level with the middle of the expression, as in the worksheet
+			let (x, y) = align(&res_ext, ext.x1 + EXPORT_RESULT_SPACING, (ext.y0 + ext.y1) / 2.0, MidRight);
+			let (x, y) = (x.floor(), y.floor());
+			let path = c.copy_path();
+			c.new_path();
+			c.translate(x, y);
+			c.append_path(&path);
+			let (r, g, b) = options.foreground;
+			c.set_source_rgb(r, g, b);
+			c.fill();
+			ext.enclosing(&res_ext.translate(x, y))
+		},
+	}
+}
+
 pub fn path_str(c: &Context, s: &str) -> Extent {
 	if !c.has_current_point() {
 		c.move_to(0.0, 0.0);
@@ -0,0 +1,564 @@
+/// Saving and loading of workspaces, and of the calculation history, as JSON files.
+///
+/// Expressions are saved as arrays of tokens, where digits and letters are strings of one character, and every other
+/// token is an object, e.g. `{"frac": [["1"], ["2"]]}`. Numbers are saved as strings, so that no precision is lost.
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use json::{self, Json};
+use num::*;
+use val::Value;
+use interval::Interval;
+use uncertain::Uncertain;
+use vis::*;
+use func::FuncType;
+use com::{VM, VarName, EvalMode, HistoryEntry, TrigMode};
+use sheet::Worksheet;
+use parse::parse_text;
+
+/// The version of the file format written. Files from later versions are refused.
+///
+/// Version 2 saves every row of the worksheet, where version 1 had a single expression. Version 3 saves brackets as
+/// tokens around an expression, where earlier versions had separate `(` and `)` characters. Version 4 saves the number
+/// of calculations dropped from the start of the history as `history_start`, so that `#n` refers to the same result
+/// after loading.
+pub const FORMAT_VERSION: usize = 4;
+
+/// The most results kept in the history file.
+const HISTORY_LIMIT: usize = 200;
+
+#[derive(Debug)]
+pub enum LoadError {
+	Io(String),
+	Syntax(json::JsonError),
+	Invalid(String, String), // (where in the file, e.g. `history[2].result`, what is wrong)
+	Version(usize),
+}
+impl Display for LoadError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			&LoadError::Io(ref e) => write!(f, "{}", e),
+			&LoadError::Syntax(ref e) => write!(f, "syntax error at {}", e),
+			&LoadError::Invalid(ref at, ref msg) => write!(f, "invalid {}: {}", at, msg),
+			&LoadError::Version(v) => write!(f, "the file is from a newer version of Equator (format version {})", v),
+		}
+	}
+}
+
+pub fn invalid<T>(at: &str, msg: &str) -> Result<T, LoadError> {
+	Err(LoadError::Invalid(at.to_string(), msg.to_string()))
+}
+
+/// Gets the member `key` of the object `j`, which is at `at`.
+pub fn member<'a>(j: &'a Json, key: &str, at: &str) -> Result<&'a Json, LoadError> {
+	match j {
+		&Json::Object(_) => match j.get(key) {
+			Some(v) => Ok(v),
+			None => invalid(at, &format!("missing \"{}\"", key)),
+		},
+		_ => invalid(at, &format!("expected an object, found {}", j.kind())),
+	}
+}
+
+fn array<'a>(j: &'a Json, at: &str) -> Result<&'a [Json], LoadError> {
+	match j.as_array() {
+		Some(vs) => Ok(vs),
+		None => invalid(at, &format!("expected an array, found {}", j.kind())),
+	}
+}
+
+fn index(j: &Json, at: &str) -> Result<usize, LoadError> {
+	match j.as_usize() {
+		Some(n) => Ok(n),
+		None => invalid(at, "expected a whole number, no more than 2^53"),
+	}
+}
+
+fn decimal(j: &Json, at: &str) -> Result<d128, LoadError> {
+	let s = match j.as_str() {
+		Some(s) => s,
+		None => return invalid(at, &format!("expected a number in a string, found {}", j.kind())),
+	};
+	match d128::from_str(s) {
+		// Invalid strings are parsed as NaN
+		Ok(v) if !v.is_nan() || s.to_lowercase().contains("nan") => Ok(v),
+		_ => invalid(at, &format!("'{}' is not a number", s)),
+	}
+}
+
+pub fn value_to_json(v: &Value) -> Json {
+	match v {
+		&Value::Num(n) => Json::Str(n.to_string()),
+		&Value::Interval(i) => Json::Object(vec![
+			("lo".into(), Json::Str(i.lo.to_string())),
+			("hi".into(), Json::Str(i.hi.to_string())),
+		]),
+		&Value::Uncertain(ref u) => Json::Object(vec![
+			("val".into(), Json::Str(u.val.to_string())),
+			("terms".into(), Json::Array(u.terms.iter().map(|&(source, c)| {
+				Json::Array(vec![Json::Num(source as f64), Json::Str(c.to_string())])
+			}).collect())),
+		]),
+		&Value::List(ref vs) => Json::Array(vs.iter().map(value_to_json).collect()),
+	}
+}
+
+pub fn value_from_json(j: &Json, at: &str) -> Result<Value, LoadError> {
+	match j {
+		&Json::Str(_) => decimal(j, at).map(Value::Num),
+		&Json::Array(ref vs) => {
+			let mut ret = Vec::with_capacity(vs.len());
+			for (i, v) in vs.iter().enumerate() {
+				let at = format!("{}[{}]", at, i);
+				match try!(value_from_json(v, &at)) {
+					Value::List(_) => return invalid(&at, "lists can't contain lists"),
+					v => ret.push(v),
+				}
+			}
+			Ok(Value::List(ret))
+		},
+		&Json::Object(_) if j.get("lo").is_some() => {
+			let lo = try!(decimal(try!(member(j, "lo", at)), &format!("{}.lo", at)));
+			let hi = try!(decimal(try!(member(j, "hi", at)), &format!("{}.hi", at)));
+			if lo > hi {
+				return invalid(at, "the interval's lower bound is above its upper bound");
+			}
+			Ok(Value::Interval(Interval::new(lo, hi)))
+		},
+		&Json::Object(_) if j.get("val").is_some() => {
+			let val = try!(decimal(try!(member(j, "val", at)), &format!("{}.val", at)));
+			let mut terms: Vec<(usize, d128)> = Vec::new();
+			let terms_at = format!("{}.terms", at);
+			for (i, t) in try!(array(try!(member(j, "terms", at)), &terms_at)).iter().enumerate() {
+				let at = format!("{}[{}]", terms_at, i);
+				let pair = try!(array(t, &at));
+				if pair.len() != 2 {
+					return invalid(&at, "expected [source, coefficient]");
+				}
+				let source = try!(index(&pair[0], &format!("{}[0]", at)));
+				let c = try!(decimal(&pair[1], &format!("{}[1]", at)));
+				if terms.last().map_or(false, |&(s, _)| s >= source) {
+					return invalid(&at, "sources must be in increasing order");
+				}
+				terms.push((source, c));
+			}
+			Ok(Value::Uncertain(Uncertain{ val: val, terms: terms }))
+		},
+		_ => invalid(at, &format!("expected a value, found {}", j.kind())),
+	}
+}
+
+fn op_char(op: OpType) -> char {
+	match op {
+		OpType::Add => '+',
+		OpType::Sub => '-',
+		OpType::Mul => '*',
+		OpType::Div => '/',
+		OpType::PlusMinus => '±',
+	}
+}
+
+pub fn tokens_to_json(tokens: &[VToken]) -> Json {
+	Json::Array(tokens.iter().map(|tok| match tok {
+		&VToken::Space => Json::Str(" ".into()),
+		&VToken::Char(c) | &VToken::Digit(c) => Json::Str(c.to_string()),
+		&VToken::Op(op) => Json::Object(vec![("op".into(), Json::Str(op_char(op).to_string()))]),
+		&VToken::Ans(n) => Json::Object(vec![("ans".into(), Json::Num(n as f64))]),
+		&VToken::Hist(n) => Json::Object(vec![("hist".into(), Json::Num(n as f64))]),
+		&VToken::Pow(ref ex) => Json::Object(vec![("pow".into(), vexpr_to_json(ex))]),
+		&VToken::Sub(ref ex) => Json::Object(vec![("sub".into(), vexpr_to_json(ex))]),
+		&VToken::Frac(ref num, ref den) => Json::Object(vec![("frac".into(), Json::Array(vec![vexpr_to_json(num), vexpr_to_json(den)]))]),
+		&VToken::Root(ref degree, ref ex) => Json::Object(vec![("root".into(), Json::Array(vec![vexpr_to_json(degree), vexpr_to_json(ex)]))]),
+		&VToken::Func(func, ref ex) => Json::Object(vec![
+			("func".into(), Json::Str(if func == FuncType::Sqrt { "sqrt".into() } else { func.to_string() })),
+			("arg".into(), vexpr_to_json(ex)),
+		]),
+		&VToken::List(ref ex) => Json::Object(vec![("list".into(), vexpr_to_json(ex))]),
+		&VToken::Delim(delim, ref ex) => Json::Object(vec![
+			("delim".into(), Json::Str(format!("{}{}", delim.open(), delim.close()))),
+			("arg".into(), vexpr_to_json(ex)),
+		]),
+	}).collect())
+}
+
+pub fn vexpr_to_json(ex: &VExprRef) -> Json {
+	tokens_to_json(&ex.borrow().tokens)
+}
+
+/// Reads an expression, giving it the parent `parent`.
+pub fn vexpr_from_json(j: &Json, parent: Option<&VExprRef>, at: &str) -> Result<VExprRef, LoadError> {
+	let ex = match parent {
+		Some(p) => VExpr::with_parent(p.clone()).to_ref(),
+		None => VExpr::new_ref(),
+	};
+	for (i, t) in try!(array(j, at)).iter().enumerate() {
+		let tok = try!(token_from_json(t, &ex, &format!("{}[{}]", at, i)));
+		ex.borrow_mut().tokens.push(tok);
+	}
+	if parent.is_none() && has_bracket_chars(&ex) {
+		// Brackets used to be separate characters, so the expression is read again as text to pair them up
+		return match parse_text(&vexpr_to_text(&ex)) {
+			Ok(ex) => Ok(ex),
+			Err(_) => invalid(at, "unmatched bracket"),
+		};
+	}
+	Ok(ex)
+}
+
+/// Returns true if there are `(` or `)` characters anywhere in `ex`, as written by versions before 3.
+fn has_bracket_chars(ex: &VExprRef) -> bool {
+	ex.borrow().tokens.iter().any(|tok| match tok {
+		&VToken::Char('(') | &VToken::Char(')') => true,
+		tok => tok.get_inner_expr().iter().any(has_bracket_chars),
+	})
+}
+
+/// Reads a pair of expressions, e.g. the numerator and denominator of a fraction.
+fn vexpr_pair_from_json(j: &Json, parent: &VExprRef, at: &str) -> Result<(VExprRef, VExprRef), LoadError> {
+	let pair = try!(array(j, at));
+	if pair.len() != 2 {
+		return invalid(at, "expected two expressions");
+	}
+	Ok((try!(vexpr_from_json(&pair[0], Some(parent), &format!("{}[0]", at))),
+		try!(vexpr_from_json(&pair[1], Some(parent), &format!("{}[1]", at)))))
+}
+
+fn token_from_json(j: &Json, parent: &VExprRef, at: &str) -> Result<VToken, LoadError> {
+	if let Some(s) = j.as_str() {
+		let mut chars = s.chars();
+		return match (chars.next(), chars.next()) {
+			(Some(' '), None) => Ok(VToken::Space),
+			(Some(c), None) if c.is_digit(10) => Ok(VToken::Digit(c)),
+			(Some(c), None) => Ok(VToken::Char(c)),
+			_ => invalid(at, "expected a single character"),
+		};
+	}
+	let key = match j {
+		&Json::Object(ref members) if members.len() != 0 => members[0].0.clone(),
+		_ => return invalid(at, &format!("expected a token, found {}", j.kind())),
+	};
+	let inner_at = format!("{}.{}", at, key);
+	let inner = try!(member(j, &key, at));
+	match key.as_str() {
+		"op" => match inner.as_str() {
+			Some("+") => Ok(VToken::Op(OpType::Add)),
+			Some("-") => Ok(VToken::Op(OpType::Sub)),
+			Some("*") => Ok(VToken::Op(OpType::Mul)),
+			Some("/") => Ok(VToken::Op(OpType::Div)),
+			Some("±") => Ok(VToken::Op(OpType::PlusMinus)),
+			_ => invalid(&inner_at, "expected one of \"+\", \"-\", \"*\", \"/\" or \"±\""),
+		},
+		"ans" => index(inner, &inner_at).map(VToken::Ans),
+		"hist" => index(inner, &inner_at).map(VToken::Hist),
+		"pow" => vexpr_from_json(inner, Some(parent), &inner_at).map(VToken::Pow),
+		"sub" => vexpr_from_json(inner, Some(parent), &inner_at).map(VToken::Sub),
+		"list" => vexpr_from_json(inner, Some(parent), &inner_at).map(VToken::List),
+		"frac" => vexpr_pair_from_json(inner, parent, &inner_at).map(|(num, den)| VToken::Frac(num, den)),
+		"root" => vexpr_pair_from_json(inner, parent, &inner_at).map(|(degree, ex)| VToken::Root(degree, ex)),
+		"func" => {
+			let func = match inner.as_str().and_then(FuncType::from_name) {
+				Some(func) => func,
+				None => return invalid(&inner_at, "unknown function"),
+			};
+			let arg = try!(vexpr_from_json(try!(member(j, "arg", at)), Some(parent), &format!("{}.arg", at)));
+			Ok(VToken::Func(func, arg))
+		},
+		"delim" => {
+			let mut chars = inner.as_str().unwrap_or("").chars();
+			let delim = match (chars.next().and_then(DelimType::from_open), chars.next(), chars.next()) {
+				(Some(delim), Some(close), None) if close == delim.close() => delim,
+				_ => return invalid(&inner_at, "expected a pair of delimiters, such as \"()\""),
+			};
+			let arg = try!(vexpr_from_json(try!(member(j, "arg", at)), Some(parent), &format!("{}.arg", at)));
+			Ok(VToken::Delim(delim, arg))
+		},
+		_ => invalid(at, &format!("unknown token \"{}\"", key)),
+	}
+}
+
+/// Reads the number of calculations dropped from the start of the history in the file `j`, which was written by
+/// `version`. Earlier versions didn't drop any.
+fn history_start_from_json(j: &Json, version: usize) -> Result<usize, LoadError> {
+	if version < 4 {
+		Ok(0)
+	} else {
+		index(try!(member(j, "history_start", "file")), "history_start")
+	}
+}
+
+pub fn history_to_json(history: &[HistoryEntry]) -> Json {
+	Json::Array(history.iter().map(|entry| Json::Object(vec![
+		("expr".into(), vexpr_to_json(&entry.expr)),
+		("result".into(), value_to_json(&entry.result)),
+	])).collect())
+}
+
+pub fn history_from_json(j: &Json, at: &str) -> Result<Vec<HistoryEntry>, LoadError> {
+	let mut history = Vec::new();
+	for (i, e) in try!(array(j, at)).iter().enumerate() {
+		let at = format!("{}[{}]", at, i);
+		let expr = try!(vexpr_from_json(try!(member(e, "expr", &at)), None, &format!("{}.expr", at)));
+		let result = try!(value_from_json(try!(member(e, "result", &at)), &format!("{}.result", at)));
+		history.push(HistoryEntry{ expr: expr, result: result });
+	}
+	Ok(history)
+}
+
+/// Reads the JSON file at `path`, checking that its format version can be read.
+pub fn read_file(path: &Path) -> Result<Json, LoadError> {
+	let mut s = String::new();
+	match File::open(path).and_then(|mut f| f.read_to_string(&mut s)) {
+		Ok(_) => {},
+		Err(e) => return Err(LoadError::Io(format!("could not read '{}': {}", path.display(), e))),
+	}
+	let j = match json::parse(&s) {
+		Ok(j) => j,
+		Err(e) => return Err(LoadError::Syntax(e)),
+	};
+	let version = try!(index(try!(member(&j, "version", "file")), "version"));
+	if version > FORMAT_VERSION {
+		return Err(LoadError::Version(version));
+	}
+	Ok(j)
+}
+
+/// Writes `j` to `path`, replacing the file only once it has been written completely.
+pub fn write_file(path: &Path, j: &Json) -> io::Result<()> {
+	let tmp = path.with_extension("tmp");
+	{
+		let mut f = try!(File::create(&tmp));
+		try!(write!(f, "{}\n", j));
+	}
+	fs::rename(&tmp, path)
+}
+
+/// Everything that is saved in a workspace file.
+pub struct Workspace {
+	pub rows: Vec<VExprRef>,
+	pub vars: Vec<(VarName, Value)>,
+	pub trig_mode: TrigMode,
+	pub eval_mode: EvalMode,
+	pub history: Vec<HistoryEntry>,
+	pub history_start: usize, // The number of calculations dropped from the start of the history
+}
+
+impl Workspace {
+	/// Gets the workspace that is open: the rows of the worksheet, and the state of the VM.
+	pub fn current(sheet: &Worksheet, vm: &VM) -> Workspace {
+		Workspace {
+			rows: sheet.rows.iter().map(|row| copy_vexpr(&row.editor.root_ex, None)).collect(),
+			vars: vm.get_vars(),
+			trig_mode: vm.get_trig_mode(),
+			eval_mode: vm.get_mode(),
+			history: vm.get_history().to_vec(),
+			history_start: vm.history_start(),
+		}
+	}
+
+	/// Makes a VM with the variables, modes and history of the workspace.
+	pub fn to_vm(&self) -> VM {
+		let mut vm = VM::new();
+		vm.set_mode(self.eval_mode);
+		vm.set_trig_mode(self.trig_mode);
+		for &(id, ref v) in self.vars.iter() {
+			vm.set_var(id, v.clone());
+		}
+		vm.set_history(self.history_start, self.history.clone());
+		vm
+	}
+}
+
+pub fn workspace_to_json(ws: &Workspace) -> Json {
+	Json::Object(vec![
+		("version".into(), Json::Num(FORMAT_VERSION as f64)),
+		("rows".into(), Json::Array(ws.rows.iter().map(vexpr_to_json).collect())),
+		("trig_mode".into(), Json::Str(match ws.trig_mode {
+			TrigMode::Radians  => "radians",
+			TrigMode::Degrees  => "degrees",
+			TrigMode::Gradians => "gradians",
+		}.into())),
+		("intervals".into(), Json::Bool(ws.eval_mode == EvalMode::Interval)),
+		("vars".into(), Json::Object(ws.vars.iter().map(|&(id, ref v)| (id.to_string(), value_to_json(v))).collect())),
+		("history".into(), history_to_json(&ws.history)),
+		("history_start".into(), Json::Num(ws.history_start as f64)),
+	])
+}
+
+pub fn workspace_from_json(j: &Json) -> Result<Workspace, LoadError> {
+	let version = try!(index(try!(member(j, "version", "file")), "version"));
+	let rows = if version < 2 {
+		vec![try!(vexpr_from_json(try!(member(j, "expr", "file")), None, "expr"))]
+	} else {
+		let mut rows = Vec::new();
+		for (i, row) in try!(array(try!(member(j, "rows", "file")), "rows")).iter().enumerate() {
+			rows.push(try!(vexpr_from_json(row, None, &format!("rows[{}]", i))));
+		}
+		if rows.is_empty() {
+			return invalid("rows", "there must be at least one row");
+		}
+		rows
+	};
+	let trig_mode = match try!(member(j, "trig_mode", "file")).as_str() {
+		Some("radians")  => TrigMode::Radians,
+		Some("degrees")  => TrigMode::Degrees,
+		Some("gradians") => TrigMode::Gradians,
+		_ => return invalid("trig_mode", "expected \"radians\", \"degrees\" or \"gradians\""),
+	};
+	let eval_mode = match try!(member(j, "intervals", "file")).as_bool() {
+		Some(true) => EvalMode::Interval,
+		Some(false) => EvalMode::Normal,
+		None => return invalid("intervals", "expected true or false"),
+	};
+	let mut vars = Vec::new();
+	match try!(member(j, "vars", "file")) {
+		&Json::Object(ref members) => for &(ref k, ref v) in members.iter() {
+			let at = format!("vars.{}", k);
+			let id = match VarName::from_text(k) {
+				Some(id) => id,
+				None => return invalid(&at, "variable names must be a letter, optionally followed by subscript digits"),
+			};
+			if id.is_constant() {
+				return invalid(&at, "constants can't be assigned to");
+			}
+			vars.push((id, try!(value_from_json(v, &at))));
+		},
+		v => return invalid("vars", &format!("expected an object, found {}", v.kind())),
+	}
+	let history = try!(history_from_json(try!(member(j, "history", "file")), "history"));
+	let history_start = try!(history_start_from_json(j, version));
+	Ok(Workspace{ rows: rows, vars: vars, trig_mode: trig_mode, eval_mode: eval_mode, history: history,
+		history_start: history_start })
+}
+
+pub fn save_workspace(path: &Path, ws: &Workspace) -> io::Result<()> {
+	write_file(path, &workspace_to_json(ws))
+}
+
+pub fn load_workspace(path: &Path) -> Result<Workspace, LoadError> {
+	workspace_from_json(&try!(read_file(path)))
+}
+
+/// The directory that Equator keeps its own files in.
+pub fn data_dir() -> Option<PathBuf> {
+	let base = if cfg!(windows) {
+		env::var_os("APPDATA").map(PathBuf::from)
+	} else {
+		env::var_os("XDG_DATA_HOME").map(PathBuf::from).or(env::var_os("HOME").map(|h| Path::new(&h).join(".local").join("share")))
+	};
+	base.map(|b| b.join("equator"))
+}
+
+fn history_path() -> Option<PathBuf> {
+	data_dir().map(|d| d.join("history.json"))
+}
+
+/// Saves the VM's history, so that it can be loaded when Equator is next started.
+pub fn save_history(vm: &VM) -> io::Result<()> {
+	let path = match history_path() {
+		Some(p) => p,
+		None => return Ok(()),
+	};
+	if let Some(dir) = path.parent() {
+		try!(fs::create_dir_all(dir));
+	}
+	// Only the most recent calculations are kept, but they keep their numbers
+	let history = vm.get_history();
+	let start = history.len().saturating_sub(HISTORY_LIMIT);
+	write_file(&path, &Json::Object(vec![
+		("version".into(), Json::Num(FORMAT_VERSION as f64)),
+		("history".into(), history_to_json(&history[start..])),
+		("history_start".into(), Json::Num((vm.history_start() + start) as f64)),
+	]))
+}
+
+/// Loads the history saved by `save_history` into the VM, replacing its history. It is not an error for there to be no
+/// history saved.
+pub fn load_history(vm: &mut VM) -> Result<(), LoadError> {
+	let path = match history_path() {
+		Some(ref p) if p.exists() => p.clone(),
+		_ => return Ok(()),
+	};
+	let j = try!(read_file(&path));
+	let history = try!(history_from_json(try!(member(&j, "history", "file")), "history"));
+	let version = try!(index(try!(member(&j, "version", "file")), "version"));
+	vm.set_history(try!(history_start_from_json(&j, version)), history);
+	Ok(())
+}
+
+#[test]
+fn workspace_test() {
+	use session::Session;
+	let mut session = Session::new();
+	session.ctx.vm.set_trig_mode(TrigMode::Degrees);
+	for text in ["a=3.5", "b₂=√(a)+#1/2", "{1, 2.5, a}", "x=1.5±0.25", "sin(30)+x^0.5"].iter() {
+		assert!(session.evaluate_text(text).is_ok(), "couldn't evaluate '{}'", text);
+	}
+	session.ctx.vm.set_mode(EvalMode::Interval);
+	assert!(session.evaluate_text("0.1+a").is_ok());
+	let ws = Workspace::current(&session.sheet, &session.ctx.vm);
+
+	// Saving the workspace and loading it again gives the same workspace
+	let text = workspace_to_json(&ws).to_string();
+	let loaded = workspace_from_json(&json::parse(&text).unwrap()).unwrap();
+	assert_eq!(loaded.rows.len(), ws.rows.len());
+	for (a, b) in loaded.rows.iter().zip(ws.rows.iter()) {
+		assert!(vexpr_eq(a, b), "'{}' was loaded as '{}'", vexpr_to_text(b), vexpr_to_text(a));
+	}
+	assert_eq!(loaded.vars, ws.vars);
+	assert_eq!(loaded.trig_mode, TrigMode::Degrees);
+	assert_eq!(loaded.eval_mode, EvalMode::Interval);
+	assert_eq!(loaded.history.len(), ws.history.len());
+	for (a, b) in loaded.history.iter().zip(ws.history.iter()) {
+		assert!(vexpr_eq(&a.expr, &b.expr));
+		assert_eq!(a.result, b.result);
+	}
+}
+
+#[test]
+fn sources_test() {
+	// A variable whose source of uncertainty isn't in the history is independent of values made after loading it
+	let x = VarName::new('x');
+	let ws = Workspace{ rows: Vec::new(), vars: vec![(x, Value::Uncertain(Uncertain::new(d128!(5), d128!(0.3), 0)))],
+		trig_mode: TrigMode::Radians, eval_mode: EvalMode::Normal, history: Vec::new(), history_start: 0 };
+	let mut vm = ws.to_vm();
+	match vm.evaluate(&parse_text("x-(5±0.4)").unwrap()) {
+		Ok(Value::Uncertain(u)) => assert_eq!(u.uncertainty(), d128!(0.5)),
+		res => panic!("x-(5±0.4) gave {:?}", res),
+	}
+
+	// Sources too large to be read exactly are rejected
+	let j = json::parse(r#"{"val": "1", "terms": [[1e300, "0.1"]]}"#).unwrap();
+	match value_from_json(&j, "x") {
+		Err(LoadError::Invalid(at, _)) => assert_eq!(at, "x.terms[0][0]"),
+		res => panic!("a source of 1e300 was loaded as {:?}", res),
+	}
+	let j = json::parse(r#"{"val": "1", "terms": [[9007199254740992, "0.1"]]}"#).unwrap();
+	assert!(value_from_json(&j, "x").is_ok());
+}
+
+#[test]
+fn history_start_test() {
+	use session::Session;
+	let mut session = Session::new();
+	for text in ["1", "2", "3", "4"].iter() {
+		assert!(session.evaluate_text(text).is_ok());
+	}
+	session.ctx.vm.trim_history(2);
+	assert_eq!(session.ctx.vm.get_hist(2), None);
+	assert_eq!(session.ctx.vm.get_hist(3), Some(Value::Num(d128!(3))));
+	assert_eq!(session.evaluate_text("#3+#4").ok(), Some(Value::Num(d128!(7))));
+	assert!(session.evaluate_text("#1").is_err());
+
+	// The calculations kept have the same numbers after loading
+	let ws = Workspace::current(&session.sheet, &session.ctx.vm);
+	let text = workspace_to_json(&ws).to_string();
+	let vm = workspace_from_json(&json::parse(&text).unwrap()).unwrap().to_vm();
+	assert_eq!(vm.history_start(), 2);
+	assert_eq!(vm.get_hist(2), None);
+	assert_eq!(vm.get_hist(5), Some(Value::Num(d128!(7))));
+	assert_eq!(vm.get_hist_entry(4).map(|e| vexpr_to_text(&e.expr)), Some("4".to_string()));
+}
@@ -0,0 +1,134 @@
+/// Sessions, which hold everything that evaluating and editing expressions needs, so that Equator can be used without
+/// its window, e.g. by another program.
+///
+/// The editor tells the program showing it about changes through a `Frontend`, rather than calling the gui directly.
+use com::VM;
+use edit::Editor;
+use sheet::Worksheet;
+use val::Value;
+use err::ParseError;
+use parse::parse_text;
+use vis::{VExprRef, copy_vexpr};
+
+/// The most calculations kept in the history by `evaluate_in_place`.
+pub const IN_PLACE_HISTORY_LIMIT: usize = 1000;
+
+/// What the program showing the editor does when it changes. Every method does nothing by default.
+pub trait Frontend {
+	/// The expression has changed, so it needs checking for errors and drawing again.
+	fn dirty_expression(&mut self) {}
+	/// Only the cursor or the selection has changed.
+	fn dirty_view(&mut self) {}
+	/// Gets the text on the clipboard, if there is any.
+	fn clipboard_text(&mut self) -> Option<String> {
+		None
+	}
+	fn set_clipboard_text(&mut self, _text: &str) {}
+	/// Returns true if the next variable typed should have the last result stored in it, instead of being inserted.
+	fn storing(&self) -> bool {
+		false
+	}
+	/// A variable has been stored to.
+	fn stop_storing(&mut self) {}
+}
+
+/// A front end for when nothing is shown, with a clipboard of its own.
+pub struct Headless {
+	clipboard: Option<String>,
+}
+
+impl Headless {
+	pub fn new() -> Headless {
+		Headless{ clipboard: None }
+	}
+}
+
+impl Frontend for Headless {
+	fn clipboard_text(&mut self) -> Option<String> {
+		self.clipboard.clone()
+	}
+	fn set_clipboard_text(&mut self, text: &str) {
+		self.clipboard = Some(text.to_string());
+	}
+}
+
+/// What expressions are evaluated and edited in: the VM, which has the variables, history and modes, and the front end.
+pub struct Context {
+	pub vm: VM,
+	pub frontend: Box<Frontend>,
+}
+
+impl Context {
+	pub fn new(frontend: Box<Frontend>) -> Context {
+		Context{ vm: VM::new(), frontend: frontend }
+	}
+}
+
+/// A worksheet and the context it is evaluated in.
+pub struct Session {
+	pub ctx: Context,
+	pub sheet: Worksheet,
+}
+
+impl Session {
+	/// Makes a session with an empty worksheet, which isn't shown anywhere.
+	pub fn new() -> Session {
+		Session::with_frontend(box Headless::new())
+	}
+
+	pub fn with_frontend(frontend: Box<Frontend>) -> Session {
+		Session{ ctx: Context::new(frontend), sheet: Worksheet::new() }
+	}
+
+	/// The editor of the row being edited.
+	pub fn editor(&mut self) -> &mut Editor {
+		self.sheet.editor()
+	}
+
+	/// Evaluates the row being edited, and the rows below it that use a variable it assigns to, as pressing Return
+	/// does. The result becomes the last result, and if it is valid it is added to the history.
+	pub fn evaluate(&mut self) -> Result<Value, ParseError> {
+		self.editor().update_errors();
+		self.ctx.vm.clear_stack();
+		let row = self.sheet.current;
+		self.sheet.evaluate(&mut self.ctx.vm, row);
+		let res = self.sheet.rows[row].result.clone();
+		self.ctx.vm.set_last_result(res.clone());
+		if let Ok(ref v) = res {
+			let ex = copy_vexpr(&self.sheet.rows[row].editor.root_ex, None);
+			self.ctx.vm.push_history(ex, v.clone());
+		}
+		res
+	}
+
+	/// Puts `text` in a new row below the row being edited, or in that row if it is empty, then evaluates it. The rows
+	/// evaluated after it can use the variable it assigns to, if it is an assignment such as `a=2`.
+	pub fn evaluate_text(&mut self, text: &str) -> Result<Value, ParseError> {
+		let ex = try!(parse_text(text));
+		let empty = self.editor().root_ex.borrow().tokens.is_empty();
+		if !empty {
+			self.sheet.insert_row(&mut self.ctx.vm);
+		}
+		self.editor().set_expression(&ex);
+		self.evaluate()
+	}
+
+	/// Puts `text` in the row being edited, replacing what was there, then evaluates it. Unlike `evaluate_text` the
+	/// worksheet doesn't grow, however many expressions are evaluated, so this suits programs that evaluate expressions
+	/// for as long as they run. Variables assigned to are kept by the VM, and results by the history.
+	///
+	/// So that the history doesn't grow either, only the last `IN_PLACE_HISTORY_LIMIT` results are kept in it. `#n`
+	/// still counts from the first result, so it is an error once the nth result has been dropped.
+	pub fn evaluate_in_place(&mut self, text: &str) -> Result<Value, ParseError> {
+		let ex = try!(parse_text(text));
+		self.evaluate_expression_in_place(&ex)
+	}
+
+	/// As `evaluate_in_place`, for an expression that has already been parsed or built.
+	pub fn evaluate_expression_in_place(&mut self, ex: &VExprRef) -> Result<Value, ParseError> {
+		self.editor().set_expression(ex);
+		let res = self.evaluate();
+		self.ctx.vm.trim_history(IN_PLACE_HISTORY_LIMIT);
+		res
+	}
+}
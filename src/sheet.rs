@@ -0,0 +1,422 @@
+/// A worksheet of expressions, one per row, each with its own editor.
+///
+/// Rows are evaluated from top to bottom, and a row can assign to a variable (e.g. `a=2x`) for the rows below it to use.
+/// When a row is evaluated again, the rows below that depend on it are too.
+use edit::{Editor, Cursor};
+use vis::*;
+use val::Value;
+use err::*;
+use com::{self, VM, VarName, EvalJob};
+use extent::Extent;
+use action::{Action, Motion};
+use diag::Fix;
+use parse::parse_text;
+use session::Context;
+
+pub struct Row {
+	pub editor: Editor,
+	/// The result of the row when it was last evaluated.
+	pub result: Result<Value, ParseError>,
+	/// Where the row was last drawn, when there is more than one row.
+	pub extent: Option<Extent>,
+	evaluated: Option<VExprRef>, // A copy of the expression when the row was last evaluated
+	assigns: Option<VarName>,
+	reads: Vec<VarName>,
+}
+
+impl Row {
+	pub fn new(editor: Editor) -> Row {
+		Row{ editor: editor, result: Err(NoLastResult), extent: None, evaluated: None, assigns: None, reads: Vec::new() }
+	}
+
+	/// Returns true if the row has been changed since it was last evaluated.
+	fn is_changed(&self) -> bool {
+		match self.evaluated {
+			Some(ref ex) => !vexpr_eq(ex, &self.editor.root_ex),
+			None => true,
+		}
+	}
+}
+
+pub struct Worksheet {
+	pub rows: Vec<Row>,
+	/// The row being edited.
+	pub current: usize,
+}
+
+impl Worksheet {
+	pub fn new() -> Worksheet {
+		Worksheet::with_rows(vec![VExpr::new_ref()])
+	}
+
+	/// Makes a worksheet with a row for each of the expressions given, with the cursor at the end of the last.
+	pub fn with_rows(exprs: Vec<VExprRef>) -> Worksheet {
+		let rows: Vec<Row> = exprs.into_iter().map(|ex| {
+			let pos = ex.borrow().tokens.len();
+			let mut editor = Editor::with_expression(ex, pos);
+			editor.update_errors();
+			Row::new(editor)
+		}).collect();
+		let current = rows.len() - 1;
+		Worksheet{ rows: rows, current: current }
+	}
+
+	/// The editor of the row being edited.
+	pub fn editor(&mut self) -> &mut Editor {
+		&mut self.rows[self.current].editor
+	}
+
+	/// Evaluates row `changed`, then every row below it that depends on a variable whose value may have changed as a
+	/// result.
+	pub fn evaluate(&mut self, vm: &mut VM, changed: usize) {
+		self.evaluate_rows(vm, changed, false);
+	}
+
+	/// Evaluates every row.
+	pub fn evaluate_all(&mut self, vm: &mut VM) {
+		self.evaluate_rows(vm, 0, true);
+	}
+
+	fn evaluate_rows(&mut self, vm: &mut VM, start: usize, all: bool) {
+		let mut dirty: Vec<VarName> = Vec::new(); // Variables whose values may have changed
+		let mut read: Vec<VarName> = Vec::new(); // Variables given the value they have at a row to evaluate it
+		for i in start..self.rows.len() {
+			if !(all || i == start || self.rows[i].reads.iter().any(|v| dirty.contains(v))) {
+				continue;
+			}
+			if let Some(v) = self.rows[i].assigns {
+				dirty.push(v);
+			}
+			self.evaluate_row(vm, i);
+			if let Some(v) = self.rows[i].assigns {
+				dirty.push(v);
+			}
+			read.extend(self.rows[i].reads.iter().cloned());
+		}
+		// Leave each of those variables with the value given to it by the last row that assigns to it. Other variables
+		// keep the value they have in the VM, which may have been set since the rows assigning to them were evaluated.
+		for row in self.rows.iter() {
+			if let (Some(v), &Ok(ref val)) = (row.assigns, &row.result) {
+				if dirty.contains(&v) || read.contains(&v) {
+					vm.set_var(v, val.clone());
+				}
+			}
+		}
+	}
+
+	/// Gets the values that the variables in `reads` have at row `i`. Variables that are assigned to by another row,
+	/// but not by one above this one, haven't been given a value yet. What this row assigned to when it was last
+	/// evaluated doesn't count, so that a row such as `a=a+1` uses the value `a` had before it. Variables that no other
+	/// row assigns to aren't included, as they keep the value they have in the VM.
+	fn values_at(&self, i: usize, reads: &[VarName]) -> Result<Vec<(VarName, Value)>, ParseError> {
+		let mut values = Vec::new();
+		for &v in reads.iter() {
+			if !self.rows.iter().enumerate().any(|(j, row)| j != i && row.assigns == Some(v)) {
+				continue;
+			}
+			match self.rows[..i].iter().rev().find(|row| row.assigns == Some(v)).map(|row| &row.result) {
+				Some(&Ok(ref val)) => values.push((v, val.clone())),
+				_ => return Err(UndefVar(v, 0)),
+			}
+		}
+		Ok(values)
+	}
+
+	fn evaluate_row(&mut self, vm: &mut VM, i: usize) {
+		let ex = self.rows[i].editor.root_ex.clone();
+		let assigns = com::split_assignment(&ex).map(|(v, _)| v);
+		let mut reads = Vec::new();
+		vars_read(&ex, assigns.is_some(), &mut reads);
+
+		// Give each variable read the value it has at this row
+		let result = self.values_at(i, &reads).and_then(|values| {
+			for (v, val) in values.into_iter() {
+				vm.set_var(v, val);
+			}
+			vm.evaluate(&ex)
+		});
+
+		let row = &mut self.rows[i];
+		row.result = result;
+		row.editor.set_eval_result(&row.result);
+		row.evaluated = Some(copy_vexpr(&ex, None));
+		row.assigns = assigns;
+		row.reads = reads;
+	}
+
+	/// Prepares the evaluation of row `i` as it is now, to be run on another thread, with the variables it reads given
+	/// the values they have at that row, so that the result is the one evaluating the row would give.
+	pub fn job(&self, vm: &VM, i: usize) -> Result<EvalJob, ParseError> {
+		let ex = &self.rows[i].editor.root_ex;
+		let mut reads = Vec::new();
+		vars_read(ex, com::split_assignment(ex).is_some(), &mut reads);
+		let values = try!(self.values_at(i, &reads));
+		let mut job = try!(vm.job(ex));
+		for (v, val) in values.into_iter() {
+			job.set_var(v, val);
+		}
+		Ok(job)
+	}
+
+	/// Moves to row `i`, evaluating the row being left if it has been changed. If `x` is given, the cursor is put at
+	/// the position in the row nearest to it.
+	pub fn move_to(&mut self, vm: &mut VM, i: usize, x: Option<f64>) {
+		if i == self.current {
+			return;
+		}
+		let old = self.current;
+		self.rows[old].editor.anchor = None;
+		if self.rows[old].is_changed() {
+			self.evaluate(vm, old);
+		}
+		self.current = i;
+		match x {
+			Some(x) => self.rows[i].editor.move_to_x(x),
+			None => {
+				let editor = &mut self.rows[i].editor;
+				let pos = editor.root_ex.borrow().tokens.len();
+				editor.cursor = Cursor::new_ex(editor.root_ex.clone(), pos);
+			},
+		}
+	}
+
+	/// Inserts an empty row below the row being edited, and moves to it.
+	pub fn insert_row(&mut self, vm: &mut VM) {
+		let i = self.current + 1;
+		self.rows.insert(i, Row::new(Editor::new()));
+		self.move_to(vm, i, None);
+	}
+
+	/// Applies a fix to row `i`, and moves to it. A variable is defined by a new row above, with the cursor after its `=`.
+	/// Returns true if anything was changed.
+	pub fn apply_fix(&mut self, vm: &mut VM, i: usize, fix: &Fix) -> bool {
+		match fix {
+			&Fix::DefineVariable(id) => {
+				let ex = match parse_text(&format!("{}=", id)) {
+					Ok(ex) => ex,
+					Err(_) => return false,
+				};
+				let pos = ex.borrow().tokens.len();
+				let mut editor = Editor::with_expression(ex, pos);
+				editor.update_errors();
+				self.rows.insert(i, Row::new(editor));
+				if self.current >= i {
+					self.current += 1;
+				}
+				self.move_to(vm, i, None);
+				true
+			},
+			_ => {
+				self.move_to(vm, i, None);
+				self.editor().apply_fix(fix)
+			},
+		}
+	}
+
+	/// Removes the row being edited if it is empty and isn't the only row, and moves to the row above.
+	/// Returns true if it was removed.
+	pub fn remove_row(&mut self, vm: &mut VM) -> bool {
+		if self.rows.len() == 1 || self.editor().root_ex.borrow().tokens.len() != 0 {
+			return false;
+		}
+		let removed = self.rows.remove(self.current);
+		if removed.assigns.is_some() {
+			// It was emptied since it was last evaluated, so the rows that used its variable need evaluating again.
+			self.evaluate_all(vm);
+		}
+		if self.current != 0 {
+			self.current -= 1;
+		}
+		let editor = self.editor();
+		let pos = editor.root_ex.borrow().tokens.len();
+		editor.cursor = Cursor::new_ex(editor.root_ex.clone(), pos);
+		true
+	}
+
+	/// Returns the row drawn nearest to the y coordinate given.
+	fn row_at(&self, y: f64) -> usize {
+		let mut best = (::std::f64::INFINITY, self.current);
+		for (i, row) in self.rows.iter().enumerate() {
+			if let Some(ext) = row.extent {
+				let d = if y < ext.y0() { ext.y0() - y } else if y > ext.y1() { y - ext.y1() } else { 0.0 };
+				if d < best.0 {
+					best = (d, i);
+				}
+			}
+		}
+		best.1
+	}
+
+	/// Performs the action given. Moving up or down past the edge of a row moves to the next row, and Backspace in an
+	/// empty row removes it. Anything else is handled by the row's editor.
+	/// Returns true if the action has been handled.
+	pub fn handle_action(&mut self, ctx: &mut Context, action: Action) -> bool {
+		// Whether the cursor can't move any further up or down within the row
+		let (at_top, at_bottom) = {
+			let cursor = &self.rows[self.current].editor.cursor;
+			(!cursor.clone().move_up(), !cursor.clone().move_down())
+		};
+		let selecting = self.rows[self.current].editor.anchor.is_some();
+		match action {
+			Action::Move(Motion::Up) if self.current != 0 && at_top => {
+				let (i, x) = (self.current - 1, self.editor().cursor_x());
+				self.move_to(&mut ctx.vm, i, x);
+				ctx.frontend.dirty_expression();
+				true
+			},
+			Action::Move(Motion::Down) if self.current + 1 < self.rows.len() && at_bottom => {
+				let (i, x) = (self.current + 1, self.editor().cursor_x());
+				self.move_to(&mut ctx.vm, i, x);
+				ctx.frontend.dirty_expression();
+				true
+			},
+			Action::NewRow => {
+				self.insert_row(&mut ctx.vm);
+				ctx.frontend.dirty_expression();
+				true
+			},
+			Action::Backspace if !selecting && self.remove_row(&mut ctx.vm) => {
+				ctx.frontend.dirty_expression();
+				true
+			},
+			Action::QuickFix => {
+				let i = self.current;
+				if let Some(fix) = self.editor().fix_at_cursor() {
+					if self.apply_fix(&mut ctx.vm, i, &fix) {
+						ctx.frontend.dirty_expression();
+					}
+				}
+				true
+			},
+			_ => self.editor().handle_action(ctx, action),
+		}
+	}
+
+	/// Handles a click at the position (x, y), moving to the row nearest to it.
+	/// Returns if the expression should be updated.
+	pub fn handle_click(&mut self, vm: &mut VM, x: f64, y: f64, select: bool) -> bool {
+		let i = self.row_at(y);
+		let moved = i != self.current && !select;
+		if moved {
+			self.move_to(vm, i, Some(x));
+		}
+		self.editor().handle_click(x, y, select) || moved
+	}
+}
+
+/// Adds the variables read by the expression to `vars`. If `assignment` is true, the variable assigned to is skipped.
+fn vars_read(ex: &VExprRef, assignment: bool, vars: &mut Vec<VarName>) {
+	let tokens = &ex.borrow().tokens;
+	let mut i = 0;
+	if assignment {
+		// Skip the name and the `=`
+		i = com::var_name_at(tokens, 0).map_or(0, |(_, len)| len + 1);
+	}
+	while i < tokens.len() {
+		match com::var_name_at(tokens, i) {
+			Some((id, len)) => {
+				if !id.is_constant() && !vars.contains(&id) {
+					vars.push(id);
+				}
+				i += len;
+			},
+			None => {
+				for inner in tokens[i].get_inner_expr().iter() {
+					vars_read(inner, false, vars);
+				}
+				i += 1;
+			},
+		}
+	}
+}
+
+#[test]
+fn evaluate_test() {
+	use session::Session;
+	fn value(res: Result<Value, ParseError>) -> String {
+		res.map(|v| v.to_string()).unwrap_or_else(|e| e.to_string())
+	}
+
+	let mut session = Session::new();
+	let a = VarName::new('a');
+	assert_eq!(value(session.evaluate_text("a=1")), "1");
+	assert_eq!(value(session.evaluate_text("b=a+1")), "2");
+	// A value set in the VM is kept when rows that don't use it are evaluated
+	session.ctx.vm.set_var(a, Value::Num(d128!(5)));
+	assert_eq!(value(session.evaluate_text("c=3")), "3");
+	assert_eq!(session.ctx.vm.get_var(a), Some(Value::Num(d128!(5))));
+	// Rows reading it use the value it has at the row
+	assert_eq!(value(session.evaluate_text("a")), "1");
+	assert_eq!(session.ctx.vm.get_var(a), Some(Value::Num(d128!(1))));
+	// A row can use the value a variable it assigns to had before it
+	assert_eq!(value(session.evaluate_text("a=a+1")), "2");
+	assert_eq!(value(session.evaluate_text("b")), "2");
+	assert_eq!(session.ctx.vm.get_var(a), Some(Value::Num(d128!(2))));
+
+	// Evaluating in place doesn't grow the worksheet
+	let mut session = Session::new();
+	for text in ["a=2", "a=a*3", "a+#1"].iter() {
+		assert!(session.evaluate_in_place(text).is_ok());
+	}
+	assert_eq!(session.sheet.rows.len(), 1);
+	assert_eq!(value(session.sheet.rows[0].result.clone()), "8");
+	// Nor does the history
+	use session::IN_PLACE_HISTORY_LIMIT;
+	for _ in 0..IN_PLACE_HISTORY_LIMIT {
+		assert!(session.evaluate_in_place("a=a+1").is_ok());
+	}
+	assert_eq!(session.ctx.vm.get_history().len(), IN_PLACE_HISTORY_LIMIT);
+	assert!(session.evaluate_in_place("#3").is_err());
+	assert_eq!(value(session.evaluate_in_place("#4")), "7");
+}
+
+#[test]
+fn job_test() {
+	fn value(res: Result<Value, ParseError>) -> String {
+		res.map(|v| v.to_string()).unwrap_or_else(|e| e.to_string())
+	}
+
+	// The result of evaluating a row live is the one evaluating it gives
+	for texts in [&["a", "a=5"][..], &["a=1", "a=a+1"][..], &["a=1", "b=a*2", "a=a+b"][..]].iter() {
+		let mut sheet = Worksheet::with_rows(texts.iter().map(|text| parse_text(text).unwrap()).collect());
+		let mut vm = VM::new();
+		sheet.evaluate_all(&mut vm);
+		for i in 0..sheet.rows.len() {
+			let live = sheet.job(&vm, i).and_then(|job| job.run());
+			assert_eq!(value(live), value(sheet.rows[i].result.clone()), "row {} of {:?}", i + 1, texts);
+		}
+	}
+}
+
+#[test]
+fn fix_test() {
+	use session::Session;
+	fn rows(session: &Session) -> Vec<String> {
+		session.sheet.rows.iter().map(|row| tokens_to_text(&row.editor.root_ex.borrow().tokens)).collect()
+	}
+	fn kind(fix: &Fix) -> &'static str {
+		match fix {
+			&Fix::InsertOperand(_) => "insert",
+			&Fix::Remove(_) => "remove",
+			&Fix::MakeSubscript(_) => "subscript",
+			&Fix::DefineVariable(_) => "define",
+		}
+	}
+
+	// Each fix is the first offered for the first problem with the expression
+	let fixes: [(&str, &str, &[&str]); 4] = [
+		("2+", "insert", &["2+()"]),
+		("2++3", "remove", &["2+3"]),
+		("x1+2", "subscript", &["x_1+2"]),
+		("q+1", "define", &["q=", "q+1"]),
+	];
+	for &(text, fix_kind, expected) in fixes.iter() {
+		let mut session = Session::new();
+		let _ = session.evaluate_text(text);
+		let fix = session.editor().diagnostics[0].fixes[0].clone();
+		assert_eq!(kind(&fix), fix_kind, "the fix offered for '{}'", text);
+		let i = session.sheet.current;
+		assert!(session.sheet.apply_fix(&mut session.ctx.vm, i, &fix));
+		assert_eq!(rows(&session), expected);
+	}
+}
@@ -0,0 +1,170 @@
+/// Statistics over lists of numbers.
+///
+/// Each function returns `None` if the list given doesn't have enough elements for the statistic to be defined.
+use std::cmp::Ordering;
+use std::ops::{Add, Sub, Mul, Div};
+
+use num::*;
+use consts::*;
+
+/// A type of number that statistics can be calculated over.
+pub trait Number: Clone + Add<Output=Self> + Sub<Output=Self> + Mul<Output=Self> + Div<Output=Self> {
+	fn from_num(x: d128) -> Self;
+	/// The value used to order the numbers, and to compare them for equality.
+	fn value(&self) -> d128;
+	fn sqrt(&self) -> Self;
+}
+impl Number for d128 {
+	fn from_num(x: d128) -> d128 {
+		x
+	}
+	fn value(&self) -> d128 {
+		*self
+	}
+	fn sqrt(&self) -> d128 {
+		sqrt(*self)
+	}
+}
+
+fn sorted<T: Number>(xs: &[T]) -> Vec<T> {
+	let mut v = xs.to_vec();
+	v.sort_by(|a, b| a.value().partial_cmp(&b.value()).unwrap_or(Ordering::Equal));
+	v
+}
+
+/// Median of an already sorted list
+fn median_sorted<T: Number>(xs: &[T]) -> Option<T> {
+	let n = xs.len();
+	if n == 0 {
+		None
+	} else if n % 2 == 1 {
+		Some(xs[n / 2].clone())
+	} else {
+		Some((xs[n / 2 - 1].clone() + xs[n / 2].clone()) / T::from_num(*D2))
+	}
+}
+
+pub fn sum<T: Number>(xs: &[T]) -> Option<T> {
+	Some(xs.iter().fold(T::from_num(*D0), |acc, x| acc + x.clone()))
+}
+
+pub fn mean<T: Number>(xs: &[T]) -> Option<T> {
+	if xs.len() == 0 {
+		return None;
+	}
+	sum(xs).map(|s| s / T::from_num(d128::from(xs.len() as u64)))
+}
+
+pub fn median<T: Number>(xs: &[T]) -> Option<T> {
+	median_sorted(&sorted(xs))
+}
+
+/// The most common value in the list. If there are several, the smallest is returned.
+pub fn mode<T: Number>(xs: &[T]) -> Option<T> {
+	let s = sorted(xs);
+	let mut best: Option<(T, usize)> = None;
+	let mut i = 0;
+	while i < s.len() {
+		let mut j = i + 1;
+		while j < s.len() && s[j].value() == s[i].value() {
+			j += 1;
+		}
+		let count = j - i;
+		best = match best {
+			Some((v, c)) if c >= count => Some((v, c)),
+			_ => Some((s[i].clone(), count)),
+		};
+		i = j;
+	}
+	best.map(|(v, _)| v)
+}
+
+/// Variance of the list. If `sample` is true, Bessel's correction is applied (divides by n - 1 instead of n).
+pub fn variance<T: Number>(xs: &[T], sample: bool) -> Option<T> {
+	let n = xs.len();
+	if n == 0 || (sample && n < 2) {
+		return None;
+	}
+	let m = match mean(xs) { Some(m) => m, None => return None };
+	let ss = xs.iter().fold(T::from_num(*D0), |acc, x| acc + (x.clone() - m.clone()) * (x.clone() - m.clone()));
+	let div = if sample { d128::from((n - 1) as u64) } else { d128::from(n as u64) };
+	Some(ss / T::from_num(div))
+}
+
+pub fn std_dev<T: Number>(xs: &[T], sample: bool) -> Option<T> {
+	variance(xs, sample).map(|v| v.sqrt())
+}
+
+/// Returns (Q1, Q2, Q3). Q1 and Q3 are the medians of the lower and upper halves, excluding the median itself
+/// if there is an odd number of elements.
+pub fn quartiles<T: Number>(xs: &[T]) -> Option<(T, T, T)> {
+	let s = sorted(xs);
+	let n = s.len();
+	if n == 0 {
+		return None;
+	} else if n == 1 {
+		return Some((s[0].clone(), s[0].clone(), s[0].clone()));
+	}
+	let lower = &s[..n / 2];
+	let upper = &s[(n + 1) / 2..];
+	match (median_sorted(lower), median_sorted(&s), median_sorted(upper)) {
+		(Some(q1), Some(q2), Some(q3)) => Some((q1, q2, q3)),
+		_ => None,
+	}
+}
+
+pub fn min<T: Number>(xs: &[T]) -> Option<T> {
+	xs.iter().fold(None, |acc, x| match acc {
+		Some(m) => if m.value() <= x.value() { Some(m) } else { Some(x.clone()) },
+		None => Some(x.clone()),
+	})
+}
+
+pub fn max<T: Number>(xs: &[T]) -> Option<T> {
+	xs.iter().fold(None, |acc, x| match acc {
+		Some(m) => if m.value() >= x.value() { Some(m) } else { Some(x.clone()) },
+		None => Some(x.clone()),
+	})
+}
+
+/// Least squares linear regression of `ys` on `xs`, of the form y = ax + b.
+///
+/// Returns (a, b, r), where r is the correlation coefficient. If the ys are all the same, r is 0, as they don't vary
+/// with x.
+pub fn lin_reg<T: Number>(xs: &[T], ys: &[T]) -> Option<(T, T, T)> {
+	if xs.len() != ys.len() || xs.len() < 2 {
+		return None;
+	}
+	let (mx, my) = match (mean(xs), mean(ys)) {
+		(Some(mx), Some(my)) => (mx, my),
+		_ => return None,
+	};
+	let mut sxx = T::from_num(*D0);
+	let mut syy = T::from_num(*D0);
+	let mut sxy = T::from_num(*D0);
+	for (x, y) in xs.iter().zip(ys.iter()) {
+		let dx = x.clone() - mx.clone();
+		let dy = y.clone() - my.clone();
+		sxx = sxx + dx.clone() * dx.clone();
+		syy = syy + dy.clone() * dy.clone();
+		sxy = sxy + dx * dy;
+	}
+	if sxx.value().is_zero() {
+		return None;
+	}
+	let a = sxy.clone() / sxx.clone();
+	let b = my - a.clone() * mx;
+	let r = if syy.value().is_zero() { T::from_num(*D0) } else { sxy / (sxx * syy).sqrt() };
+	Some((a, b, r))
+}
+
+#[test]
+fn lin_reg_test() {
+	let xs = [d128!(1), d128!(2), d128!(3), d128!(4)];
+	assert_eq!(lin_reg(&xs, &[d128!(3), d128!(5), d128!(7), d128!(9)]), Some((d128!(2), d128!(1), d128!(1))));
+	assert_eq!(lin_reg(&xs, &[d128!(4), d128!(3), d128!(2), d128!(1)]), Some((d128!(-1), d128!(5), d128!(-1))));
+	// The ys don't vary with x
+	assert_eq!(lin_reg(&xs, &[d128!(5), d128!(5), d128!(5), d128!(5)]), Some((d128!(0), d128!(5), d128!(0))));
+	// Nor can a line be fitted through points with the same x
+	assert_eq!(lin_reg(&[d128!(1), d128!(1)], &[d128!(1), d128!(2)]), None);
+}
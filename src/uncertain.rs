@@ -0,0 +1,151 @@
+/// Values with standard uncertainties, propagated to first order.
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Add, Sub, Mul, Div, Neg};
+
+use num::*;
+use consts::*;
+use stat;
+
+/// A value with a standard uncertainty.
+///
+/// The uncertainty is stored as a linear combination of independent sources of error, rather than as a single
+/// number, so that values depending on the same source are correlated. For example `x - x` is exactly zero for any
+/// uncertain `x`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Uncertain {
+	pub val: d128,
+	/// (source, ∂val/∂source × σ of the source) for each source that the value depends on, sorted by source.
+	pub terms: Vec<(usize, d128)>,
+}
+impl Display for Uncertain {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		let u = self.uncertainty();
+		if u.is_zero() || !u.is_finite() || !self.val.is_finite() {
+			return write!(f, "{} ± {}", self.val, u);
+		}
+		// Quote the uncertainty to two significant figures if it starts with a 1 or 2, as rounding those to one
+		// significant figure loses a lot of information, and to one otherwise. The value is given to the same place.
+		let e: i32 = u.logb().into();
+		let lead = u.scaleb(d128::from(-e));
+		let sig = if lead < d128::from(3) { 2 } else { 1 };
+		let dp = sig - 1 - e;
+		let round = |x: d128| if dp > 0 { x.quantize(D1.scaleb(d128::from(-dp))) } else { round_dp(x, dp).quantize(*D1) };
+		write!(f, "{} ± {}", round(self.val), round(u))
+	}
+}
+impl Uncertain {
+	/// A value with no uncertainty
+	pub fn exact(val: d128) -> Uncertain {
+		Uncertain{ val: val, terms: Vec::new() }
+	}
+
+	/// A value with the standard uncertainty `sigma`, that comes from the independent source `source`
+	pub fn new(val: d128, sigma: d128, source: usize) -> Uncertain {
+		Uncertain{ val: val, terms: vec![(source, sigma)] }
+	}
+
+	/// The combined standard uncertainty
+	pub fn uncertainty(&self) -> d128 {
+		sqrt(self.terms.iter().fold(*D0, |acc, &(_, c)| acc + c * c))
+	}
+
+	/// Returns a value of `val`, which is a function of the values in `partials`. Each is given with the partial
+	/// derivative of the function with respect to it.
+	pub fn propagate(val: d128, partials: &[(&Uncertain, d128)]) -> Uncertain {
+		let mut terms: Vec<(usize, d128)> = Vec::new();
+		for &(x, d) in partials.iter() {
+			for &(source, c) in x.terms.iter() {
+				match terms.binary_search_by(|&(s, _)| s.cmp(&source)) {
+					Ok(i) => terms[i].1 = terms[i].1 + d * c,
+					Err(i) => terms.insert(i, (source, d * c)),
+				}
+			}
+		}
+		Uncertain{ val: val, terms: terms }
+	}
+
+	/// Applies the function `f` with the derivative `df`
+	pub fn apply<F, G>(&self, f: F, df: G) -> Uncertain where F: Fn(d128) -> d128, G: Fn(d128) -> d128 {
+		if self.terms.is_empty() {
+			return Uncertain::exact(f(self.val));
+		}
+		Uncertain::propagate(f(self.val), &[(self, df(self.val))])
+	}
+
+	pub fn pow(&self, b: &Uncertain) -> Uncertain {
+		let v = self.val.pow(b.val);
+		// ∂/∂a a^b = b a^(b - 1) and ∂/∂b a^b = a^b ln a. The second is only needed if b is uncertain, which also
+		// avoids taking the logarithm of negative bases.
+		let da = if self.terms.is_empty() { *D0 } else { b.val * self.val.pow(b.val - *D1) };
+		let db = if b.terms.is_empty() { *D0 } else { v * self.val.ln() };
+		Uncertain::propagate(v, &[(self, da), (b, db)])
+	}
+}
+
+impl stat::Number for Uncertain {
+	fn from_num(x: d128) -> Uncertain {
+		Uncertain::exact(x)
+	}
+	fn value(&self) -> d128 {
+		self.val
+	}
+	fn sqrt(&self) -> Uncertain {
+		self.apply(sqrt, |x| *D1 / (*D2 * sqrt(x)))
+	}
+}
+
+impl Add for Uncertain {
+	type Output = Uncertain;
+	fn add(self, b: Uncertain) -> Uncertain {
+		Uncertain::propagate(self.val + b.val, &[(&self, *D1), (&b, *D1)])
+	}
+}
+impl Sub for Uncertain {
+	type Output = Uncertain;
+	fn sub(self, b: Uncertain) -> Uncertain {
+		Uncertain::propagate(self.val - b.val, &[(&self, *D1), (&b, -*D1)])
+	}
+}
+impl Neg for Uncertain {
+	type Output = Uncertain;
+	fn neg(self) -> Uncertain {
+		Uncertain::propagate(-self.val, &[(&self, -*D1)])
+	}
+}
+impl Mul for Uncertain {
+	type Output = Uncertain;
+	fn mul(self, b: Uncertain) -> Uncertain {
+		Uncertain::propagate(self.val * b.val, &[(&self, b.val), (&b, self.val)])
+	}
+}
+impl Div for Uncertain {
+	type Output = Uncertain;
+	fn div(self, b: Uncertain) -> Uncertain {
+		let v = self.val / b.val;
+		Uncertain::propagate(v, &[(&self, *D1 / b.val), (&b, -v / b.val)])
+	}
+}
+
+#[test]
+fn propagate_test() {
+	let a = Uncertain::new(*D1, d128!(0.1), 0);
+	let b = Uncertain::new(*D2, d128!(0.2), 1);
+
+	// Independent sources add in quadrature
+	let x = a.clone() + b.clone();
+	assert_eq!(x.val, d128!(3));
+	assert_eq!(x.uncertainty(), sqrt(d128!(0.05)));
+	assert_eq!(format!("{}", x), "3.00 ± 0.22");
+	assert_eq!((a.clone() * b.clone()).terms, vec![(0, d128!(0.2)), (1, d128!(0.2))]);
+	assert_eq!((a.clone() / b.clone()).terms, vec![(0, d128!(0.05)), (1, d128!(-0.05))]);
+
+	// The same source is correlated with itself
+	assert_eq!((a.clone() - a.clone()).uncertainty(), *D0);
+	assert_eq!((a.clone() + a.clone()).uncertainty(), d128!(0.2));
+	assert_eq!(a.pow(&Uncertain::exact(*D2)).terms, vec![(0, d128!(0.2))]);
+
+	// Exact values stay exact
+	let x = Uncertain::exact(*D2) * Uncertain::exact(d128!(3));
+	assert!(x.terms.is_empty());
+	assert_eq!(format!("{}", x), "6 ± 0");
+}
@@ -0,0 +1,182 @@
+use std::fmt::{self, Display, Formatter};
+
+use num::*;
+use err::*;
+use interval::Interval;
+use uncertain::Uncertain;
+
+/// A value that can be held on the stack of the VM, or stored in a variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	Num(d128),
+	Interval(Interval),
+	Uncertain(Uncertain),
+	List(Vec<Value>), // The elements are never lists themselves.
+}
+impl Display for Value {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			&Value::Num(ref v) => write!(f, "{}", v),
+			&Value::Interval(ref v) => write!(f, "{}", v),
+			&Value::Uncertain(ref v) => write!(f, "{}", v),
+			&Value::List(ref vs) => {
+				try!(write!(f, "{{"));
+				for (i, v) in vs.iter().enumerate() {
+					if i != 0 {
+						try!(write!(f, ", "));
+					}
+					try!(write!(f, "{}", v));
+				}
+				write!(f, "}}")
+			},
+		}
+	}
+}
+impl From<d128> for Value {
+	fn from(v: d128) -> Value {
+		Value::Num(v)
+	}
+}
+impl From<Interval> for Value {
+	fn from(v: Interval) -> Value {
+		Value::Interval(v)
+	}
+}
+impl From<Uncertain> for Value {
+	fn from(v: Uncertain) -> Value {
+		Value::Uncertain(v)
+	}
+}
+impl Value {
+	/// Returns the number held, or `ExpectedNum(pos)` if this is anything else.
+	pub fn to_num(&self, pos: usize) -> Result<d128, ParseError> {
+		match self {
+			&Value::Num(v) => Ok(v),
+			&Value::Interval(_) | &Value::Uncertain(_) | &Value::List(_) => Err(ExpectedNum(pos)),
+		}
+	}
+
+	/// Returns the interval held, treating a number as a single point, or `ExpectedNum(pos)` if this is a list.
+	pub fn to_interval(&self, pos: usize) -> Result<Interval, ParseError> {
+		match self {
+			&Value::Num(v) => Ok(Interval::point(v)),
+			&Value::Interval(v) => Ok(v),
+			&Value::Uncertain(_) => Err(IncompatibleValues(pos)),
+			&Value::List(_) => Err(ExpectedNum(pos)),
+		}
+	}
+
+	/// Returns the uncertain value held, treating a number as exact, or `ExpectedNum(pos)` if this is a list.
+	pub fn to_uncertain(&self, pos: usize) -> Result<Uncertain, ParseError> {
+		match self {
+			&Value::Num(v) => Ok(Uncertain::exact(v)),
+			&Value::Uncertain(ref v) => Ok(v.clone()),
+			&Value::Interval(_) => Err(IncompatibleValues(pos)),
+			&Value::List(_) => Err(ExpectedNum(pos)),
+		}
+	}
+
+	/// Returns the elements of the list. A single value is treated as a list of one element.
+	pub fn to_list(&self) -> Vec<Value> {
+		match self {
+			&Value::List(ref vs) => vs.clone(),
+			_ => vec![self.clone()],
+		}
+	}
+
+	pub fn is_list(&self) -> bool {
+		match self {
+			&Value::List(_) => true,
+			&Value::Num(_) | &Value::Interval(_) | &Value::Uncertain(_) => false,
+		}
+	}
+
+	/// Returns true if the value is, or contains, an interval.
+	pub fn is_interval(&self) -> bool {
+		match self {
+			&Value::Interval(_) => true,
+			&Value::List(ref vs) => vs.iter().any(|v| v.is_interval()),
+			&Value::Num(_) | &Value::Uncertain(_) => false,
+		}
+	}
+
+	/// Returns true if the value is, or contains, an uncertain value.
+	pub fn is_uncertain(&self) -> bool {
+		match self {
+			&Value::Uncertain(_) => true,
+			&Value::List(ref vs) => vs.iter().any(|v| v.is_uncertain()),
+			&Value::Num(_) | &Value::Interval(_) => false,
+		}
+	}
+
+	/// Converts every number in the value into an interval containing only that number.
+	pub fn into_interval(self) -> Value {
+		match self {
+			Value::Num(v) => Value::Interval(Interval::point(v)),
+			Value::List(vs) => Value::List(vs.into_iter().map(|v| v.into_interval()).collect()),
+			v => v,
+		}
+	}
+
+	/// Applies `f` to the number, `g` to the interval or `h` to the uncertain value, or one of them to each element of
+	/// the list.
+	pub fn map<F, G, H>(self, f: F, g: G, h: H) -> Value
+			where F: Fn(d128) -> d128, G: Fn(Interval) -> Interval, H: Fn(Uncertain) -> Uncertain {
+		self.map_ref(&f, &g, &h)
+	}
+
+	fn map_ref<F, G, H>(self, f: &F, g: &G, h: &H) -> Value
+			where F: Fn(d128) -> d128, G: Fn(Interval) -> Interval, H: Fn(Uncertain) -> Uncertain {
+		match self {
+			Value::Num(v) => Value::Num(f(v)),
+			Value::Interval(v) => Value::Interval(g(v)),
+			Value::Uncertain(v) => Value::Uncertain(h(v)),
+			Value::List(vs) => Value::List(vs.into_iter().map(|v| v.map_ref(f, g, h)).collect()),
+		}
+	}
+
+	/// Combines two values element-wise using `f`, or `g` if either of the elements is an interval, or `h` if either
+	/// is uncertain. Intervals can't be combined with uncertain values.
+	///
+	/// A single value combined with a list is applied to every element of the list. Two lists must be the same length.
+	pub fn zip_with<F, G, H>(self, other: Value, pos: usize, f: F, g: G, h: H) -> Result<Value, ParseError>
+			where F: Fn(d128, d128) -> d128, G: Fn(Interval, Interval) -> Interval, H: Fn(Uncertain, Uncertain) -> Uncertain {
+		self.zip_with_ref(other, pos, &f, &g, &h)
+	}
+
+	fn zip_with_ref<F, G, H>(self, other: Value, pos: usize, f: &F, g: &G, h: &H) -> Result<Value, ParseError>
+			where F: Fn(d128, d128) -> d128, G: Fn(Interval, Interval) -> Interval, H: Fn(Uncertain, Uncertain) -> Uncertain {
+		match (self, other) {
+			(Value::List(as_), Value::List(bs)) => {
+				if as_.len() != bs.len() {
+					return Err(LengthMismatch(pos));
+				}
+				let mut ret = Vec::with_capacity(as_.len());
+				for (a, b) in as_.into_iter().zip(bs.into_iter()) {
+					ret.push(try!(a.zip_with_ref(b, pos, f, g, h)));
+				}
+				Ok(Value::List(ret))
+			},
+			(Value::List(as_), b) => {
+				let mut ret = Vec::with_capacity(as_.len());
+				for a in as_.into_iter() {
+					ret.push(try!(a.zip_with_ref(b.clone(), pos, f, g, h)));
+				}
+				Ok(Value::List(ret))
+			},
+			(a, Value::List(bs)) => {
+				let mut ret = Vec::with_capacity(bs.len());
+				for b in bs.into_iter() {
+					ret.push(try!(a.clone().zip_with_ref(b, pos, f, g, h)));
+				}
+				Ok(Value::List(ret))
+			},
+			(Value::Num(a), Value::Num(b)) => Ok(Value::Num(f(a, b))),
+			(a, b) => if a.is_uncertain() || b.is_uncertain() {
+				Ok(Value::Uncertain(h(try!(a.to_uncertain(pos)), try!(b.to_uncertain(pos)))))
+			} else {
+				Ok(Value::Interval(g(try!(a.to_interval(pos)), try!(b.to_interval(pos)))))
+			},
+		}
+	}
+}
@@ -16,6 +16,7 @@ pub enum OpType {
 	Sub,
 	Mul,
 	Div,
+	PlusMinus,
 }
 impl Display for OpType {
 	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
@@ -24,10 +25,66 @@ impl Display for OpType {
 			&OpType::Sub => write!(f, "{}", CHAR_SUB),
 			&OpType::Mul => write!(f, "{}", CHAR_MUL),
 			&OpType::Div => write!(f, "{}", CHAR_DIV),
+			&OpType::PlusMinus => write!(f, "{}", CHAR_PLUS_MINUS),
 		}
 	}
 }
 
+/// A pair of delimiters around an expression, which grow to fit it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DelimType {
+	Paren,
+	Square,
+	Abs,
+	Floor,
+	Ceil,
+}
+impl DelimType {
+	pub fn open(&self) -> char {
+		match self {
+			&DelimType::Paren  => '(',
+			&DelimType::Square => '[',
+			&DelimType::Abs    => '|',
+			&DelimType::Floor  => '⌊',
+			&DelimType::Ceil   => '⌈',
+		}
+	}
+	pub fn close(&self) -> char {
+		match self {
+			&DelimType::Paren  => ')',
+			&DelimType::Square => ']',
+			&DelimType::Abs    => '|',
+			&DelimType::Floor  => '⌋',
+			&DelimType::Ceil   => '⌉',
+		}
+	}
+	/// Finds the delimiters that start with `c`.
+	pub fn from_open(c: char) -> Option<DelimType> {
+		DelimType::all().iter().cloned().find(|d| d.open() == c)
+	}
+	/// Finds the delimiters that end with `c`.
+	pub fn from_close(c: char) -> Option<DelimType> {
+		DelimType::all().iter().cloned().find(|d| d.close() == c)
+	}
+	pub fn all() -> &'static [DelimType] {
+		static ALL: [DelimType; 5] = [DelimType::Paren, DelimType::Square, DelimType::Abs, DelimType::Floor, DelimType::Ceil];
+		&ALL
+	}
+	/// The function that the delimiters apply to their contents, if they aren't just brackets.
+	pub fn func(&self) -> Option<FuncType> {
+		match self {
+			&DelimType::Paren | &DelimType::Square => None,
+			&DelimType::Abs   => Some(FuncType::Abs),
+			&DelimType::Floor => Some(FuncType::Floor),
+			&DelimType::Ceil  => Some(FuncType::Ceil),
+		}
+	}
+	/// Finds the delimiters that apply the function given, so that e.g. `abs` can be written as `|x|`.
+	pub fn from_func(func: FuncType) -> Option<DelimType> {
+		DelimType::all().iter().cloned().find(|d| d.func() == Some(func))
+	}
+}
+
 #[derive(Clone, Debug)]
 pub enum VToken {
 	Space,
@@ -35,9 +92,14 @@ pub enum VToken {
 	Digit(char),
 	Op(OpType),
 	Pow(VExprRef),
+	Sub(VExprRef), // Subscript, e.g. `x₁`
 	Frac(VExprRef, VExprRef), // (numerator, denominator)
 	Root(VExprRef, VExprRef),
 	Func(FuncType, VExprRef),
+	List(VExprRef), // Comma seperated elements
+	Delim(DelimType, VExprRef), // e.g. `(x)`, `|x|` or `⌊x⌋`
+	Ans(usize), // `Ans` if 0, `Ansₙ` otherwise
+	Hist(usize), // `#n`
 }
 impl VToken {
 	pub fn from_char(c: char) -> VToken {
@@ -49,6 +111,7 @@ impl VToken {
 				'-' | CHAR_SUB => VToken::Op(OpType::Sub),
 				'*' | CHAR_MUL => VToken::Op(OpType::Mul),
 				'/' | CHAR_DIV => VToken::Op(OpType::Div),
+				CHAR_PLUS_MINUS => VToken::Op(OpType::PlusMinus),
 				_ => VToken::Char(c)
 			}
 		}
@@ -56,20 +119,36 @@ impl VToken {
 	
 	pub fn get_inner_expr(&self) -> Box<[VExprRef]> {
 		match self {
-			&Pow(ref ex) | &Func(_, ref ex) => box [ex.clone()],
+			&Pow(ref ex) | &Sub(ref ex) | &Func(_, ref ex) | &List(ref ex) | &Delim(_, ref ex) => box [ex.clone()],
 			&Root(ref ex1, ref ex2) | &Frac(ref ex1, ref ex2) => box [ex1.clone(), ex2.clone()],
-			&Op(_) | &Digit(_) | &Char(_) | &Space => box []
+			&Op(_) | &Digit(_) | &Char(_) | &Space | &Ans(_) | &Hist(_) => box []
 		}
 	}
 	
 	pub fn has_inner_expr(&self) -> bool {
 		match self {
-			&Pow(_) | &Func(_, _) | &Root(_, _) | &Frac(_, _) => true,
-			&Op(_) | &Digit(_) | &Char(_) | &Space => false
+			&Pow(_) | &Sub(_) | &Func(_, _) | &Root(_, _) | &Frac(_, _) | &List(_) | &Delim(_, _) => true,
+			&Op(_) | &Digit(_) | &Char(_) | &Space | &Ans(_) | &Hist(_) => false
 		}
 	}
 }
 
+/// Returns `n` as subscript digits, or an empty string if `n` is 0.
+pub fn subscript(n: usize) -> String {
+	if n == 0 {
+		return String::new();
+	}
+	subscript_digits(n)
+}
+
+/// Returns `n` as subscript digits.
+pub fn subscript_digits(n: usize) -> String {
+	n.to_string().chars().map(|c| match c {
+		'0' => '₀', '1' => '₁', '2' => '₂', '3' => '₃', '4' => '₄',
+		'5' => '₅', '6' => '₆', '7' => '₇', '8' => '₈', _ => '₉',
+	}).collect()
+}
+
 pub type VExprRef = Rc<RefCell<VExpr>>;
 
 #[derive(Clone, Debug)]
@@ -128,12 +207,23 @@ pub fn display_vexpr<T: Write>(ex: VExprRef, cursor_opt: &Option<edit::Cursor>,
 			VToken::Op(op) => {
 				try!(write!(buf, "{}", op));
 			},
+			VToken::Ans(n) => {
+				try!(write!(buf, "Ans{}", subscript(n)));
+			},
+			VToken::Hist(n) => {
+				try!(write!(buf, "#{}", n));
+			},
 			VToken::Pow(inner_ex_ref) => {
 				// Recursive stuff yay!
 				try!(write!(buf, "^("));
 				try!(display_vexpr(inner_ex_ref, &Some(cursor.clone()), buf));
 				try!(write!(buf, ")"));
 			},
+			VToken::Sub(inner_ex_ref) => {
+				try!(write!(buf, "_("));
+				try!(display_vexpr(inner_ex_ref, &Some(cursor.clone()), buf));
+				try!(write!(buf, ")"));
+			},
 			VToken::Func(func_type, inner_ex_ref) => {
 				try!(write!(buf, " {}(", func_type));
 				try!(display_vexpr(inner_ex_ref, &Some(cursor.clone()), buf));
@@ -153,6 +243,16 @@ pub fn display_vexpr<T: Write>(ex: VExprRef, cursor_opt: &Option<edit::Cursor>,
 				try!(display_vexpr(den_ex, &Some(cursor.clone()), buf));
 				try!(write!(buf, "))"));
 			}
+			VToken::List(inner_ex) => {
+				try!(write!(buf, "{{"));
+				try!(display_vexpr(inner_ex, &Some(cursor.clone()), buf));
+				try!(write!(buf, "}}"));
+			}
+			VToken::Delim(delim, inner_ex) => {
+				try!(write!(buf, "{}", delim.open()));
+				try!(display_vexpr(inner_ex, &Some(cursor.clone()), buf));
+				try!(write!(buf, "{}", delim.close()));
+			}
 		}
 	}
 
@@ -200,6 +300,22 @@ pub fn display_vexpr_errors<T: Write, V: Write>(ex: VExprRef, cursor_opt: &Optio
 				if err { try!(write!(e_buf, "~"));
 				} else { try!(write!(e_buf, " ")); }
 			},
+			VToken::Ans(n) => {
+				let s = format!("Ans{}", subscript(n));
+				try!(write!(buf, "{}", s));
+				for _ in 0..s.chars().count() {
+					if err { try!(write!(e_buf, "~"));
+					} else { try!(write!(e_buf, " ")); }
+				}
+			},
+			VToken::Hist(n) => {
+				let s = format!("#{}", n);
+				try!(write!(buf, "{}", s));
+				for _ in 0..s.chars().count() {
+					if err { try!(write!(e_buf, "~"));
+					} else { try!(write!(e_buf, " ")); }
+				}
+			},
 			VToken::Pow(inner_ex_ref) => {
 				// Recursive stuff yay!
 				try!(write!(buf, "^("));
@@ -210,6 +326,15 @@ pub fn display_vexpr_errors<T: Write, V: Write>(ex: VExprRef, cursor_opt: &Optio
 				if err { try!(write!(e_buf, "~"));
 				} else { try!(write!(e_buf, " ")); }
 			},
+			VToken::Sub(inner_ex_ref) => {
+				try!(write!(buf, "_("));
+				if err { try!(write!(e_buf, "~~"));
+				} else { try!(write!(e_buf, "  ")); }
+				try!(display_vexpr(inner_ex_ref, &Some(cursor.clone()), buf));
+				try!(write!(buf, ")"));
+				if err { try!(write!(e_buf, "~"));
+				} else { try!(write!(e_buf, " ")); }
+			},
 			VToken::Func(func_type, inner_ex_ref) => {
 				let s = format!("{}", func_type);
 				try!(write!(buf, " {}(", s));
@@ -248,6 +373,24 @@ pub fn display_vexpr_errors<T: Write, V: Write>(ex: VExprRef, cursor_opt: &Optio
 				if err { try!(write!(e_buf, "~~"));
 				} else { try!(write!(e_buf, "  ")); }
 			}
+			VToken::List(inner_ex) => {
+				try!(write!(buf, "{{"));
+				if err { try!(write!(e_buf, "~"));
+				} else { try!(write!(e_buf, " ")); }
+				try!(display_vexpr(inner_ex, &Some(cursor.clone()), buf));
+				try!(write!(buf, "}}"));
+				if err { try!(write!(e_buf, "~"));
+				} else { try!(write!(e_buf, " ")); }
+			}
+			VToken::Delim(delim, inner_ex) => {
+				try!(write!(buf, "{}", delim.open()));
+				if err { try!(write!(e_buf, "~"));
+				} else { try!(write!(e_buf, " ")); }
+				try!(display_vexpr(inner_ex, &Some(cursor.clone()), buf));
+				try!(write!(buf, "{}", delim.close()));
+				if err { try!(write!(e_buf, "~"));
+				} else { try!(write!(e_buf, " ")); }
+			}
 		}
 	}
 
@@ -290,3 +433,176 @@ pub fn find_vexpr(needle: &VExprRef, haystack: &VExprRef) -> Option<(usize, usiz
 	}
 	None
 }
+
+/// Makes a deep copy of `ex`, giving the copy the parent `parent`.
+pub fn copy_vexpr(ex: &VExprRef, parent: Option<&VExprRef>) -> VExprRef {
+	let copy = match parent {
+		Some(p) => VExpr::with_parent(p.clone()).to_ref(),
+		None => VExpr::new_ref(),
+	};
+	let tokens = copy_tokens(&ex.borrow().tokens, &copy);
+	copy.borrow_mut().tokens = tokens;
+	copy
+}
+
+/// Makes deep copies of `tokens`, so that they can be inserted into `parent`.
+pub fn copy_tokens(tokens: &[VToken], parent: &VExprRef) -> Vec<VToken> {
+	tokens.iter().map(|tok| match tok {
+		&Pow(ref ex) => Pow(copy_vexpr(ex, Some(parent))),
+		&Sub(ref ex) => Sub(copy_vexpr(ex, Some(parent))),
+		&Frac(ref ex1, ref ex2) => Frac(copy_vexpr(ex1, Some(parent)), copy_vexpr(ex2, Some(parent))),
+		&Root(ref ex1, ref ex2) => Root(copy_vexpr(ex1, Some(parent)), copy_vexpr(ex2, Some(parent))),
+		&Func(ref func, ref ex) => Func(func.clone(), copy_vexpr(ex, Some(parent))),
+		&List(ref ex) => List(copy_vexpr(ex, Some(parent))),
+		&Delim(delim, ref ex) => Delim(delim, copy_vexpr(ex, Some(parent))),
+		tok => tok.clone(),
+	}).collect()
+}
+
+/// Returns true if the expressions `a` and `b` have the same structure and contents.
+pub fn vexpr_eq(a: &VExprRef, b: &VExprRef) -> bool {
+	let (a, b) = (a.borrow(), b.borrow());
+	a.tokens.len() == b.tokens.len() && a.tokens.iter().zip(b.tokens.iter()).all(|(ta, tb)| {
+		let same = match (ta, tb) {
+			(&Space, &Space) => true,
+			(&Char(x), &Char(y)) | (&Digit(x), &Digit(y)) => x == y,
+			(&Op(x), &Op(y)) => x == y,
+			(&Ans(x), &Ans(y)) | (&Hist(x), &Hist(y)) => x == y,
+			(&Func(ref x, _), &Func(ref y, _)) => x == y,
+			(&Delim(x, _), &Delim(y, _)) => x == y,
+			(&Pow(_), &Pow(_)) | (&Sub(_), &Sub(_)) | (&Frac(_, _), &Frac(_, _)) | (&Root(_, _), &Root(_, _)) | (&List(_), &List(_)) => true,
+			_ => false,
+		};
+		same && ta.get_inner_expr().iter().zip(tb.get_inner_expr().iter()).all(|(x, y)| vexpr_eq(x, y))
+	})
+}
+
+/// Gives the route from the root expression down to `ex`, as the (token position, position in that token) of each
+/// expression on the way.
+pub fn vexpr_path(ex: &VExprRef) -> Vec<(usize, usize)> {
+	let mut path = Vec::new();
+	let mut ex = ex.clone();
+	loop {
+		let parent = match ex.borrow().get_parent() {
+			Some(p) => p,
+			None => break,
+		};
+		match find_vexpr(&ex, &parent) {
+			Some(step) => path.push(step),
+			None => break,
+		}
+		ex = parent;
+	}
+	path.reverse();
+	path
+}
+
+/// Follows a path given by `vexpr_path` down from `root`.
+pub fn vexpr_at_path(root: &VExprRef, path: &[(usize, usize)]) -> Option<VExprRef> {
+	let mut ex = root.clone();
+	for &(i, j) in path.iter() {
+		let inner = match ex.borrow().tokens.get(i) {
+			Some(tok) => tok.get_inner_expr().get(j).cloned(),
+			None => None,
+		};
+		match inner {
+			Some(inner) => ex = inner,
+			None => return None,
+		}
+	}
+	Some(ex)
+}
+
+/// Writes the tokens as plain text, in a form that can be typed back in.
+pub fn tokens_to_text(tokens: &[VToken]) -> String {
+	let mut s = String::new();
+	for tok in tokens.iter() {
+		match tok {
+			&Space => {},
+			&Char(c) | &Digit(c) => s.push(c),
+			&Op(OpType::Add) => s.push('+'),
+			&Op(OpType::Sub) => s.push('-'),
+			&Op(OpType::Mul) => s.push('*'),
+			&Op(OpType::Div) => s.push('/'),
+			&Op(OpType::PlusMinus) => s.push(CHAR_PLUS_MINUS),
+			&Ans(n) => s.push_str(&format!("Ans{}", subscript(n))),
+			&Hist(n) => s.push_str(&format!("#{}", n)),
+			&Pow(ref ex) => s.push_str(&format!("^{}", operand_to_text(ex))),
+			&Sub(ref ex) => s.push_str(&format!("_{}", operand_to_text(ex))),
+			&Frac(ref num, ref den) => s.push_str(&format!("{}/{}", operand_to_text(num), operand_to_text(den))),
+			&Root(ref degree, ref ex) => s.push_str(&format!("root({}, {})", vexpr_to_text(degree), vexpr_to_text(ex))),
+			&Func(FuncType::Sqrt, ref ex) => s.push_str(&format!("sqrt({})", vexpr_to_text(ex))),
+			&Func(func, ref ex) => s.push_str(&format!("{}({})", func, vexpr_to_text(ex))),
+			&List(ref ex) => s.push_str(&format!("{{{}}}", vexpr_to_text(ex))),
+			&Delim(delim, ref ex) => s.push_str(&format!("{}{}{}", delim.open(), vexpr_to_text(ex), delim.close())),
+		}
+	}
+	s
+}
+pub fn vexpr_to_text(ex: &VExprRef) -> String {
+	tokens_to_text(&ex.borrow().tokens)
+}
+/// Writes the expression as plain text, in brackets unless it is a single number, variable or function.
+fn operand_to_text(ex: &VExprRef) -> String {
+	let tokens = &ex.borrow().tokens;
+	let single = match tokens.len() {
+		1 => match tokens[0] { Op(_) | Pow(_) | Sub(_) | Frac(_, _) => false, _ => true },
+		_ => tokens.iter().all(|tok| match tok { &Digit(_) | &Char('.') => true, _ => false }),
+	};
+	if single && tokens.len() != 0 {
+		tokens_to_text(tokens)
+	} else {
+		format!("({})", tokens_to_text(tokens))
+	}
+}
+
+/// Writes the tokens as LaTeX maths.
+pub fn tokens_to_latex(tokens: &[VToken]) -> String {
+	let mut s = String::new();
+	for tok in tokens.iter() {
+		match tok {
+			&Space => {},
+			&Char('π') => s.push_str("\\pi "),
+			&Char('φ') => s.push_str("\\varphi "),
+			&Char(c) | &Digit(c) => s.push(c),
+			&Op(OpType::Add) => s.push('+'),
+			&Op(OpType::Sub) => s.push('-'),
+			&Op(OpType::Mul) => s.push_str("\\times "),
+			&Op(OpType::Div) => s.push_str("\\div "),
+			&Op(OpType::PlusMinus) => s.push_str("\\pm "),
+			&Ans(0) => s.push_str("\\mathrm{Ans}"),
+			&Ans(n) => s.push_str(&format!("\\mathrm{{Ans}}_{{{}}}", n)),
+			&Hist(n) => s.push_str(&format!("\\#{}", n)),
+			&Pow(ref ex) => s.push_str(&format!("^{{{}}}", vexpr_to_latex(ex))),
+			&Sub(ref ex) => s.push_str(&format!("_{{{}}}", vexpr_to_latex(ex))),
+			&Frac(ref num, ref den) => s.push_str(&format!("\\frac{{{}}}{{{}}}", vexpr_to_latex(num), vexpr_to_latex(den))),
+			&Root(ref degree, ref ex) => s.push_str(&format!("\\sqrt[{}]{{{}}}", vexpr_to_latex(degree), vexpr_to_latex(ex))),
+			&Func(FuncType::Sqrt, ref ex) => s.push_str(&format!("\\sqrt{{{}}}", vexpr_to_latex(ex))),
+			&Func(FuncType::Abs, ref ex) => s.push_str(&format!("\\left|{}\\right|", vexpr_to_latex(ex))),
+			&Func(FuncType::Fact, ref ex) => s.push_str(&format!("\\left({}\\right)!", vexpr_to_latex(ex))),
+			&Func(func, ref ex) => {
+				let name = match func {
+					FuncType::Sin | FuncType::Cos | FuncType::Tan | FuncType::Sinh | FuncType::Cosh | FuncType::Tanh
+						| FuncType::Ln | FuncType::Min | FuncType::Max => format!("\\{}", func),
+					_ => format!("\\operatorname{{{}}}", func),
+				};
+				s.push_str(&format!("{}\\left({}\\right)", name, vexpr_to_latex(ex)));
+			},
+			&List(ref ex) => s.push_str(&format!("\\left\\{{{}\\right\\}}", vexpr_to_latex(ex))),
+			&Delim(delim, ref ex) => {
+				let (open, close) = match delim {
+					DelimType::Paren  => ("(", ")"),
+					DelimType::Square => ("[", "]"),
+					DelimType::Abs    => ("|", "|"),
+					DelimType::Floor  => ("\\lfloor ", "\\rfloor "),
+					DelimType::Ceil   => ("\\lceil ", "\\rceil "),
+				};
+				s.push_str(&format!("\\left{}{}\\right{}", open, vexpr_to_latex(ex), close));
+			},
+		}
+	}
+	s
+}
+pub fn vexpr_to_latex(ex: &VExprRef) -> String {
+	tokens_to_latex(&ex.borrow().tokens)
+}